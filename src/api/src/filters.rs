@@ -1,3 +1,6 @@
+pub mod api_key;
+pub mod session;
+pub mod token;
 pub mod xtream;
 
 use std::{convert::Infallible, sync::Arc};
@@ -13,6 +16,7 @@ use crate::{
         xtream::{Credentials, Output, XtreamConfig},
         ApiConfiguration,
     },
+    utils::{api_key::ApiKeyUtil, session::SessionManager, token::StreamTokenUtil},
 };
 
 pub fn with_db(db: Arc<DB>) -> impl Filter<Extract = (Arc<DB>,), Error = Infallible> + Clone {
@@ -77,3 +81,21 @@ pub fn with_proxy_handler(
 pub fn with_output() -> impl Filter<Extract = (Output,), Error = Infallible> + Clone {
     any().map(move || Output::Custom)
 }
+
+pub fn with_stream_token_util(
+    stream_token_util: StreamTokenUtil,
+) -> impl Filter<Extract = (StreamTokenUtil,), Error = Infallible> + Clone {
+    any().map(move || stream_token_util.clone())
+}
+
+pub fn with_api_key_util(
+    api_key_util: ApiKeyUtil,
+) -> impl Filter<Extract = (ApiKeyUtil,), Error = Infallible> + Clone {
+    any().map(move || api_key_util.clone())
+}
+
+pub fn with_session_manager(
+    session_manager: SessionManager,
+) -> impl Filter<Extract = (SessionManager,), Error = Infallible> + Clone {
+    any().map(move || session_manager.clone())
+}
@@ -0,0 +1,72 @@
+use std::sync::Arc;
+
+use db::{services::api_key::ApiKeyDBService, DB};
+use log::error;
+use warp::{any, header, hyper::StatusCode, reject::custom, Filter, Rejection};
+
+use crate::{
+    filters::{with_api_key_util, with_db},
+    models::{api_key::ApiKeyScope, Invalid},
+    utils::api_key::ApiKeyUtil,
+};
+
+pub fn api_key_auth(
+    api_key_util: ApiKeyUtil,
+    db: Arc<DB>,
+    scope: ApiKeyScope,
+) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    header::optional::<String>("authorization")
+        .and(any().map(move || scope))
+        .and(with_api_key_util(api_key_util))
+        .and(with_db(db))
+        .and_then(authorize)
+        .untuple_one()
+}
+
+/// Accepts the configured admin key as well as any stored key that grants the scope.
+async fn authorize(
+    authorization: Option<String>,
+    scope: ApiKeyScope,
+    api_key_util: ApiKeyUtil,
+    db: Arc<DB>,
+) -> Result<(), Rejection> {
+    let key = match authorization
+        .as_deref()
+        .and_then(|authorization| authorization.strip_prefix("Bearer "))
+        .map(str::trim)
+    {
+        Some(key) if !key.is_empty() => key,
+        _ => {
+            return Err(custom(Invalid {
+                status_code: StatusCode::UNAUTHORIZED,
+            }))
+        }
+    };
+
+    let key_hash = api_key_util.hash(key);
+
+    if api_key_util.is_admin_key(&key_hash) {
+        return Ok(());
+    }
+
+    let mut api_key_service = ApiKeyDBService::new();
+    api_key_service.initialize_db(db);
+
+    let key_scope = match api_key_service.get_by_hash(&key_hash).await {
+        Ok(api_key) => api_key.and_then(|api_key| api_key.scope.parse::<ApiKeyScope>().ok()),
+        Err(err) => {
+            error!("Failed to authenticate api key: {}", err);
+            None
+        }
+    };
+
+    match key_scope {
+        Some(key_scope) if key_scope.grants(scope) => Ok(()),
+        Some(_) => Err(custom(Invalid {
+            status_code: StatusCode::FORBIDDEN,
+        })),
+        None => Err(custom(Invalid {
+            status_code: StatusCode::UNAUTHORIZED,
+        })),
+    }
+}
@@ -0,0 +1,32 @@
+use std::net::SocketAddr;
+
+use warp::{addr::remote, header, Filter, Rejection};
+
+use crate::models::session::SessionClient;
+
+/// Extracts the address and user agent of the client, preferring the address a reverse proxy
+/// forwarded over the one of the connection.
+pub fn session_client() -> impl Filter<Extract = (SessionClient,), Error = Rejection> + Clone {
+    remote()
+        .and(header::optional::<String>("x-forwarded-for"))
+        .and(header::optional::<String>("x-real-ip"))
+        .and(header::optional::<String>("user-agent"))
+        .map(
+            |addr: Option<SocketAddr>,
+             forwarded_for: Option<String>,
+             real_ip: Option<String>,
+             user_agent: Option<String>| {
+                let forwarded_ip = forwarded_for
+                    .as_deref()
+                    .and_then(|forwarded_for| forwarded_for.split(',').next())
+                    .or(real_ip.as_deref())
+                    .map(|ip| ip.trim().to_string())
+                    .filter(|ip| !ip.is_empty());
+
+                SessionClient {
+                    ip: forwarded_ip.or_else(|| addr.map(|addr| addr.ip().to_string())),
+                    user_agent,
+                }
+            },
+        )
+}
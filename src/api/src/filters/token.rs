@@ -0,0 +1,73 @@
+use std::sync::Arc;
+
+use db::{services::xtream_user::XtreamUserDBService, DB};
+use log::error;
+use serde::Deserialize;
+use warp::{hyper::StatusCode, path::FullPath, query, reject::custom, Filter, Rejection};
+
+use crate::{
+    filters::{
+        with_db, with_stream_token_util, with_xtream_config,
+        xtream::{proxied_account, user_account},
+    },
+    models::{
+        xtream::{XtreamAccount, XtreamConfig},
+        Invalid,
+    },
+    utils::token::StreamTokenUtil,
+};
+
+#[derive(Debug, Deserialize)]
+struct StreamToken {
+    token: Option<String>,
+}
+
+/// Accepts requests with a valid token for their path, as long as the account the token was
+/// issued to is still enabled and unexpired.
+pub fn stream_token_auth(
+    stream_token_util: StreamTokenUtil,
+    xtream_config: XtreamConfig,
+    db: Arc<DB>,
+) -> impl Filter<Extract = (XtreamAccount,), Error = Rejection> + Clone {
+    warp::path::full()
+        .and(query())
+        .and(with_stream_token_util(stream_token_util))
+        .and(with_xtream_config(xtream_config))
+        .and(with_db(db))
+        .and_then(authenticate)
+}
+
+async fn authenticate(
+    path: FullPath,
+    params: StreamToken,
+    stream_token_util: StreamTokenUtil,
+    xtream_config: XtreamConfig,
+    db: Arc<DB>,
+) -> Result<XtreamAccount, Rejection> {
+    let forbidden = || {
+        custom(Invalid {
+            status_code: StatusCode::FORBIDDEN,
+        })
+    };
+
+    let account_id = params
+        .token
+        .and_then(|token| stream_token_util.verify(path.as_str(), &token))
+        .ok_or_else(forbidden)?;
+
+    if account_id == 0 {
+        return Ok(proxied_account(&xtream_config));
+    }
+
+    let mut xtream_user_service = XtreamUserDBService::new();
+    xtream_user_service.initialize_db(db);
+
+    match xtream_user_service.get_active_user(account_id).await {
+        Ok(Some(user)) => user_account(user, &xtream_user_service).await,
+        Ok(None) => Err(forbidden()),
+        Err(err) => {
+            error!("Failed to get xtream user of stream token: {}", err);
+            Err(forbidden())
+        }
+    }
+}
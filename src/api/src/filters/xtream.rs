@@ -1,43 +1,36 @@
+use std::sync::Arc;
+
+use db::{models::XtreamUserModel, services::xtream_user::XtreamUserDBService, DB};
+use log::error;
 use warp::{hyper::StatusCode, path::FullPath, query, reject::custom, Filter, Rejection};
 
 use crate::{
-    filters::with_xtream_config,
+    filters::{with_db, with_xtream_config},
     models::{
-        xtream::{Credentials, XtreamConfig},
+        xtream::{Credentials, XtreamAccount, XtreamConfig},
         Invalid,
     },
 };
 
 pub fn xtream_param_auth(
     xtream_config: XtreamConfig,
-) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    db: Arc<DB>,
+) -> impl Filter<Extract = (XtreamAccount,), Error = Rejection> + Clone {
     query()
         .and(with_xtream_config(xtream_config))
-        .and_then(
-            |credentials: Credentials, xtream_config: XtreamConfig| async move {
-                if credentials.username == xtream_config.xtream_proxied_username
-                    && credentials.password == xtream_config.xtream_proxied_password
-                {
-                    Ok(())
-                } else {
-                    Err(custom(Invalid {
-                        status_code: StatusCode::FORBIDDEN,
-                    }))
-                }
-            },
-        )
-        .untuple_one()
+        .and(with_db(db))
+        .and_then(authenticate)
 }
 
 pub fn xtream_path_auth(
     xtream_config: XtreamConfig,
-) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    db: Arc<DB>,
+) -> impl Filter<Extract = (XtreamAccount,), Error = Rejection> + Clone {
     warp::any()
         .and(warp::path::full())
-        .and(with_xtream_config(xtream_config))
-        .map(|path: FullPath, xtream_config: XtreamConfig| {
+        .map(|path: FullPath| {
             let path = path.as_str();
-            let path_segments = match path.starts_with("/series")
+            let path_segments: Vec<String> = match path.starts_with("/series")
                 || path.starts_with("/movie")
                 || path.starts_with("/live")
             {
@@ -45,25 +38,79 @@ pub fn xtream_path_auth(
                 false => path.split('/').skip(1).take(2).map(String::from).collect(),
             };
 
-            (path_segments, xtream_config, path.to_string())
+            Credentials {
+                username: path_segments.first().cloned().unwrap_or_default(),
+                password: path_segments.last().cloned().unwrap_or_default(),
+            }
         })
-        .and_then(
-            |(credentials, xtream_config, path): (Vec<String>, XtreamConfig, String)| async move {
-                if path.contains("/play") {
-                    return Ok(());
-                }
+        .and(with_xtream_config(xtream_config))
+        .and(with_db(db))
+        .and_then(authenticate)
+}
+
+/// Accepts the configured proxied credentials as well as any enabled, unexpired xtream user.
+async fn authenticate(
+    credentials: Credentials,
+    xtream_config: XtreamConfig,
+    db: Arc<DB>,
+) -> Result<XtreamAccount, Rejection> {
+    if credentials.username == xtream_config.xtream_proxied_username
+        && credentials.password == xtream_config.xtream_proxied_password
+    {
+        return Ok(proxied_account(&xtream_config));
+    }
+
+    let mut xtream_user_service = XtreamUserDBService::new();
+    xtream_user_service.initialize_db(db);
+
+    let user = match xtream_user_service
+        .authenticate(&credentials.username, &credentials.password)
+        .await
+    {
+        Ok(Some(user)) => user,
+        Ok(None) => {
+            return Err(custom(Invalid {
+                status_code: StatusCode::FORBIDDEN,
+            }))
+        }
+        Err(err) => {
+            error!("Failed to authenticate xtream user: {}", err);
+            return Err(custom(Invalid {
+                status_code: StatusCode::FORBIDDEN,
+            }));
+        }
+    };
+
+    user_account(user, &xtream_user_service).await
+}
+
+/// The account of the configured proxied credentials, which isn't limited to any groups.
+pub fn proxied_account(xtream_config: &XtreamConfig) -> XtreamAccount {
+    XtreamAccount {
+        username: xtream_config.xtream_proxied_username.clone(),
+        password: xtream_config.xtream_proxied_password.clone(),
+        user: None,
+        groups: vec![],
+    }
+}
 
-                if credentials.first().unwrap().to_owned() == xtream_config.xtream_proxied_username
-                    && credentials.last().unwrap().to_owned()
-                        == xtream_config.xtream_proxied_password
-                {
-                    Ok(())
-                } else {
-                    Err(custom(Invalid {
-                        status_code: StatusCode::FORBIDDEN,
-                    }))
-                }
-            },
-        )
-        .untuple_one()
+/// The account of an xtream user, along with the groups it is limited to.
+pub async fn user_account(
+    user: XtreamUserModel,
+    xtream_user_service: &XtreamUserDBService,
+) -> Result<XtreamAccount, Rejection> {
+    match xtream_user_service.get_groups(user.id).await {
+        Ok(groups) => Ok(XtreamAccount {
+            username: user.username.clone(),
+            password: user.password.clone(),
+            user: Some(user),
+            groups,
+        }),
+        Err(err) => {
+            error!("Failed to get groups of xtream user: {}", err);
+            Err(custom(Invalid {
+                status_code: StatusCode::FORBIDDEN,
+            }))
+        }
+    }
 }
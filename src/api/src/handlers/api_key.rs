@@ -0,0 +1,88 @@
+use std::{convert::Infallible, sync::Arc};
+
+use db::{models::ApiKeyRequest, services::api_key::ApiKeyDBService, DB};
+use log::{error, info};
+use reqwest::StatusCode;
+use warp::{
+    reply::{json, with_status, Response},
+    Reply,
+};
+
+use crate::{
+    models::{
+        api_key::{ApiKeyCreateRequest, ApiKeyCreated},
+        error::ApiError,
+    },
+    utils::api_key::ApiKeyUtil,
+};
+
+pub async fn get_api_keys(db: Arc<DB>) -> Result<Response, Infallible> {
+    let mut api_key_service = ApiKeyDBService::new();
+    api_key_service.initialize_db(db);
+
+    let res = match api_key_service.get_keys().await {
+        Ok(keys) => json(&keys).into_response(),
+        Err(err) => {
+            error!("{}", err);
+            with_status(json(&ApiError {}), StatusCode::INTERNAL_SERVER_ERROR).into_response()
+        }
+    };
+
+    Ok(res)
+}
+
+pub async fn create_api_key(
+    req: ApiKeyCreateRequest,
+    api_key_util: ApiKeyUtil,
+    db: Arc<DB>,
+) -> Result<Response, Infallible> {
+    let mut api_key_service = ApiKeyDBService::new();
+    api_key_service.initialize_db(db);
+
+    let key = api_key_util.generate();
+
+    let res = match api_key_service
+        .create_key(ApiKeyRequest {
+            name: req.name.clone(),
+            key_hash: api_key_util.hash(&key),
+            scope: req.scope.to_string(),
+        })
+        .await
+    {
+        Ok(id) => {
+            info!("Successfully created api key {}", req.name);
+            json(&ApiKeyCreated {
+                id,
+                name: req.name,
+                scope: req.scope,
+                key,
+            })
+            .into_response()
+        }
+        Err(err) => {
+            error!("{}", err);
+            with_status(json(&ApiError {}), StatusCode::BAD_REQUEST).into_response()
+        }
+    };
+
+    Ok(res)
+}
+
+pub async fn delete_api_key(id: u64, db: Arc<DB>) -> Result<StatusCode, Infallible> {
+    let mut api_key_service = ApiKeyDBService::new();
+    api_key_service.initialize_db(db);
+
+    let res = match api_key_service.delete_key(id).await {
+        Ok(0) => StatusCode::NOT_FOUND,
+        Ok(_) => {
+            info!("Successfully deleted api key");
+            StatusCode::OK
+        }
+        Err(err) => {
+            error!("Failed to delete api key\n{}", err);
+            StatusCode::BAD_REQUEST
+        }
+    };
+
+    Ok(res)
+}
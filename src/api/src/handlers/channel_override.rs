@@ -0,0 +1,67 @@
+use std::{convert::Infallible, sync::Arc};
+
+use db::{
+    models::ChannelOverrideRequest, services::channel_override::ChannelOverrideDBService, DB,
+};
+use log::{error, info};
+use reqwest::StatusCode;
+use warp::{
+    reply::{json, with_status, Response},
+    Reply,
+};
+
+use crate::models::error::ApiError;
+
+pub async fn get_overrides(db: Arc<DB>) -> Result<Response, Infallible> {
+    let mut channel_override_service = ChannelOverrideDBService::new();
+    channel_override_service.initialize_db(db);
+
+    let res = match channel_override_service.get_overrides().await {
+        Ok(overrides) => json(&overrides).into_response(),
+        Err(err) => {
+            error!("{}", err);
+            with_status(json(&ApiError {}), StatusCode::INTERNAL_SERVER_ERROR).into_response()
+        }
+    };
+
+    Ok(res)
+}
+
+pub async fn create_override(
+    channel_override: ChannelOverrideRequest,
+    db: Arc<DB>,
+) -> Result<Response, Infallible> {
+    let mut channel_override_service = ChannelOverrideDBService::new();
+    channel_override_service.initialize_db(db);
+
+    let res = match channel_override_service
+        .create_override(channel_override)
+        .await
+    {
+        Ok(id) => json(&id).into_response(),
+        Err(err) => {
+            error!("{}", err);
+            with_status(json(&ApiError {}), StatusCode::BAD_REQUEST).into_response()
+        }
+    };
+
+    Ok(res)
+}
+
+pub async fn delete_override(id: u64, db: Arc<DB>) -> Result<StatusCode, Infallible> {
+    let mut channel_override_service = ChannelOverrideDBService::new();
+    channel_override_service.initialize_db(db);
+
+    let res = match channel_override_service.delete_override(id).await {
+        Ok(_) => {
+            info!("Successfully deleted channel override");
+            StatusCode::OK
+        }
+        Err(err) => {
+            error!("Failed to delete channel override\n{}", err);
+            StatusCode::BAD_REQUEST
+        }
+    };
+
+    Ok(res)
+}
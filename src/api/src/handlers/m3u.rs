@@ -6,10 +6,9 @@ use std::{
 };
 
 use db::{services::provider::ProviderDBService, DB};
-use iptv::m3u::builder::create_m3u_file;
+use iptv::{m3u::builder::create_m3u_file, models::ProviderConfig};
 use log::{debug, error};
-use tokio::fs::{read_dir, DirEntry, File};
-use tokio_util::io::ReaderStream;
+use tokio::fs::{read_dir, read_to_string, DirEntry, File};
 
 use warp::{
     hyper::{Body, StatusCode},
@@ -17,28 +16,25 @@ use warp::{
     Reply,
 };
 
-use crate::models::{provider::CreateM3uApiModel, xtream::Output};
+use crate::{
+    models::{
+        provider::CreateM3uApiModel,
+        xtream::{Output, XtreamAccount},
+        ApiConfiguration,
+    },
+    utils::playlist::PlaylistUtil,
+};
 
-pub async fn get_latest_m3u_file(output: Output) -> Result<Response, Infallible> {
+/// The latest m3u file, as composed for the requesting account.
+pub async fn get_latest_m3u_file(
+    output: Output,
+    account: XtreamAccount,
+    config: ApiConfiguration,
+) -> Result<Response, Infallible> {
     let path = get_latest_m3u_path(output).await;
 
-    let response = match File::open(path).await {
-        Ok(file) => {
-            let stream = ReaderStream::new(file);
-
-            let body = Body::wrap_stream(stream);
-
-            let response = warp::hyper::Response::builder()
-                .status(200)
-                .header(
-                    "Content-Disposition",
-                    "attachement; filename = \"playlist.m3u\"",
-                )
-                .body(body)
-                .unwrap();
-
-            response
-        }
+    let response = match read_to_string(path).await {
+        Ok(playlist) => compose_m3u_response(&playlist, &account, &config),
         Err(_) => {
             debug!("No m3u file available");
             warp::hyper::Response::builder()
@@ -86,25 +82,65 @@ async fn get_latest_m3u_path(output: Output) -> PathBuf {
     freshest_file
 }
 
-pub async fn serve_file_by_file_name(file_name: String) -> Result<Response, Infallible> {
-    let path = Path::new(file_name.as_str());
-    let file = tokio::fs::File::open(path)
-        .await
-        .expect("Could not open file from disc");
+/// Serves one of the generated m3u files in the output directory by its name. Any other file
+/// is answered with `404 Not Found`.
+pub async fn serve_file_by_file_name(
+    file_name: String,
+    account: XtreamAccount,
+    config: ApiConfiguration,
+) -> Result<Response, Infallible> {
+    if !is_generated_m3u_file_name(&file_name) {
+        debug!("Refused to serve file {}", file_name);
+
+        return Ok(warp::hyper::Response::builder()
+            .status(404)
+            .body(Body::default())
+            .unwrap_or_default());
+    }
+
+    let path = Path::new(".").join(file_name);
 
-    let stream = ReaderStream::new(file);
-    let body = Body::wrap_stream(stream);
+    let response = match read_to_string(path).await {
+        Ok(playlist) => compose_m3u_response(&playlist, &account, &config),
+        Err(_) => warp::hyper::Response::builder()
+            .status(404)
+            .body(Body::default())
+            .unwrap_or_default(),
+    };
+
+    Ok(response)
+}
 
-    let response = warp::hyper::Response::builder()
+/// Generated m3u files are named `{output}_{timestamp}_{date}.m3u` and live directly in the
+/// output directory.
+fn is_generated_m3u_file_name(file_name: &str) -> bool {
+    let is_plain_name = !file_name.is_empty()
+        && !file_name.starts_with('.')
+        && !file_name.contains("..")
+        && !file_name.contains(['/', '\\']);
+
+    let is_generated = [Output::M3u8, Output::Ts, Output::Custom]
+        .iter()
+        .any(|output| file_name.starts_with(&format!("{}_", output)));
+
+    is_plain_name && is_generated && file_name.ends_with(".m3u")
+}
+
+fn compose_m3u_response(
+    playlist: &str,
+    account: &XtreamAccount,
+    config: &ApiConfiguration,
+) -> Response {
+    let playlist = PlaylistUtil::new(config).compose_account_playlist(playlist, account);
+
+    warp::hyper::Response::builder()
         .status(200)
         .header(
             "Content-Disposition",
             "attachement; filename = \"playlist.m3u\"",
         )
-        .body(body)
-        .unwrap_or_default();
-
-    Ok(response)
+        .body(Body::from(playlist))
+        .unwrap_or_default()
 }
 
 pub async fn m3u_file_exist(output: Output) -> Result<Response, Infallible> {
@@ -134,7 +170,9 @@ pub async fn create_m3u(req: CreateM3uApiModel, db: Arc<DB>) -> Result<Response,
     provider.initialize_db(db);
 
     if let Ok(provider) = provider.get_provider(req.provider_id).await {
-        if let Err(err) = create_m3u_file(provider, req.iptv_config).await {
+        if let Err(err) =
+            create_m3u_file(vec![(ProviderConfig::default(), provider)], req.iptv_config).await
+        {
             error!(".m3u file created failed with {}", err);
             return Ok(error);
         }
@@ -144,3 +182,37 @@ pub async fn create_m3u(req: CreateM3uApiModel, db: Arc<DB>) -> Result<Response,
         Ok(error)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_generated_m3u_file_names() {
+        for file_name in [
+            "m3u8_1700000000_2023-11-14_22-13-20.m3u",
+            "ts_1700000000_2023-11-14_22-13-20.m3u",
+            "custom_1700000000_2023-11-14_22-13-20.m3u",
+        ] {
+            assert!(is_generated_m3u_file_name(file_name), "{}", file_name);
+        }
+    }
+
+    #[test]
+    fn rejects_other_file_names() {
+        for file_name in [
+            "",
+            ".env",
+            ".ts_1700000000.m3u",
+            "../ts_1700000000.m3u",
+            "ts_..m3u",
+            "ts_1700000000/../../etc/passwd.m3u",
+            "ts_1700000000\\..\\secret.m3u",
+            "ts_1700000000.m3u8",
+            "Cargo.toml",
+            "playlist.m3u",
+        ] {
+            assert!(!is_generated_m3u_file_name(file_name), "{}", file_name);
+        }
+    }
+}
@@ -10,11 +10,15 @@ use warp::{
 
 use crate::models::{error::ErrorMessage, Invalid};
 
+pub mod api_key;
+pub mod channel_override;
 pub mod m3u;
 pub mod provider;
 pub mod proxy;
 pub mod root;
+pub mod session;
 pub mod xtream;
+pub mod xtream_user;
 
 pub async fn handle_rejection(err: Rejection) -> Result<impl Reply, Infallible> {
     let code;
@@ -27,23 +31,20 @@ pub async fn handle_rejection(err: Rejection) -> Result<impl Reply, Infallible>
         if invalid.status_code == StatusCode::FORBIDDEN {
             code = StatusCode::FORBIDDEN;
             message = "FORBIDDEN";
+        } else if invalid.status_code == StatusCode::UNAUTHORIZED {
+            code = StatusCode::UNAUTHORIZED;
+            message = "UNAUTHORIZED";
         } else {
             code = StatusCode::BAD_REQUEST;
             message = "BAD_REQUEST";
         }
     } else if let Some(e) = err.find::<BodyDeserializeError>() {
         message = match e.source() {
-            Some(cause) => {
-                if cause.to_string().contains("denom") {
-                    "FIELD_ERROR: denom"
-                } else {
-                    "BAD_REQUEST"
-                }
-            }
-            None => "BAD_REQUEST",
+            Some(cause) if cause.to_string().contains("denom") => "FIELD_ERROR: denom",
+            _ => "BAD_REQUEST",
         };
         code = StatusCode::BAD_REQUEST;
-    } else if let Some(_) = err.find::<MethodNotAllowed>() {
+    } else if err.find::<MethodNotAllowed>().is_some() {
         code = StatusCode::METHOD_NOT_ALLOWED;
         message = "METHOD_NOT_ALLOWED";
     } else {
@@ -55,6 +55,25 @@ pub async fn get_provider(id: u64, db: Arc<DB>) -> Result<impl Reply, Infallible
     Ok(json(&provider.unwrap()).into_response())
 }
 
+pub async fn get_provider_report(id: u64, db: Arc<DB>) -> Result<Response, Infallible> {
+    let mut provider_service = ProviderService::new();
+    provider_service.initialize_db(db);
+
+    let res = match provider_service.get_report(id).await {
+        Ok(res) => res,
+        Err(err) if matches!(err.downcast_ref(), Some(sqlx::Error::RowNotFound)) => {
+            error!("{}", err);
+            with_status("NOT FOUND", StatusCode::NOT_FOUND).into_response()
+        }
+        Err(err) => {
+            error!("{}", err);
+            with_status(json(&ApiError {}), StatusCode::INTERNAL_SERVER_ERROR).into_response()
+        }
+    };
+
+    Ok(res)
+}
+
 pub async fn get_provider_entries_by_url(
     url: &str,
     db: Arc<DB>,
@@ -1,15 +1,23 @@
 use std::{convert::Infallible, sync::Arc};
 
-use ::warp::http::HeaderMap;
+use ::warp::http::{HeaderMap, Method};
 use db::DB;
 use log::error;
 use rest_client::RestClient;
 use warp::hyper::{Body, Response};
 
 use crate::{
-    models::Path,
+    models::{
+        error::{ConnectionLimitError, UserConnectionLimitError},
+        session::SessionClient,
+        xtream::XtreamAccount,
+        ApiConfiguration, Path,
+    },
     services::proxy::ProxyService,
-    utils::{proxy::ProxyUtil, response::ResponseUtil},
+    utils::{
+        connection::ConnectionManager, hls::HlsUtil, proxy::ProxyUtil, response::ResponseUtil,
+        segmenter::SegmenterManager, session::SessionManager,
+    },
 };
 
 #[derive(Clone)]
@@ -19,9 +27,25 @@ pub struct ProxyHandler {
 }
 
 impl ProxyHandler {
-    pub fn new(db: Arc<DB>, client: Arc<RestClient>) -> Self {
+    pub fn new(
+        config: ApiConfiguration,
+        db: Arc<DB>,
+        client: Arc<RestClient>,
+        connection_manager: ConnectionManager,
+        hls_util: HlsUtil,
+        segmenter_manager: SegmenterManager,
+        session_manager: SessionManager,
+    ) -> Self {
         ProxyHandler {
-            proxy_service: ProxyService::new(db.clone(), client.clone()),
+            proxy_service: ProxyService::new(
+                config,
+                db.clone(),
+                client.clone(),
+                connection_manager,
+                hls_util,
+                segmenter_manager,
+                session_manager,
+            ),
             proxy_util: ProxyUtil::new(ResponseUtil::new(), db, client),
         }
     }
@@ -29,10 +53,35 @@ impl ProxyHandler {
     pub async fn proxy_stream(
         self,
         path: Path,
+        account: XtreamAccount,
+        method: Method,
         headers: HeaderMap,
+        client: SessionClient,
     ) -> Result<Response<Body>, Infallible> {
-        let res = match self.proxy_service.proxy_stream(path.clone(), headers).await {
+        let res = match method {
+            Method::HEAD => {
+                self.proxy_service
+                    .proxy_stream_head(path.clone(), headers)
+                    .await
+            }
+            _ => {
+                self.proxy_service
+                    .proxy_stream(path.clone(), headers, account, client)
+                    .await
+            }
+        };
+
+        let res = match res {
             Ok(res) => res,
+            Err(err)
+                if err.is::<ConnectionLimitError>() || err.is::<UserConnectionLimitError>() =>
+            {
+                error!("Failed to proxy stream with id {}, error: {}", path.id, err);
+                Response::builder()
+                    .status(503)
+                    .body(Body::from(err.to_string()))
+                    .unwrap_or_default()
+            }
             Err(err) => {
                 error!("Failed to proxy stream with id {}, error: {}", path.id, err);
                 Response::builder()
@@ -44,6 +93,43 @@ impl ProxyHandler {
         Ok(res)
     }
 
+    pub async fn proxy_stream_segment(
+        self,
+        id: u64,
+        segment: String,
+        account: XtreamAccount,
+        client: SessionClient,
+    ) -> Result<Response<Body>, Infallible> {
+        let res = match self
+            .proxy_service
+            .proxy_stream_segment(id, segment.clone(), account, client)
+            .await
+        {
+            Ok(res) => res,
+            Err(err) if err.is::<UserConnectionLimitError>() => {
+                error!(
+                    "Failed to proxy segment {} of stream {}, error: {}",
+                    segment, id, err
+                );
+                Response::builder()
+                    .status(503)
+                    .body(Body::from(err.to_string()))
+                    .unwrap_or_default()
+            }
+            Err(err) => {
+                error!(
+                    "Failed to proxy segment {} of stream {}, error: {}",
+                    segment, id, err
+                );
+                Response::builder()
+                    .status(500)
+                    .body(Body::from(format!("Error on segment proxy {}", id)))
+                    .unwrap_or_default()
+            }
+        };
+        Ok(res)
+    }
+
     pub async fn proxy_attr(self, id: u64) -> Result<Response<Body>, Infallible> {
         let res = match self.proxy_util.proxy_attribute(id).await {
             Ok(res) => res,
@@ -61,16 +147,28 @@ impl ProxyHandler {
 
     pub async fn proxy_hls(
         self,
-        path: Path,
+        token: String,
         headers: HeaderMap,
+        account: XtreamAccount,
     ) -> Result<Response<Body>, Infallible> {
-        let res = match self.proxy_service.proxy_hls(path.clone(), headers).await {
+        let res = match self
+            .proxy_service
+            .proxy_hls(token.clone(), headers, account)
+            .await
+        {
             Ok(res) => res,
+            Err(err) if err.is::<UserConnectionLimitError>() => {
+                error!("Failed to proxy hls uri {}, error: {}", token, err);
+                Response::builder()
+                    .status(503)
+                    .body(Body::from(err.to_string()))
+                    .unwrap_or_default()
+            }
             Err(err) => {
-                error!("Failed to proxy stream with id {}, error: {}", path.id, err);
+                error!("Failed to proxy hls uri {}, error: {}", token, err);
                 Response::builder()
                     .status(500)
-                    .body(Body::from(format!("Error on stream proxy {}", path.id)))
+                    .body(Body::from(format!("Error on hls proxy {}", token)))
                     .unwrap_or_default()
             }
         };
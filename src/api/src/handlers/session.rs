@@ -0,0 +1,22 @@
+use std::convert::Infallible;
+
+use reqwest::StatusCode;
+use warp::reply::{json, Json};
+
+use crate::utils::session::SessionManager;
+
+pub async fn get_sessions(session_manager: SessionManager) -> Result<Json, Infallible> {
+    Ok(json(&session_manager.get_sessions()))
+}
+
+pub async fn delete_session(
+    id: u64,
+    session_manager: SessionManager,
+) -> Result<StatusCode, Infallible> {
+    let res = match session_manager.cancel(id) {
+        true => StatusCode::OK,
+        false => StatusCode::NOT_FOUND,
+    };
+
+    Ok(res)
+}
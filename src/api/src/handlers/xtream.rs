@@ -4,7 +4,7 @@ use db::DB;
 use log::error;
 use rest_client::RestClient;
 use warp::{
-    http::HeaderMap,
+    http::{HeaderMap, Method},
     hyper::{Body, Response, StatusCode},
     path::FullPath,
     reply::with_status,
@@ -13,10 +13,13 @@ use warp::{
 
 use crate::{
     models::{
-        xtream::{Action, OptionalParams, TypeOutput},
+        error::{ConnectionLimitError, EntitlementError, UserConnectionLimitError},
+        session::SessionClient,
+        xtream::{Action, OptionalParams, TypeOutput, XtreamAccount},
         ApiConfiguration, Path,
     },
     services::xtream::XtreamService,
+    utils::{connection::ConnectionManager, hls::HlsUtil, session::SessionManager},
 };
 
 #[derive(Clone)]
@@ -25,19 +28,59 @@ pub struct XtreamHandler {
 }
 
 impl XtreamHandler {
-    pub fn new(config: ApiConfiguration, db: Arc<DB>, client: Arc<RestClient>) -> Self {
+    pub fn new(
+        config: ApiConfiguration,
+        db: Arc<DB>,
+        client: Arc<RestClient>,
+        connection_manager: ConnectionManager,
+        session_manager: SessionManager,
+        hls_util: HlsUtil,
+    ) -> Self {
         XtreamHandler {
-            xtream_service: XtreamService::new(config, db, client),
+            xtream_service: XtreamService::new(
+                config,
+                db,
+                client,
+                connection_manager,
+                session_manager,
+                hls_util,
+            ),
         }
     }
 
     pub async fn stream(
         self,
         path: Path,
+        account: XtreamAccount,
+        method: Method,
         headers: HeaderMap,
+        client: SessionClient,
     ) -> Result<Response<Body>, Infallible> {
-        let res = match self.xtream_service.proxy_stream(path, headers).await {
+        let res = match method {
+            Method::HEAD => {
+                self.xtream_service
+                    .proxy_stream_head(path, headers, account)
+                    .await
+            }
+            _ => {
+                self.xtream_service
+                    .proxy_stream(path, headers, account, client)
+                    .await
+            }
+        };
+
+        let res = match res {
             Ok(res) => res,
+            Err(err) if err.is::<EntitlementError>() => {
+                error!("Failed to proxy xtream request: {}", err);
+                with_status("FORBIDDEN", StatusCode::FORBIDDEN).into_response()
+            }
+            Err(err)
+                if err.is::<ConnectionLimitError>() || err.is::<UserConnectionLimitError>() =>
+            {
+                error!("Failed to proxy xtream request: {}", err);
+                with_status(err.to_string(), StatusCode::SERVICE_UNAVAILABLE).into_response()
+            }
             Err(err) => {
                 error!("Failed to proxy xtream request: {}", err);
                 with_status("INTERNAL SERVER ERROR", StatusCode::INTERNAL_SERVER_ERROR)
@@ -48,8 +91,16 @@ impl XtreamHandler {
         Ok(res)
     }
 
-    pub async fn xmltv(self, path: FullPath) -> Result<Response<Body>, Infallible> {
-        let res = match self.xtream_service.proxy_xmltv(path.as_str()).await {
+    pub async fn xmltv(
+        self,
+        path: FullPath,
+        account: XtreamAccount,
+    ) -> Result<Response<Body>, Infallible> {
+        let res = match self
+            .xtream_service
+            .proxy_xmltv(path.as_str(), &account)
+            .await
+        {
             Ok(res) => res,
             Err(err) => {
                 error!("Failed to proxy xmltv {}", err);
@@ -61,11 +112,33 @@ impl XtreamHandler {
         Ok(res)
     }
 
+    pub async fn epg(self, account: XtreamAccount) -> Result<Response<Body>, Infallible> {
+        let res = match self
+            .xtream_service
+            .proxy_xmltv("/xmltv.php", &account)
+            .await
+        {
+            Ok(res) => res,
+            Err(err) => {
+                error!("Failed to proxy epg {}", err);
+                with_status("INTERNAL SERVER ERROR", StatusCode::INTERNAL_SERVER_ERROR)
+                    .into_response()
+            }
+        };
+
+        Ok(res)
+    }
+
     pub async fn get_type_output(
         self,
         type_output: TypeOutput,
+        account: XtreamAccount,
     ) -> Result<Response<Body>, Infallible> {
-        let res = match self.xtream_service.proxy_type_output(type_output).await {
+        let res = match self
+            .xtream_service
+            .proxy_type_output(type_output, account)
+            .await
+        {
             Ok(res) => res,
             Err(err) => {
                 error!("Failed to proxy get_type_output: {}", err);
@@ -82,13 +155,18 @@ impl XtreamHandler {
         action: Action,
         optional_params: OptionalParams,
         path: FullPath,
+        account: XtreamAccount,
     ) -> Result<Response<Body>, Infallible> {
         let res = match self
             .xtream_service
-            .proxy_action(path.as_str(), action, optional_params)
+            .proxy_action(path.as_str(), action, optional_params, account)
             .await
         {
             Ok(res) => res,
+            Err(err) if err.is::<EntitlementError>() => {
+                error!("Failed to proxy player_api_action request: {}", err);
+                with_status("FORBIDDEN", StatusCode::FORBIDDEN).into_response()
+            }
             Err(err) => {
                 error!("Failed to proxy player_api_action request: {}", err);
                 with_status("INTERNAL SERVER ERROR", StatusCode::INTERNAL_SERVER_ERROR)
@@ -99,8 +177,16 @@ impl XtreamHandler {
         Ok(res)
     }
 
-    pub async fn player_api_login(self, path: FullPath) -> Result<Response<Body>, Infallible> {
-        let res = match self.xtream_service.proxy_login(path.as_str()).await {
+    pub async fn player_api_login(
+        self,
+        path: FullPath,
+        account: XtreamAccount,
+    ) -> Result<Response<Body>, Infallible> {
+        let res = match self
+            .xtream_service
+            .proxy_login(path.as_str(), account)
+            .await
+        {
             Ok(res) => res,
             Err(err) => {
                 error!("Failed to proxy player_api_login request: {}", err);
@@ -0,0 +1,122 @@
+use std::{convert::Infallible, sync::Arc};
+
+use db::{models::XtreamUserRequest, services::xtream_user::XtreamUserDBService, DB};
+use log::{error, info};
+use reqwest::StatusCode;
+use warp::{
+    reply::{json, with_status, Response},
+    Reply,
+};
+
+use crate::models::error::ApiError;
+
+pub async fn get_users(db: Arc<DB>) -> Result<Response, Infallible> {
+    let mut xtream_user_service = XtreamUserDBService::new();
+    xtream_user_service.initialize_db(db);
+
+    let res = match xtream_user_service.get_users().await {
+        Ok(users) => json(&users).into_response(),
+        Err(err) => {
+            error!("{}", err);
+            with_status(json(&ApiError {}), StatusCode::INTERNAL_SERVER_ERROR).into_response()
+        }
+    };
+
+    Ok(res)
+}
+
+pub async fn create_user(
+    xtream_user: XtreamUserRequest,
+    db: Arc<DB>,
+) -> Result<Response, Infallible> {
+    let mut xtream_user_service = XtreamUserDBService::new();
+    xtream_user_service.initialize_db(db);
+
+    let res = match xtream_user_service.create_user(xtream_user).await {
+        Ok(id) => json(&id).into_response(),
+        Err(err) => {
+            error!("{}", err);
+            with_status(json(&ApiError {}), StatusCode::BAD_REQUEST).into_response()
+        }
+    };
+
+    Ok(res)
+}
+
+pub async fn update_user(
+    id: u64,
+    xtream_user: XtreamUserRequest,
+    db: Arc<DB>,
+) -> Result<StatusCode, Infallible> {
+    let mut xtream_user_service = XtreamUserDBService::new();
+    xtream_user_service.initialize_db(db);
+
+    let res = match xtream_user_service.update_user(id, xtream_user).await {
+        Ok(0) => StatusCode::NOT_FOUND,
+        Ok(_) => {
+            info!("Successfully updated xtream user");
+            StatusCode::OK
+        }
+        Err(err) => {
+            error!("Failed to update xtream user\n{}", err);
+            StatusCode::BAD_REQUEST
+        }
+    };
+
+    Ok(res)
+}
+
+pub async fn delete_user(id: u64, db: Arc<DB>) -> Result<StatusCode, Infallible> {
+    let mut xtream_user_service = XtreamUserDBService::new();
+    xtream_user_service.initialize_db(db);
+
+    let res = match xtream_user_service.delete_user(id).await {
+        Ok(_) => {
+            info!("Successfully deleted xtream user");
+            StatusCode::OK
+        }
+        Err(err) => {
+            error!("Failed to delete xtream user\n{}", err);
+            StatusCode::BAD_REQUEST
+        }
+    };
+
+    Ok(res)
+}
+
+pub async fn get_user_groups(id: u64, db: Arc<DB>) -> Result<Response, Infallible> {
+    let mut xtream_user_service = XtreamUserDBService::new();
+    xtream_user_service.initialize_db(db);
+
+    let res = match xtream_user_service.get_groups(id).await {
+        Ok(groups) => json(&groups).into_response(),
+        Err(err) => {
+            error!("{}", err);
+            with_status(json(&ApiError {}), StatusCode::INTERNAL_SERVER_ERROR).into_response()
+        }
+    };
+
+    Ok(res)
+}
+
+pub async fn set_user_groups(
+    id: u64,
+    groups: Vec<String>,
+    db: Arc<DB>,
+) -> Result<StatusCode, Infallible> {
+    let mut xtream_user_service = XtreamUserDBService::new();
+    xtream_user_service.initialize_db(db);
+
+    let res = match xtream_user_service.set_groups(id, groups).await {
+        Ok(_) => {
+            info!("Successfully updated xtream user groups");
+            StatusCode::OK
+        }
+        Err(err) => {
+            error!("Failed to update xtream user groups\n{}", err);
+            StatusCode::BAD_REQUEST
+        }
+    };
+
+    Ok(res)
+}
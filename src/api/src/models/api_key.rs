@@ -0,0 +1,66 @@
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumString};
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Display, EnumString, Serialize, Deserialize)]
+#[strum(serialize_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum ApiKeyScope {
+    Read,
+    Admin,
+}
+
+impl ApiKeyScope {
+    /// Admin keys may also use every read-only route.
+    pub fn grants(&self, scope: ApiKeyScope) -> bool {
+        *self == ApiKeyScope::Admin || *self == scope
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ApiKeyCreateRequest {
+    pub name: String,
+    pub scope: ApiKeyScope,
+}
+
+/// Returned once on creation, only the hash of the key is stored.
+#[derive(Debug, Clone, Serialize)]
+pub struct ApiKeyCreated {
+    pub id: u64,
+    pub name: String,
+    pub scope: ApiKeyScope,
+    pub key: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    #[test]
+    fn admin_scope_grants_every_scope() {
+        assert!(ApiKeyScope::Admin.grants(ApiKeyScope::Admin));
+        assert!(ApiKeyScope::Admin.grants(ApiKeyScope::Read));
+    }
+
+    #[test]
+    fn read_scope_only_grants_read() {
+        assert!(ApiKeyScope::Read.grants(ApiKeyScope::Read));
+        assert!(!ApiKeyScope::Read.grants(ApiKeyScope::Admin));
+    }
+
+    #[test]
+    fn scope_is_stored_and_parsed_in_snake_case() {
+        assert_eq!(ApiKeyScope::Admin.to_string(), "admin");
+        assert_eq!(ApiKeyScope::from_str("read").unwrap(), ApiKeyScope::Read);
+        assert!(ApiKeyScope::from_str("write").is_err());
+
+        let request: ApiKeyCreateRequest =
+            serde_json::from_str(r#"{"name": "grafana", "scope": "read"}"#).unwrap();
+        assert_eq!(request.scope, ApiKeyScope::Read);
+        assert!(
+            serde_json::from_str::<ApiKeyCreateRequest>(r#"{"name": "x", "scope": "root"}"#)
+                .is_err()
+        );
+    }
+}
@@ -9,3 +9,53 @@ pub struct ErrorMessage {
     pub(crate) code: u16,
     pub(crate) message: String,
 }
+
+#[derive(Debug)]
+pub struct ConnectionLimitError {
+    pub provider: String,
+    pub max_connections: u32,
+}
+
+impl std::fmt::Display for ConnectionLimitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Connection limit of {} reached for provider {}",
+            self.max_connections, self.provider
+        )
+    }
+}
+
+impl std::error::Error for ConnectionLimitError {}
+
+#[derive(Debug)]
+pub struct UserConnectionLimitError {
+    pub username: String,
+    pub max_connections: u32,
+}
+
+impl std::fmt::Display for UserConnectionLimitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Connection limit of {} reached for user {}",
+            self.max_connections, self.username
+        )
+    }
+}
+
+impl std::error::Error for UserConnectionLimitError {}
+
+#[derive(Debug)]
+pub struct EntitlementError {
+    pub username: String,
+    pub id: String,
+}
+
+impl std::fmt::Display for EntitlementError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "User {} is not entitled to {}", self.username, self.id)
+    }
+}
+
+impl std::error::Error for EntitlementError {}
@@ -1,11 +1,14 @@
-use reqwest::{header::HeaderMap, Url};
+use iptv::models::{FilterRules, ProviderConfig};
+use reqwest::header::HeaderMap;
 use serde::{Deserialize, Serialize};
 use warp::{hyper::StatusCode, reject::Reject};
 
 use self::xtream::XtreamConfig;
 
+pub mod api_key;
 pub mod error;
 pub mod provider;
+pub mod session;
 pub mod xtream;
 
 #[derive(Debug)]
@@ -24,13 +27,48 @@ pub struct ResponseData<T> {
     pub status_code: StatusCode,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Default, PartialEq)]
 pub struct ApiConfiguration {
-    pub m3u_url: Url,
+    pub providers: Vec<ProviderConfig>,
     pub group_excludes: Vec<String>,
+    pub group_includes: Vec<String>,
+    pub filter_rules: FilterRules,
+    pub connection_queue_timeout: u64,
+    pub stream_token_secret: String,
+    pub stream_token_ttl: u64,
+    pub admin_api_key: Option<String>,
     pub xtream: XtreamConfig,
 }
 
+impl ApiConfiguration {
+    pub fn get_provider_by_slot(&self, slot: u64) -> Option<&ProviderConfig> {
+        self.providers.iter().find(|provider| provider.slot == slot)
+    }
+
+    pub fn get_provider_by_source(&self, source: &str) -> Option<&ProviderConfig> {
+        self.providers
+            .iter()
+            .find(|provider| provider.source == source)
+    }
+
+    pub fn xtream_providers(&self) -> Vec<&ProviderConfig> {
+        let mut providers: Vec<&ProviderConfig> = self
+            .providers
+            .iter()
+            .filter(|provider| {
+                provider
+                    .xtream
+                    .as_ref()
+                    .is_some_and(|xtream| xtream.enabled || provider.slot == 0)
+            })
+            .collect();
+
+        providers.sort_by_key(|provider| std::cmp::Reverse(provider.priority));
+
+        providers
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct Path {
     pub segment1: Option<String>,
@@ -1,5 +1,6 @@
-use iptv::models::IptvConfiguration;
-use serde::Deserialize;
+use chrono::NaiveDateTime;
+use iptv::models::{IptvConfiguration, ParseReport};
+use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct CreateProviderRequestApiModel {
@@ -14,3 +15,13 @@ pub struct CreateM3uApiModel {
     pub proxy_domain: String,
     pub iptv_config: IptvConfiguration,
 }
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ParseReportApiModel {
+    pub id: u64,
+    pub provider_id: u64,
+    pub created_at: Option<NaiveDateTime>,
+
+    #[serde(flatten)]
+    pub report: ParseReport,
+}
@@ -0,0 +1,20 @@
+use chrono::NaiveDateTime;
+use serde::Serialize;
+
+/// The client a stream is proxied to.
+#[derive(Debug, Clone, Default)]
+pub struct SessionClient {
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Session {
+    pub id: u64,
+    pub username: Option<String>,
+    pub channel: String,
+    pub client_ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub started_at: NaiveDateTime,
+    pub bytes_transferred: u64,
+}
@@ -1,3 +1,5 @@
+use db::models::XtreamUserModel;
+use iptv::models::ProviderConfig;
 use reqwest::Url;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
@@ -10,6 +12,32 @@ pub struct Credentials {
     pub password: String,
 }
 
+/// The account a client authenticated with, either the configured proxied credentials or one
+/// of the xtream users.
+#[derive(Debug, Clone)]
+pub struct XtreamAccount {
+    pub username: String,
+    pub password: String,
+    pub user: Option<XtreamUserModel>,
+    pub groups: Vec<String>,
+}
+
+impl XtreamAccount {
+    /// The id stream tokens are bound to. The proxied credentials have no user and use 0.
+    pub fn id(&self) -> u64 {
+        self.user.as_ref().map_or(0, |user| user.id)
+    }
+
+    /// Accounts without any groups are entitled to all of them.
+    pub fn is_entitled(&self, group_title: &str) -> bool {
+        self.groups.is_empty()
+            || self
+                .groups
+                .iter()
+                .any(|group| group.eq_ignore_ascii_case(group_title))
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct Action {
     pub action: String,
@@ -232,6 +260,12 @@ pub struct XtreamUrl {
     pub proxied: Url,
 }
 
+#[derive(Debug, Clone)]
+pub struct XtreamTarget {
+    pub provider: ProviderConfig,
+    pub urls: XtreamUrl,
+}
+
 #[derive(Debug, Eq, PartialEq, Display, EnumString)]
 #[strum(serialize_all = "snake_case")]
 pub enum Output {
@@ -0,0 +1,63 @@
+use std::sync::Arc;
+
+use db::DB;
+use warp::{delete, filters::BoxedFilter, get, path, post, Filter, Rejection, Reply};
+
+use crate::{
+    filters::{api_key::api_key_auth, json_body, with_api_key_util, with_db},
+    handlers,
+    models::{api_key::ApiKeyScope, ApiConfiguration},
+    utils::api_key::ApiKeyUtil,
+};
+
+/// All api key routes, only usable with an admin key
+pub fn api_key_routes(
+    config: ApiConfiguration,
+    db: Arc<DB>,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    let api_key_util = ApiKeyUtil::new(&config);
+    let admin_auth = api_key_auth(api_key_util.clone(), db.clone(), ApiKeyScope::Admin).boxed();
+
+    get_api_keys(admin_auth.clone(), db.clone())
+        .or(create_api_key(admin_auth.clone(), api_key_util, db.clone()))
+        .or(delete_api_key(admin_auth, db))
+}
+
+/// GET /api-key
+fn get_api_keys(
+    admin_auth: BoxedFilter<()>,
+    db: Arc<DB>,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    path!("api-key")
+        .and(get())
+        .and(admin_auth)
+        .and(with_db(db))
+        .and_then(handlers::api_key::get_api_keys)
+}
+
+/// POST /api-key
+fn create_api_key(
+    admin_auth: BoxedFilter<()>,
+    api_key_util: ApiKeyUtil,
+    db: Arc<DB>,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    path!("api-key")
+        .and(post())
+        .and(admin_auth)
+        .and(json_body())
+        .and(with_api_key_util(api_key_util))
+        .and(with_db(db))
+        .and_then(handlers::api_key::create_api_key)
+}
+
+/// DELETE /api-key/{u64}
+fn delete_api_key(
+    admin_auth: BoxedFilter<()>,
+    db: Arc<DB>,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    path!("api-key" / u64)
+        .and(delete())
+        .and(admin_auth)
+        .and(with_db(db))
+        .and_then(handlers::api_key::delete_api_key)
+}
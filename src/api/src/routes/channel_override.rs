@@ -0,0 +1,62 @@
+use std::sync::Arc;
+
+use db::DB;
+use warp::{delete, filters::BoxedFilter, get, path, post, Filter, Rejection, Reply};
+
+use crate::{
+    filters::{api_key::api_key_auth, json_body, with_db},
+    handlers,
+    models::{api_key::ApiKeyScope, ApiConfiguration},
+    utils::api_key::ApiKeyUtil,
+};
+
+/// All channel override routes
+pub fn channel_override_routes(
+    config: ApiConfiguration,
+    db: Arc<DB>,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    let api_key_util = ApiKeyUtil::new(&config);
+    let read_auth = api_key_auth(api_key_util.clone(), db.clone(), ApiKeyScope::Read).boxed();
+    let admin_auth = api_key_auth(api_key_util, db.clone(), ApiKeyScope::Admin).boxed();
+
+    get_overrides(read_auth, db.clone())
+        .or(create_override(admin_auth.clone(), db.clone()))
+        .or(delete_override(admin_auth, db))
+}
+
+/// GET /override
+fn get_overrides(
+    read_auth: BoxedFilter<()>,
+    db: Arc<DB>,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    path!("override")
+        .and(get())
+        .and(read_auth)
+        .and(with_db(db))
+        .and_then(handlers::channel_override::get_overrides)
+}
+
+/// POST /override
+fn create_override(
+    admin_auth: BoxedFilter<()>,
+    db: Arc<DB>,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    path!("override")
+        .and(post())
+        .and(admin_auth)
+        .and(json_body())
+        .and(with_db(db))
+        .and_then(handlers::channel_override::create_override)
+}
+
+/// DELETE /override/{u64}
+fn delete_override(
+    admin_auth: BoxedFilter<()>,
+    db: Arc<DB>,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    path!("override" / u64)
+        .and(delete())
+        .and(admin_auth)
+        .and(with_db(db))
+        .and_then(handlers::channel_override::delete_override)
+}
@@ -1,32 +1,53 @@
 use std::sync::Arc;
 
 use db::DB;
-use warp::{get, path, post, Filter, Rejection, Reply};
+use warp::{filters::BoxedFilter, get, path, post, Filter, Rejection, Reply};
 
 use crate::{
-    filters::{json_body, with_db, with_output},
+    filters::{
+        api_key::api_key_auth, json_body, with_config, with_db, with_output,
+        xtream::xtream_param_auth,
+    },
     handlers,
+    models::{api_key::ApiKeyScope, ApiConfiguration},
+    utils::api_key::ApiKeyUtil,
 };
 
-pub fn m3u_routes(db: Arc<DB>) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
-    get_latest_m3u_file()
-        .or(get_m3u_from_disc())
+pub fn m3u_routes(
+    config: ApiConfiguration,
+    db: Arc<DB>,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    let admin_auth = api_key_auth(ApiKeyUtil::new(&config), db.clone(), ApiKeyScope::Admin).boxed();
+
+    get_latest_m3u_file(config.clone(), db.clone())
+        .or(get_m3u_from_disc(config, db.clone()))
         .or(get_m3u_file_exist())
-        .or(create_m3u_file(db))
+        .or(create_m3u_file(admin_auth, db))
 }
 
-/// GET /m3u
-fn get_latest_m3u_file() -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+/// GET /m3u?username={username}&password={password}
+fn get_latest_m3u_file(
+    config: ApiConfiguration,
+    db: Arc<DB>,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
     path!("m3u")
         .and(get())
         .and(with_output())
+        .and(xtream_param_auth(config.xtream.clone(), db))
+        .and(with_config(config))
         .and_then(handlers::m3u::get_latest_m3u_file)
 }
 
-fn get_m3u_from_disc() -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+/// GET /m3u/{file_name}?username={username}&password={password}
+fn get_m3u_from_disc(
+    config: ApiConfiguration,
+    db: Arc<DB>,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
     path!("m3u" / String)
         .and(get())
-        .and_then(|file_name: String| handlers::m3u::serve_file_by_file_name(file_name))
+        .and(xtream_param_auth(config.xtream.clone(), db))
+        .and(with_config(config))
+        .and_then(handlers::m3u::serve_file_by_file_name)
 }
 
 fn get_m3u_file_exist() -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
@@ -36,10 +57,14 @@ fn get_m3u_file_exist() -> impl Filter<Extract = impl Reply, Error = Rejection>
         .and_then(handlers::m3u::m3u_file_exist)
 }
 
-/// GET /m3u/create
-fn create_m3u_file(db: Arc<DB>) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+/// POST /m3u/create
+fn create_m3u_file(
+    admin_auth: BoxedFilter<()>,
+    db: Arc<DB>,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
     path!("m3u" / "create")
         .and(post())
+        .and(admin_auth)
         .and(json_body())
         .and(with_db(db))
         .and_then(handlers::m3u::create_m3u)
@@ -3,27 +3,68 @@ use rest_client::RestClient;
 use std::{convert::Infallible, sync::Arc};
 use warp::Filter;
 
-use crate::models::ApiConfiguration;
+use crate::{
+    handlers::handle_rejection,
+    models::ApiConfiguration,
+    utils::{
+        connection::ConnectionManager, hls::HlsUtil, segmenter::SegmenterManager,
+        session::SessionManager,
+    },
+};
 
 use self::{
-    m3u::m3u_routes, provider::provider_routes, proxy::proxy_routes, root::root_routes,
-    xtream::xtream_routes,
+    api_key::api_key_routes, channel_override::channel_override_routes, m3u::m3u_routes,
+    provider::provider_routes, proxy::proxy_routes, root::root_routes, session::session_routes,
+    xtream::xtream_routes, xtream_user::xtream_user_routes,
 };
 
+pub mod api_key;
+pub mod channel_override;
 pub mod m3u;
 pub mod provider;
 pub mod proxy;
 pub mod root;
+pub mod session;
 pub mod xtream;
+pub mod xtream_user;
 
 pub fn get_routes(
     config: ApiConfiguration,
     db: Arc<DB>,
     client: Arc<RestClient>,
 ) -> impl Filter<Extract = impl warp::Reply, Error = Infallible> + Clone {
+    let connection_manager = ConnectionManager::new(&config);
+    let hls_util = HlsUtil::new(&config);
+    let segmenter_manager = SegmenterManager::new(&config);
+    let session_manager = SessionManager::new();
+
     root_routes()
         .or(provider_routes(config.clone(), db.clone(), client.clone()))
-        .or(m3u_routes(db.clone()))
-        .or(proxy_routes(db.clone(), client.clone()))
-        .or(xtream_routes(config, client, db))
+        .or(api_key_routes(config.clone(), db.clone()))
+        .or(channel_override_routes(config.clone(), db.clone()))
+        .or(xtream_user_routes(config.clone(), db.clone()))
+        .or(m3u_routes(config.clone(), db.clone()))
+        .or(session_routes(
+            config.clone(),
+            db.clone(),
+            session_manager.clone(),
+        ))
+        .or(proxy_routes(
+            config.clone(),
+            db.clone(),
+            client.clone(),
+            connection_manager.clone(),
+            hls_util.clone(),
+            segmenter_manager,
+            session_manager.clone(),
+        ))
+        .or(xtream_routes(
+            config,
+            client,
+            db,
+            connection_manager,
+            session_manager,
+            hls_util,
+        ))
+        .recover(handle_rejection)
 }
@@ -1,11 +1,12 @@
 use std::sync::Arc;
 
-use crate::filters::{json_body, with_config, with_rest_client};
-use crate::models::ApiConfiguration;
+use crate::filters::{api_key::api_key_auth, json_body, with_config, with_rest_client};
+use crate::models::{api_key::ApiKeyScope, ApiConfiguration};
+use crate::utils::api_key::ApiKeyUtil;
 use crate::{filters::with_db, handlers};
 use db::DB;
 use rest_client::RestClient;
-use warp::{delete, get, path, post, Filter, Rejection, Reply};
+use warp::{delete, filters::BoxedFilter, get, path, post, Filter, Rejection, Reply};
 
 /// All provider routes
 pub fn provider_routes(
@@ -13,20 +14,32 @@ pub fn provider_routes(
     db: Arc<DB>,
     client: Arc<RestClient>,
 ) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
-    refresh_all_providers(config.clone(), db.clone(), client.clone())
-        .or(get_provider(db.clone()))
-        .or(delete_provider(db.clone()))
-        .or(create_provider(config, db.clone(), client))
+    let api_key_util = ApiKeyUtil::new(&config);
+    let read_auth = api_key_auth(api_key_util.clone(), db.clone(), ApiKeyScope::Read).boxed();
+    let admin_auth = api_key_auth(api_key_util, db.clone(), ApiKeyScope::Admin).boxed();
+
+    refresh_all_providers(
+        admin_auth.clone(),
+        config.clone(),
+        db.clone(),
+        client.clone(),
+    )
+    .or(get_provider_report(read_auth.clone(), db.clone()))
+    .or(get_provider(read_auth, db.clone()))
+    .or(delete_provider(admin_auth.clone(), db.clone()))
+    .or(create_provider(admin_auth, config, db.clone(), client))
 }
 
 /// POST /provider
 fn create_provider(
+    admin_auth: BoxedFilter<()>,
     config: ApiConfiguration,
     db: Arc<DB>,
     client: Arc<RestClient>,
 ) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
     path("provider")
         .and(post())
+        .and(admin_auth)
         .and(json_body())
         .and(with_config(config))
         .and(with_db(db))
@@ -35,21 +48,39 @@ fn create_provider(
 }
 
 /// GET /provider/{u64}
-fn get_provider(db: Arc<DB>) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+fn get_provider(
+    read_auth: BoxedFilter<()>,
+    db: Arc<DB>,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
     path!("provider" / u64)
         .and(get())
+        .and(read_auth)
         .and(with_db(db))
         .and_then(handlers::provider::get_provider)
 }
 
+/// GET /provider/{u64}/report
+fn get_provider_report(
+    read_auth: BoxedFilter<()>,
+    db: Arc<DB>,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    path!("provider" / u64 / "report")
+        .and(get())
+        .and(read_auth)
+        .and(with_db(db))
+        .and_then(handlers::provider::get_provider_report)
+}
+
 /// GET /provider/refresh
 fn refresh_all_providers(
+    admin_auth: BoxedFilter<()>,
     config: ApiConfiguration,
     db: Arc<DB>,
     client: Arc<RestClient>,
 ) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
     path!("provider" / "refresh")
         .and(get())
+        .and(admin_auth)
         .and(with_config(config))
         .and(with_db(db))
         .and(with_rest_client(client))
@@ -57,9 +88,13 @@ fn refresh_all_providers(
 }
 
 /// DELETE /providers/{u64}
-fn delete_provider(db: Arc<DB>) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+fn delete_provider(
+    admin_auth: BoxedFilter<()>,
+    db: Arc<DB>,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
     path!("provider" / u64)
         .and(delete())
+        .and(admin_auth)
         .and(with_db(db))
         .and_then(handlers::provider::delete_provider)
 }
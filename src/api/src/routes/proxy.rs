@@ -2,60 +2,118 @@ use std::sync::Arc;
 
 use db::DB;
 use rest_client::RestClient;
-use warp::{header::headers_cloned, Filter};
+use warp::{filters::BoxedFilter, header::headers_cloned, Filter};
 
-use crate::{filters::with_proxy_handler, handlers::proxy::ProxyHandler, models::Path};
+use crate::{
+    filters::{session::session_client, token::stream_token_auth, with_proxy_handler},
+    handlers::proxy::ProxyHandler,
+    models::{xtream::XtreamAccount, ApiConfiguration, Path},
+    utils::{
+        connection::ConnectionManager, hls::HlsUtil, segmenter::SegmenterManager,
+        session::SessionManager, token::StreamTokenUtil,
+    },
+};
 
 pub fn proxy_routes(
+    config: ApiConfiguration,
     db: Arc<DB>,
     client: Arc<RestClient>,
+    connection_manager: ConnectionManager,
+    hls_util: HlsUtil,
+    segmenter_manager: SegmenterManager,
+    session_manager: SessionManager,
 ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
-    let handler = ProxyHandler::new(db, client);
+    let token_auth = stream_token_auth(
+        StreamTokenUtil::new(&config),
+        config.xtream.clone(),
+        db.clone(),
+    )
+    .boxed();
+    let handler = ProxyHandler::new(
+        config,
+        db,
+        client,
+        connection_manager,
+        hls_util,
+        segmenter_manager,
+        session_manager,
+    );
 
-    proxy_stream(handler.clone())
-        .or(proxy_attribute_url(handler.clone()))
-        .or(proxy_hls(handler))
+    proxy_stream(token_auth.clone(), handler.clone())
+        .or(proxy_stream_segment(token_auth.clone(), handler.clone()))
+        .or(proxy_attribute_url(token_auth.clone(), handler.clone()))
+        .or(proxy_hls(token_auth, handler))
 }
 
-/// GET /stream/{id}
+/// GET /stream/{id}[.ts|.m3u8]
 fn proxy_stream(
+    token_auth: BoxedFilter<(XtreamAccount,)>,
     handler: ProxyHandler,
 ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
-    warp::path!("stream" / u64)
-        .and(warp::get())
-        .map(|id: u64| Path {
-            segment1: None,
-            segment2: None,
-            segment3: None,
-            id: id.to_string(),
+    warp::path!("stream" / String)
+        .and(warp::get().or(warp::head()).unify())
+        .and(token_auth)
+        .map(|id: String, account: XtreamAccount| {
+            (
+                Path {
+                    segment1: None,
+                    segment2: None,
+                    segment3: None,
+                    id,
+                },
+                account,
+            )
         })
+        .untuple_one()
+        .and(warp::method())
         .and(headers_cloned())
+        .and(session_client())
+        .and(with_proxy_handler(handler))
+        .and_then(
+            |path, account, method, headers, client, handler: ProxyHandler| {
+                handler.proxy_stream(path, account, method, headers, client)
+            },
+        )
+}
+
+/// GET /stream/{id}/{sequence}.ts
+fn proxy_stream_segment(
+    token_auth: BoxedFilter<(XtreamAccount,)>,
+    handler: ProxyHandler,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::path!("stream" / u64 / String)
+        .and(warp::get())
+        .and(token_auth)
+        .and(session_client())
         .and(with_proxy_handler(handler))
-        .and_then(|path, headers, handler: ProxyHandler| handler.proxy_stream(path, headers))
+        .and_then(|id, segment, account, client, handler: ProxyHandler| {
+            handler.proxy_stream_segment(id, segment, account, client)
+        })
 }
 
 /// GET /attr/{id}
 fn proxy_attribute_url(
+    token_auth: BoxedFilter<(XtreamAccount,)>,
     handler: ProxyHandler,
 ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
     warp::path!("attr" / u64)
         .and(warp::get())
+        .and(token_auth)
         .and(with_proxy_handler(handler))
-        .and_then(|id, handler: ProxyHandler| handler.proxy_attr(id))
+        .and_then(|id, _account, handler: ProxyHandler| handler.proxy_attr(id))
 }
 
+/// GET /hls/{token}
 fn proxy_hls(
+    token_auth: BoxedFilter<(XtreamAccount,)>,
     handler: ProxyHandler,
 ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
-    warp::path!("hls" / String / String)
+    warp::path!("hls" / String)
         .and(warp::get())
-        .map(|segment1: String, id: String| Path {
-            segment1: Some(segment1),
-            segment2: None,
-            segment3: None,
-            id: id.to_string(),
-        })
+        .and(token_auth)
         .and(headers_cloned())
         .and(with_proxy_handler(handler))
-        .and_then(|path, headers, handler: ProxyHandler| handler.proxy_hls(path, headers))
+        .and_then(|token, account, headers, handler: ProxyHandler| {
+            handler.proxy_hls(token, headers, account)
+        })
 }
@@ -0,0 +1,48 @@
+use std::sync::Arc;
+
+use db::DB;
+use warp::{delete, filters::BoxedFilter, get, path, Filter, Rejection, Reply};
+
+use crate::{
+    filters::{api_key::api_key_auth, with_session_manager},
+    handlers,
+    models::{api_key::ApiKeyScope, ApiConfiguration},
+    utils::{api_key::ApiKeyUtil, session::SessionManager},
+};
+
+/// All session routes
+pub fn session_routes(
+    config: ApiConfiguration,
+    db: Arc<DB>,
+    session_manager: SessionManager,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    let api_key_util = ApiKeyUtil::new(&config);
+    let read_auth = api_key_auth(api_key_util.clone(), db.clone(), ApiKeyScope::Read).boxed();
+    let admin_auth = api_key_auth(api_key_util, db, ApiKeyScope::Admin).boxed();
+
+    get_sessions(read_auth, session_manager.clone()).or(delete_session(admin_auth, session_manager))
+}
+
+/// GET /sessions
+fn get_sessions(
+    read_auth: BoxedFilter<()>,
+    session_manager: SessionManager,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    path!("sessions")
+        .and(get())
+        .and(read_auth)
+        .and(with_session_manager(session_manager))
+        .and_then(handlers::session::get_sessions)
+}
+
+/// DELETE /sessions/{u64}
+fn delete_session(
+    admin_auth: BoxedFilter<()>,
+    session_manager: SessionManager,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    path!("sessions" / u64)
+        .and(delete())
+        .and(admin_auth)
+        .and(with_session_manager(session_manager))
+        .and_then(handlers::session::delete_session)
+}
@@ -1,51 +1,77 @@
-use std::{convert::Infallible, sync::Arc};
+use std::sync::Arc;
 
 use db::DB;
 use rest_client::RestClient;
 use warp::{
-    filters::BoxedFilter, get, header::headers_cloned, path, query, Filter, Rejection, Reply,
+    filters::BoxedFilter, get, head, header::headers_cloned, method, path, query, Filter,
+    Rejection, Reply,
 };
 
 use crate::{
     filters::{
+        session::session_client,
+        token::stream_token_auth,
         with_xtream_handler,
         xtream::{xtream_param_auth, xtream_path_auth},
     },
-    handlers::{handle_rejection, xtream::XtreamHandler},
+    handlers::xtream::XtreamHandler,
     models::{
-        xtream::{Action, OptionalParams, TypeOutput},
+        xtream::{Action, OptionalParams, TypeOutput, XtreamAccount},
         ApiConfiguration, Path,
     },
+    utils::{
+        connection::ConnectionManager, hls::HlsUtil, session::SessionManager,
+        token::StreamTokenUtil,
+    },
 };
 
 pub fn xtream_routes(
     config: ApiConfiguration,
     client: Arc<RestClient>,
     db: Arc<DB>,
-) -> impl Filter<Extract = impl Reply, Error = Infallible> + Clone {
-    let handler = XtreamHandler::new(config.clone(), db.clone(), client.clone());
+    connection_manager: ConnectionManager,
+    session_manager: SessionManager,
+    hls_util: HlsUtil,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    let handler = XtreamHandler::new(
+        config.clone(),
+        db.clone(),
+        client.clone(),
+        connection_manager,
+        session_manager,
+        hls_util,
+    );
 
     let player_base_url = warp::path!("player_api.php")
         .and(get())
-        .and(xtream_param_auth(config.xtream.clone()))
+        .and(xtream_param_auth(config.xtream.clone(), db.clone()))
         .boxed();
 
-    let get_path_auth = xtream_path_auth(config.xtream.clone()).and(get()).boxed();
-    let get_param_auth = xtream_param_auth(config.xtream.clone()).and(get()).boxed();
+    let stream_path_auth = xtream_path_auth(config.xtream.clone(), db.clone())
+        .and(get().or(head()).unify())
+        .boxed();
+    let get_param_auth = xtream_param_auth(config.xtream.clone(), db.clone())
+        .and(get())
+        .boxed();
+    let token_auth =
+        stream_token_auth(StreamTokenUtil::new(&config), config.xtream.clone(), db).boxed();
 
     player_api_action(handler.clone(), player_base_url.clone())
         .or(get_type_output(get_param_auth.clone(), handler.clone()))
         .or(xmltv(get_param_auth, handler.clone()))
         .or(player_api_login(handler.clone(), player_base_url))
-        .or(url_proxy(handler.clone()))
-        .or(xmltv_url_proxy(handler.clone()))
-        .or(stream_three_segment(get_path_auth.clone(), handler.clone()))
-        .or(stream_four_segment(get_path_auth, handler.clone()))
-        .recover(handle_rejection)
+        .or(url_proxy(token_auth.clone(), handler.clone()))
+        .or(xmltv_url_proxy(token_auth.clone(), handler.clone()))
+        .or(epg(token_auth, handler.clone()))
+        .or(stream_three_segment(
+            stream_path_auth.clone(),
+            handler.clone(),
+        ))
+        .or(stream_four_segment(stream_path_auth, handler.clone()))
 }
 
 fn stream_three_segment(
-    base_filter: BoxedFilter<()>,
+    base_filter: BoxedFilter<(XtreamAccount,)>,
     handler: XtreamHandler,
 ) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
     base_filter
@@ -53,19 +79,31 @@ fn stream_three_segment(
         .and(path::param::<String>())
         .and(path::param::<String>())
         .and(path::end())
-        .map(|seg1, seg2: String, id: String| Path {
-            segment1: Some(seg1),
-            segment2: Some(seg2),
-            segment3: None,
-            id,
+        .map(|account, seg1, seg2: String, id: String| {
+            (
+                Path {
+                    segment1: Some(seg1),
+                    segment2: Some(seg2),
+                    segment3: None,
+                    id,
+                },
+                account,
+            )
         })
+        .untuple_one()
+        .and(method())
         .and(headers_cloned())
+        .and(session_client())
         .and(with_xtream_handler(handler))
-        .and_then(|path, headers, handler: XtreamHandler| handler.stream(path, headers))
+        .and_then(
+            |path, account, method, headers, client, handler: XtreamHandler| {
+                handler.stream(path, account, method, headers, client)
+            },
+        )
 }
 
 fn stream_four_segment(
-    base_filter: BoxedFilter<()>,
+    base_filter: BoxedFilter<(XtreamAccount,)>,
     handler: XtreamHandler,
 ) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
     base_filter
@@ -74,79 +112,109 @@ fn stream_four_segment(
         .and(path::param::<String>())
         .and(path::param::<String>())
         .and(path::end())
-        .map(|seg1, seg2: String, seg3: String, id: String| Path {
-            segment1: Some(seg1),
-            segment2: Some(seg2),
-            segment3: Some(seg3),
-            id,
+        .map(|account, seg1, seg2: String, seg3: String, id: String| {
+            (
+                Path {
+                    segment1: Some(seg1),
+                    segment2: Some(seg2),
+                    segment3: Some(seg3),
+                    id,
+                },
+                account,
+            )
         })
+        .untuple_one()
+        .and(method())
         .and(headers_cloned())
+        .and(session_client())
         .and(with_xtream_handler(handler))
-        .and_then(|path, headers, handler: XtreamHandler| handler.stream(path, headers))
+        .and_then(
+            |path, account, method, headers, client, handler: XtreamHandler| {
+                handler.stream(path, account, method, headers, client)
+            },
+        )
 }
 
 fn xmltv(
-    base_filter: BoxedFilter<()>,
+    base_filter: BoxedFilter<(XtreamAccount,)>,
     handler: XtreamHandler,
 ) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
     warp::path!("xmltv.php")
         .and(base_filter)
         .and(path::full())
         .and(with_xtream_handler(handler))
-        .and_then(|path, handler: XtreamHandler| handler.xmltv(path))
+        .and_then(|account, path, handler: XtreamHandler| handler.xmltv(path, account))
+}
+
+/// GET /epg?token={token}, the XMLTV guide referenced by `url-tvg` in generated playlists
+fn epg(
+    token_auth: BoxedFilter<(XtreamAccount,)>,
+    handler: XtreamHandler,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    warp::path!("epg")
+        .and(get())
+        .and(token_auth)
+        .and(with_xtream_handler(handler))
+        .and_then(|account, handler: XtreamHandler| handler.epg(account))
 }
 
 fn get_type_output(
-    base_filter: BoxedFilter<()>,
+    base_filter: BoxedFilter<(XtreamAccount,)>,
     handler: XtreamHandler,
 ) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
     warp::path!("get.php")
         .and(base_filter)
         .and(query::<TypeOutput>())
         .and(with_xtream_handler(handler))
-        .and_then(|type_output: TypeOutput, handler: XtreamHandler| {
-            handler.get_type_output(type_output)
+        .and_then(|account, type_output: TypeOutput, handler: XtreamHandler| {
+            handler.get_type_output(type_output, account)
         })
 }
 
 fn player_api_action(
     handler: XtreamHandler,
-    base_url: BoxedFilter<()>,
+    base_url: BoxedFilter<(XtreamAccount,)>,
 ) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
     base_url
         .and(query::<Action>())
         .and(query::<OptionalParams>())
         .and(path::full())
         .and(with_xtream_handler(handler))
-        .and_then(|action, optional_params, path, handler: XtreamHandler| {
-            handler.player_api_action(action, optional_params, path)
-        })
+        .and_then(
+            |account, action, optional_params, path, handler: XtreamHandler| {
+                handler.player_api_action(action, optional_params, path, account)
+            },
+        )
 }
 
 fn player_api_login(
     handler: XtreamHandler,
-    base_url: BoxedFilter<()>,
+    base_url: BoxedFilter<(XtreamAccount,)>,
 ) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
     base_url
         .and(path::full())
         .and(with_xtream_handler(handler))
-        .and_then(|path, handler: XtreamHandler| handler.player_api_login(path))
+        .and_then(|account, path, handler: XtreamHandler| handler.player_api_login(path, account))
 }
 
 fn url_proxy(
+    token_auth: BoxedFilter<(XtreamAccount,)>,
     handler: XtreamHandler,
 ) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
     warp::path!("url" / u64)
         .and(get())
+        .and(token_auth)
         .and(with_xtream_handler(handler))
-        .and_then(|id, handler: XtreamHandler| handler.url_proxy(id))
+        .and_then(|id, _account, handler: XtreamHandler| handler.url_proxy(id))
 }
 
 fn xmltv_url_proxy(
+    token_auth: BoxedFilter<(XtreamAccount,)>,
     handler: XtreamHandler,
 ) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
     warp::path!("xmltv" / u64)
         .and(get())
+        .and(token_auth)
         .and(with_xtream_handler(handler))
-        .and_then(|id, handler: XtreamHandler| handler.xmltv_url_proxy(id))
+        .and_then(|id, _account, handler: XtreamHandler| handler.xmltv_url_proxy(id))
 }
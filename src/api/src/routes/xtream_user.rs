@@ -0,0 +1,103 @@
+use std::sync::Arc;
+
+use db::DB;
+use warp::{delete, filters::BoxedFilter, get, path, post, put, Filter, Rejection, Reply};
+
+use crate::{
+    filters::{api_key::api_key_auth, json_body, with_db},
+    handlers,
+    models::{api_key::ApiKeyScope, ApiConfiguration},
+    utils::api_key::ApiKeyUtil,
+};
+
+/// All xtream user routes
+pub fn xtream_user_routes(
+    config: ApiConfiguration,
+    db: Arc<DB>,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    let api_key_util = ApiKeyUtil::new(&config);
+    let read_auth = api_key_auth(api_key_util.clone(), db.clone(), ApiKeyScope::Read).boxed();
+    let admin_auth = api_key_auth(api_key_util, db.clone(), ApiKeyScope::Admin).boxed();
+
+    get_users(read_auth.clone(), db.clone())
+        .or(create_user(admin_auth.clone(), db.clone()))
+        .or(update_user(admin_auth.clone(), db.clone()))
+        .or(delete_user(admin_auth.clone(), db.clone()))
+        .or(get_user_groups(read_auth, db.clone()))
+        .or(set_user_groups(admin_auth, db))
+}
+
+/// GET /user
+fn get_users(
+    read_auth: BoxedFilter<()>,
+    db: Arc<DB>,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    path!("user")
+        .and(get())
+        .and(read_auth)
+        .and(with_db(db))
+        .and_then(handlers::xtream_user::get_users)
+}
+
+/// POST /user
+fn create_user(
+    admin_auth: BoxedFilter<()>,
+    db: Arc<DB>,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    path!("user")
+        .and(post())
+        .and(admin_auth)
+        .and(json_body())
+        .and(with_db(db))
+        .and_then(handlers::xtream_user::create_user)
+}
+
+/// PUT /user/{u64}
+fn update_user(
+    admin_auth: BoxedFilter<()>,
+    db: Arc<DB>,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    path!("user" / u64)
+        .and(put())
+        .and(admin_auth)
+        .and(json_body())
+        .and(with_db(db))
+        .and_then(handlers::xtream_user::update_user)
+}
+
+/// DELETE /user/{u64}
+fn delete_user(
+    admin_auth: BoxedFilter<()>,
+    db: Arc<DB>,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    path!("user" / u64)
+        .and(delete())
+        .and(admin_auth)
+        .and(with_db(db))
+        .and_then(handlers::xtream_user::delete_user)
+}
+
+/// GET /user/{u64}/group
+fn get_user_groups(
+    read_auth: BoxedFilter<()>,
+    db: Arc<DB>,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    path!("user" / u64 / "group")
+        .and(get())
+        .and(read_auth)
+        .and(with_db(db))
+        .and_then(handlers::xtream_user::get_user_groups)
+}
+
+/// PUT /user/{u64}/group
+fn set_user_groups(
+    admin_auth: BoxedFilter<()>,
+    db: Arc<DB>,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    path!("user" / u64 / "group")
+        .and(put())
+        .and(admin_auth)
+        .and(json_body())
+        .and(with_db(db))
+        .and_then(handlers::xtream_user::set_user_groups)
+}
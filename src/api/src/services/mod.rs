@@ -1,5 +1,3 @@
-use std::convert::TryInto;
-
 use serde_json::Value;
 
 use crate::models::xtream::{LiveStream, Series, SeriesInfo, VodInfo, VodStream};
@@ -14,14 +12,14 @@ pub trait HasId {
 
 impl HasId for LiveStream {
     fn get_set_id(&mut self) -> &Value {
-        self.id = self.stream_id.clone().try_into().unwrap_or_default();
+        self.id = self.stream_id.clone();
         &self.id
     }
 }
 
 impl HasId for VodStream {
     fn get_set_id(&mut self) -> &Value {
-        self.id = self.stream_id.clone().try_into().unwrap_or_default();
+        self.id = self.stream_id.clone().into();
         &self.id
     }
 }
@@ -1,12 +1,12 @@
 use anyhow::{bail, Context, Error};
 use db::{
-    models::ProviderRequest,
+    models::{ParseReportRequest, ProviderRequest},
     services::provider::{CreateProviderRequest, ProviderDBService, M3U},
     DB,
 };
+use iptv::m3u::fetcher::{compose_source, parse_source};
 use iptv::m3u::parser::parse_m3u_url;
-use iptv::m3u::tools::{count_channels, count_groups};
-use reqwest::Url;
+use iptv::m3u::tools::{count_channels, count_groups, get_stream_host};
 use rest_client::RestClient;
 use std::sync::Arc;
 use warp::hyper::{Body, Response, StatusCode};
@@ -14,7 +14,10 @@ use warp::reply::{json, with_status};
 use warp::Reply;
 
 use crate::handlers::provider::create_provider;
-use crate::models::{provider::CreateProviderRequestApiModel, ApiConfiguration};
+use crate::models::{
+    provider::{CreateProviderRequestApiModel, ParseReportApiModel},
+    ApiConfiguration,
+};
 
 pub struct ProviderService {
     db: Option<Arc<DB>>,
@@ -34,19 +37,31 @@ impl ProviderService {
         self.client = Some(client);
     }
 
+    /// Initializes the service for the operations that don't fetch from providers.
+    pub fn initialize_db(&mut self, db: Arc<DB>) {
+        self.db = Some(db);
+    }
+
     pub async fn create_provider(
         &self,
-        provider_source: &String,
+        provider_source: &str,
         config: ApiConfiguration,
     ) -> Result<Response<Body>, Error> {
         if let (Some(db), Some(client)) = (self.db.as_ref(), self.client.as_ref()) {
-            let url =
-                Url::parse(provider_source).context("Could not parse M3U URL, not a valid URL")?;
+            let url = parse_source(provider_source)
+                .context("Could not parse M3U source, not a valid URL or path")?;
+
+            let xtream_config = match config.get_provider_by_source(&compose_source(&url)) {
+                Some(provider) => provider.xtream.clone().unwrap_or_default(),
+                None => config.xtream.into(),
+            };
 
             let parsed_m3u = parse_m3u_url(
                 &url,
                 &config.group_excludes,
-                config.xtream.into(),
+                &config.group_includes,
+                &config.filter_rules,
+                xtream_config,
                 client.clone(),
             )
             .await
@@ -54,20 +69,40 @@ impl ProviderService {
 
             let extinf_entries_count = count_channels(&parsed_m3u);
 
+            let (domain, port) = match url.host_str() {
+                Some(host) => (host.to_string(), url.port()),
+                None => get_stream_host(&parsed_m3u),
+            };
+
+            let report = ParseReportRequest {
+                total_lines: parsed_m3u.report.total_lines,
+                invalid_lines: parsed_m3u.report.invalid_lines,
+                invalid_extinf_entries: parsed_m3u.report.invalid_extinf_entries,
+                duplicate_channels: parsed_m3u.report.duplicate_channels.len() as u32,
+                discovered_groups: parsed_m3u.report.groups.len() as u32,
+                excluded_channels: parsed_m3u.report.excluded_channels,
+                excluded_groups: parsed_m3u.report.excluded_groups,
+                details: serde_json::to_string(&parsed_m3u.report)
+                    .context("Could not serialize parse report")?,
+                provider_id: None,
+            };
+
             let req = CreateProviderRequest {
                 provider_request: ProviderRequest {
                     name: None,
-                    source: url.to_string(),
+                    source: compose_source(&url),
                     channels: Some(extinf_entries_count),
                     groups: Some(count_groups(&parsed_m3u)),
                 },
                 m3u: M3U {
-                    domain: url.host_str().unwrap().to_string(),
-                    port: url.port(),
+                    domain,
+                    port,
+                    header_attributes: parsed_m3u.header_attributes,
                     extinfs: parsed_m3u.extinfs,
                 },
                 channel_count: extinf_entries_count,
                 groups: parsed_m3u.groups,
+                report,
             };
 
             let mut provider_db_service = ProviderDBService::new();
@@ -117,6 +152,27 @@ impl ProviderService {
         }
     }
 
+    pub async fn get_report(&self, provider_id: u64) -> Result<Response<Body>, Error> {
+        if let Some(ref db) = self.db {
+            let mut provider_db_service = ProviderDBService::new();
+            provider_db_service.initialize_db(db.clone());
+
+            let model = provider_db_service.get_report(provider_id).await?;
+
+            let report = ParseReportApiModel {
+                id: model.id,
+                provider_id,
+                created_at: model.created_at,
+                report: serde_json::from_str(&model.details)
+                    .context("Could not deserialize parse report")?,
+            };
+
+            Ok(json(&report).into_response())
+        } else {
+            bail!("Unable to initialize db");
+        }
+    }
+
     pub async fn get_provider_entries_by_url(&self, url: &str) -> Result<Response<Body>, Error> {
         if let Some(ref db) = self.db {
             let mut provider_db_service = ProviderDBService::new();
@@ -1,32 +1,75 @@
-use std::sync::Arc;
+use std::{cmp::Reverse, sync::Arc};
 
-use anyhow::{Context, Error};
-use db::{CRUD, DB};
+use anyhow::{bail, ensure, Context, Error};
+use db::{
+    models::ChannelEquivalentModel, services::channel_equivalence::ChannelEquivalenceDBService,
+    Connection, CRUD, DB,
+};
+use futures::{
+    stream::{once, unfold},
+    StreamExt,
+};
+use iptv::models::ProviderConfig;
+use log::warn;
 use reqwest::{Method, Url};
 use rest_client::RestClient;
+use tokio::{spawn, sync::mpsc::channel};
 use warp::{
-    http::HeaderMap,
+    http::{
+        header::{CONTENT_LENGTH, CONTENT_TYPE},
+        HeaderMap, HeaderValue, StatusCode,
+    },
     hyper::{Body, Response},
 };
 
 use crate::{
-    models::Path,
-    utils::{response::ResponseUtil, url::UrlUtil},
+    models::{session::SessionClient, xtream::XtreamAccount, ApiConfiguration, Path},
+    utils::{
+        connection::{ConnectionManager, Subscription},
+        header::HeaderUtil,
+        hls::HlsUtil,
+        response::ResponseUtil,
+        segmenter::{HlsViewer, SegmentedStream, SegmenterManager},
+        session::SessionManager,
+        url::UrlUtil,
+    },
 };
 
+const REMUX_BUFFER_SIZE: usize = 64;
+
 #[derive(Clone)]
 pub struct ProxyService {
     response_util: ResponseUtil,
     url_util: UrlUtil,
+    connection_manager: ConnectionManager,
+    hls_util: HlsUtil,
+    header_util: HeaderUtil,
+    segmenter_manager: SegmenterManager,
+    session_manager: SessionManager,
+    config: ApiConfiguration,
     db: Arc<DB>,
     client: Arc<RestClient>,
 }
 
 impl ProxyService {
-    pub fn new(db: Arc<DB>, client: Arc<RestClient>) -> Self {
+    pub fn new(
+        config: ApiConfiguration,
+        db: Arc<DB>,
+        client: Arc<RestClient>,
+        connection_manager: ConnectionManager,
+        hls_util: HlsUtil,
+        segmenter_manager: SegmenterManager,
+        session_manager: SessionManager,
+    ) -> Self {
         ProxyService {
             response_util: ResponseUtil::new(),
             url_util: UrlUtil::new(),
+            connection_manager,
+            hls_util,
+            header_util: HeaderUtil::new(),
+            segmenter_manager,
+            session_manager,
+            config,
             db,
             client,
         }
@@ -36,6 +79,8 @@ impl ProxyService {
         &self,
         path: Path,
         headers: HeaderMap,
+        account: XtreamAccount,
+        client: SessionClient,
     ) -> Result<Response<Body>, Error> {
         let mut tx = self.db.pool.begin().await?;
 
@@ -48,53 +93,460 @@ impl ProxyService {
             .await
             .context(format!("Unable to get ext entry with ID: {}", track.id))?;
 
-        let url = Url::parse(&extinf.url)?;
-
-        let res = self.client.request(Method::GET, url, headers).await?;
+        let provider = self.get_provider_config(&mut tx, extinf.m3u_id).await?;
 
-        let builder = self.response_util.compose_base_response(&res).await?;
+        tx.commit().await?;
 
-        if self.url_util.is_hls_stream(extinf.url) {
-            self.url_util
-                .persist_final_response_url(res.url(), self.db.clone())
-                .await?;
+        if track.extension.as_deref() == Some("m3u8")
+            && !self
+                .hls_util
+                .is_playlist(&Url::parse(&extinf.url)?, &HeaderMap::new())
+        {
+            return self
+                .proxy_segmented_stream(
+                    track.id,
+                    &extinf.name,
+                    extinf.url,
+                    provider,
+                    account,
+                    client,
+                )
+                .await;
         }
 
+        let permit = self.connection_manager.acquire_user(&account)?;
+        let remux = track.extension.as_deref() == Some("ts");
+
         let res = self
-            .response_util
-            .compose_proxy_stream_response(res, builder)
+            .proxy_with_failover(
+                track.id,
+                &extinf.url,
+                provider.as_ref(),
+                headers,
+                remux,
+                account.id(),
+            )
+            .await?;
+        let res = self.connection_manager.guard_user_response(res, permit);
+
+        Ok(self
+            .session_manager
+            .track(res, extinf.name, Some(account.username), client))
+    }
+
+    /// Answers a HEAD request with the headers of the primary upstream, without opening a
+    /// stream or taking a provider connection.
+    pub async fn proxy_stream_head(
+        &self,
+        path: Path,
+        headers: HeaderMap,
+    ) -> Result<Response<Body>, Error> {
+        let mut tx = self.db.pool.begin().await?;
+
+        let track = self.url_util.parse_track(path.id)?;
+
+        let extinf = self
+            .db
+            .extinf
+            .get(&mut tx, track.id)
             .await
-            .context("error proxying stream")?;
+            .context(format!("Unable to get ext entry with ID: {}", track.id))?;
+
+        let provider = self.get_provider_config(&mut tx, extinf.m3u_id).await?;
 
         tx.commit().await?;
 
-        return Ok(res);
+        let headers = self
+            .header_util
+            .compose_request_headers(&headers, provider.as_ref());
+
+        let res = self
+            .client
+            .request(Method::HEAD, Url::parse(&extinf.url)?, headers.clone())
+            .await?;
+
+        self.response_util.compose_head_response(&headers, res)
     }
 
-    pub async fn proxy_hls(&self, path: Path, headers: HeaderMap) -> Result<Response<Body>, Error> {
-        let mut tx = self.db.pool.begin().await?;
+    pub async fn proxy_stream_segment(
+        &self,
+        id: u64,
+        segment: String,
+        account: XtreamAccount,
+        client: SessionClient,
+    ) -> Result<Response<Body>, Error> {
+        let sequence = self.url_util.parse_track(segment)?.id;
 
-        let host = self.db.hls_url.get_latest(&mut tx).await?;
+        let stream = self.segmenter_manager.get(id);
+        let data = stream.as_ref().and_then(|stream| stream.segment(sequence));
 
-        tx.commit().await?;
+        if let (Some(stream), Some(data)) = (&stream, &data) {
+            if !self.watch_segmented_stream(stream, &account, client, data.len() as u64)? {
+                return self.segmenter_manager.compose_ended();
+            }
+        }
 
-        let url = Url::parse(&format!(
-            "{}/hls/{}/{}",
-            host.url,
-            path.segment1.unwrap(),
-            path.id
-        ))?;
+        self.segmenter_manager.compose_segment(data)
+    }
 
-        let res = self.client.request(Method::GET, url, headers).await?;
+    async fn proxy_segmented_stream(
+        &self,
+        id: u64,
+        channel: &str,
+        url: String,
+        provider: Option<ProviderConfig>,
+        account: XtreamAccount,
+        client: SessionClient,
+    ) -> Result<Response<Body>, Error> {
+        let account_id = account.id();
+        let (stream, sender) = self.segmenter_manager.get_or_create(id, channel);
+
+        if !self.watch_segmented_stream(&stream, &account, client, 0)? {
+            return self.segmenter_manager.compose_ended();
+        }
+
+        if let Some(sender) = sender {
+            let service = self.clone();
+            let stream = stream.clone();
+
+            spawn(async move {
+                match service
+                    .proxy_with_failover(
+                        id,
+                        &url,
+                        provider.as_ref(),
+                        HeaderMap::new(),
+                        false,
+                        account_id,
+                    )
+                    .await
+                {
+                    Ok(res) => {
+                        service
+                            .segmenter_manager
+                            .run(id, stream, sender, res.into_body())
+                            .await
+                    }
+                    Err(err) => {
+                        warn!("Unable to start hls output for stream {}: {:#}", id, err);
+                        service.segmenter_manager.remove(id, &stream);
+                    }
+                }
+            });
+        }
+
+        self.segmenter_manager
+            .compose_playlist(id, stream, account_id)
+            .await
+    }
+
+    /// Counts a viewer of an hls output against the connection limit of its user and lists
+    /// it as a session, for as long as it keeps polling the output.
+    fn watch_segmented_stream(
+        &self,
+        stream: &SegmentedStream,
+        account: &XtreamAccount,
+        client: SessionClient,
+        bytes: u64,
+    ) -> Result<bool, Error> {
+        let key = format!(
+            "{}@{}",
+            account.username,
+            client.ip.as_deref().unwrap_or_default()
+        );
 
-        let builder = self.response_util.compose_base_response(&res).await?;
+        stream.watch(key, bytes, |channel| {
+            let permit = self.connection_manager.acquire_user(account)?;
+            let session = self.session_manager.open(
+                channel.to_owned(),
+                Some(account.username.clone()),
+                client,
+            );
+
+            Ok(HlsViewer::new(session, permit))
+        })
+    }
+
+    async fn proxy_with_failover(
+        &self,
+        id: u64,
+        url: &str,
+        provider: Option<&ProviderConfig>,
+        headers: HeaderMap,
+        remux: bool,
+        account_id: u64,
+    ) -> Result<Response<Body>, Error> {
+        let mut res = self
+            .try_proxy_stream(url, provider, headers.clone(), remux, account_id)
+            .await;
+
+        if let Err(err) = &res {
+            warn!("Stream {} failed on primary source: {:#}", id, err);
+
+            for equivalent in self.get_failover_sources(id).await? {
+                let provider = self.config.get_provider_by_source(&equivalent.source);
+
+                res = self
+                    .try_proxy_stream(
+                        &equivalent.url,
+                        provider,
+                        headers.clone(),
+                        remux,
+                        account_id,
+                    )
+                    .await;
+
+                match &res {
+                    Ok(_) => {
+                        warn!("Stream {} failed over to {}", id, equivalent.url);
+                        break;
+                    }
+                    Err(err) => warn!("Stream {} failed on {}: {:#}", id, equivalent.url, err),
+                }
+            }
+        }
+
+        res
+    }
+
+    async fn try_proxy_stream(
+        &self,
+        url: &str,
+        provider: Option<&ProviderConfig>,
+        headers: HeaderMap,
+        remux: bool,
+        account_id: u64,
+    ) -> Result<Response<Body>, Error> {
+        let upstream_url = Url::parse(url)?;
+        let headers = self.header_util.compose_request_headers(&headers, provider);
+
+        if remux && self.hls_util.is_playlist(&upstream_url, &HeaderMap::new()) {
+            return self.try_remux_hls_stream(upstream_url, provider).await;
+        }
+
+        let share_guard = match self.connection_manager.subscribe(url, &headers).await {
+            Subscription::Shared(res) => return Ok(res),
+            Subscription::First(share_guard) => Some(share_guard),
+            Subscription::Exclusive => None,
+        };
+
+        let permit = self.connection_manager.acquire(provider).await?;
 
         let res = self
-            .response_util
-            .compose_proxy_stream_response(res, builder)
+            .client
+            .request(Method::GET, upstream_url.clone(), headers.clone())
+            .await?;
+
+        ensure!(
+            !res.status().is_client_error() && !res.status().is_server_error(),
+            "upstream responded with {}",
+            res.status()
+        );
+
+        if self.hls_util.is_playlist(res.url(), res.headers()) {
+            return self
+                .hls_util
+                .compose_playlist_response(res, provider, account_id)
+                .await;
+        }
+
+        let status = res.status();
+        let response_headers = self.header_util.compose_response_headers(res.headers());
+
+        let mut stream = res.bytes_stream();
+
+        let first_chunk = match stream.next().await {
+            Some(Ok(chunk)) if !chunk.is_empty() => chunk,
+            Some(Err(err)) => return Err(err).context("reading first chunk"),
+            _ => bail!("upstream sent an empty first chunk"),
+        };
+
+        let stream = once(async move { Ok(first_chunk) }).chain(stream);
+
+        if let Some(share_guard) = share_guard.filter(|_| {
+            self.connection_manager
+                .is_shareable(url, &headers, &response_headers)
+        }) {
+            let stream = match response_headers.contains_key(CONTENT_LENGTH) {
+                true => stream.boxed(),
+                false => self
+                    .response_util
+                    .compose_reconnecting_stream(self.client.clone(), upstream_url, headers, stream)
+                    .boxed(),
+            };
+
+            return Ok(self.connection_manager.share(
+                share_guard,
+                status,
+                response_headers,
+                stream,
+                permit,
+            ));
+        }
+
+        let (builder, stream) = self.response_util.compose_range_response(
+            &headers,
+            status,
+            &response_headers,
+            stream,
+        )?;
+
+        let body = self.connection_manager.guard_body(stream, permit);
+
+        let res = builder.body(body).context("error proxying stream")?;
+
+        Ok(res)
+    }
+
+    async fn try_remux_hls_stream(
+        &self,
+        url: Url,
+        provider: Option<&ProviderConfig>,
+    ) -> Result<Response<Body>, Error> {
+        let key = format!("{}#remux", url);
+
+        let share_guard = match self
+            .connection_manager
+            .subscribe(&key, &HeaderMap::new())
             .await
-            .context("error proxying stream")?;
+        {
+            Subscription::Shared(res) => return Ok(res),
+            Subscription::First(share_guard) => share_guard,
+            Subscription::Exclusive => bail!("remuxed stream {} cannot be shared", key),
+        };
+
+        let permit = self.connection_manager.acquire(provider).await?;
+        let headers = self
+            .header_util
+            .compose_request_headers(&HeaderMap::new(), provider);
+
+        let (mut playlist_url, mut playlist) = self
+            .hls_util
+            .fetch_playlist(&self.client, &url, &headers)
+            .await?;
+
+        if let Some(variant_url) = self.hls_util.select_variant(&playlist, &playlist_url) {
+            (playlist_url, playlist) = self
+                .hls_util
+                .fetch_playlist(&self.client, &variant_url, &headers)
+                .await?;
+        }
+
+        let media_playlist = self
+            .hls_util
+            .parse_media_playlist(&playlist, &playlist_url)?;
+
+        ensure!(
+            !media_playlist.segments.is_empty(),
+            "upstream hls playlist has no segments"
+        );
+
+        let (sender, receiver) = channel(REMUX_BUFFER_SIZE);
+
+        spawn(self.hls_util.clone().remux_segments(
+            self.client.clone(),
+            headers,
+            playlist_url,
+            media_playlist,
+            sender,
+        ));
+
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("video/mp2t"));
+
+        let stream = unfold(receiver, |mut receiver| async move {
+            receiver
+                .recv()
+                .await
+                .map(|chunk| (Ok::<_, reqwest::Error>(chunk), receiver))
+        });
+
+        Ok(self
+            .connection_manager
+            .share(share_guard, StatusCode::OK, headers, stream, permit))
+    }
+
+    async fn get_provider_config(
+        &self,
+        tx: &mut Connection,
+        m3u_id: Option<u64>,
+    ) -> Result<Option<ProviderConfig>, Error> {
+        let m3u_id = match m3u_id {
+            Some(m3u_id) => m3u_id,
+            None => return Ok(None),
+        };
+
+        let m3u = self.db.m3u.get(tx, m3u_id).await?;
+
+        let provider = match m3u.provider_id {
+            Some(provider_id) => self.db.provider.get(tx, provider_id).await?,
+            None => return Ok(None),
+        };
+
+        Ok(self
+            .config
+            .get_provider_by_source(&provider.source)
+            .cloned())
+    }
+
+    async fn get_failover_sources(
+        &self,
+        extinf_id: u64,
+    ) -> Result<Vec<ChannelEquivalentModel>, Error> {
+        let mut channel_equivalence_service = ChannelEquivalenceDBService::new();
+        channel_equivalence_service.initialize_db(self.db.clone());
+
+        let mut equivalents = channel_equivalence_service
+            .get_equivalents(extinf_id)
+            .await
+            .context("getting equivalent channels")?;
+
+        equivalents.sort_by_key(|equivalent| {
+            Reverse(
+                self.config
+                    .get_provider_by_source(&equivalent.source)
+                    .map(|provider| provider.priority)
+                    .unwrap_or_default(),
+            )
+        });
+
+        Ok(equivalents)
+    }
+
+    pub async fn proxy_hls(
+        &self,
+        token: String,
+        headers: HeaderMap,
+        account: XtreamAccount,
+    ) -> Result<Response<Body>, Error> {
+        let permit = self.connection_manager.acquire_user(&account)?;
+
+        let (url, slot) = self
+            .hls_util
+            .resolve(&token)
+            .context(format!("Unknown or expired hls token: {}", token))?;
+
+        let provider = slot.and_then(|slot| self.config.get_provider_by_slot(slot));
+        let headers = self.header_util.compose_request_headers(&headers, provider);
+
+        let res = self.client.request(Method::GET, url, headers).await?;
+
+        let res = match self.hls_util.is_playlist(res.url(), res.headers()) {
+            true => {
+                self.hls_util
+                    .compose_playlist_response(res, provider, account.id())
+                    .await?
+            }
+            false => {
+                let builder = self.response_util.compose_base_response(&res).await?;
+
+                self.response_util
+                    .compose_proxy_stream_response(res, builder)
+                    .await
+                    .context("error proxying stream")?
+            }
+        };
 
-        return Ok(res);
+        Ok(self.connection_manager.guard_user_response(res, permit))
     }
 }
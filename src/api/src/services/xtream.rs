@@ -1,15 +1,24 @@
 use anyhow::{bail, ensure, Context, Error};
 use async_recursion::async_recursion;
 use db::{
-    models::XtreamUrlRequest,
-    services::{group::GroupDBService, provider::ProviderDBService},
+    models::{ExtInfModel, ProviderModel, XtreamUrlRequest},
+    services::{
+        channel_override::{find_override, ChannelOverrideDBService},
+        group::GroupDBService,
+        provider::ProviderDBService,
+    },
     Connection, CRUD, DB,
 };
+use futures::StreamExt;
+use iptv::{
+    m3u::tools::{namespace_id, namespace_stream_id, split_stream_id},
+    models::{ProviderConfig, XtreamConfig},
+};
 
 use std::sync::Arc;
 use url::Url;
 
-use log::info;
+use log::{error, info};
 use reqwest::Method;
 use rest_client::RestClient;
 use serde::{de::DeserializeOwned, Serialize};
@@ -17,7 +26,7 @@ use serde_json::{from_str, to_string};
 use serde_yaml::{from_value, to_value, Mapping, Sequence, Value};
 use std::fmt::Write;
 use warp::{
-    http::HeaderMap,
+    http::{header::CONTENT_LENGTH, HeaderMap},
     hyper::{Body, Response},
     Reply,
 };
@@ -27,13 +36,25 @@ use std::str::FromStr;
 use crate::{
     handlers::m3u::get_latest_m3u_file,
     models::{
+        error::EntitlementError,
+        session::SessionClient,
         xtream::{
             Action, ActionTypes, Categories, LiveStream, Login, OptionalParams, Output, Series,
-            SeriesInfo, TypeOutput, VodInfo, VodStream, XtreamUrl,
+            SeriesInfo, TypeOutput, VodInfo, VodStream, XtreamAccount, XtreamTarget, XtreamUrl,
         },
-        ApiConfiguration, Path,
+        ApiConfiguration, Path, ResponseData,
+    },
+    utils::{
+        connection::{ConnectionManager, Subscription},
+        header::HeaderUtil,
+        hls::HlsUtil,
+        proxy::ProxyUtil,
+        response::ResponseUtil,
+        session::SessionManager,
+        token::StreamTokenUtil,
+        url::UrlUtil,
+        xml::XmlUtil,
     },
-    utils::{proxy::ProxyUtil, response::ResponseUtil, url::UrlUtil, xml::XmlUtil},
 };
 
 use super::HasId;
@@ -45,13 +66,25 @@ pub struct XtreamService {
     response_util: ResponseUtil,
     url_util: UrlUtil,
     xml_util: XmlUtil,
+    connection_manager: ConnectionManager,
+    session_manager: SessionManager,
+    hls_util: HlsUtil,
+    header_util: HeaderUtil,
+    stream_token_util: StreamTokenUtil,
     config: ApiConfiguration,
     db: Arc<DB>,
     client: Arc<RestClient>,
 }
 
 impl XtreamService {
-    pub fn new(config: ApiConfiguration, db: Arc<DB>, client: Arc<RestClient>) -> Self {
+    pub fn new(
+        config: ApiConfiguration,
+        db: Arc<DB>,
+        client: Arc<RestClient>,
+        connection_manager: ConnectionManager,
+        session_manager: SessionManager,
+        hls_util: HlsUtil,
+    ) -> Self {
         let mut provider_db_service = ProviderDBService::new();
         provider_db_service.initialize_db(db.clone());
 
@@ -60,7 +93,12 @@ impl XtreamService {
             proxy_util: ProxyUtil::new(ResponseUtil::new(), db.clone(), client.clone()),
             response_util: ResponseUtil::new(),
             url_util: UrlUtil::new(),
-            xml_util: XmlUtil::new(db.clone()),
+            xml_util: XmlUtil::new(db.clone(), StreamTokenUtil::new(&config)),
+            connection_manager,
+            session_manager,
+            hls_util,
+            header_util: HeaderUtil::new(),
+            stream_token_util: StreamTokenUtil::new(&config),
             config,
             db,
             client,
@@ -71,60 +109,263 @@ impl XtreamService {
         &self,
         path: Path,
         headers: HeaderMap,
+        account: XtreamAccount,
+        client: SessionClient,
     ) -> Result<Response<Body>, Error> {
+        self.ensure_entitled_stream(&path, &account).await?;
+
+        let permit = self.connection_manager.acquire_user(&account)?;
+
+        let channel = self.get_channel_name(&path).await;
+
+        let res = self
+            .proxy_provider_stream(path, headers, account.id())
+            .await?;
+        let res = self.connection_manager.guard_user_response(res, permit);
+
+        Ok(self
+            .session_manager
+            .track(res, channel, Some(account.username), client))
+    }
+
+    /// Looks up the name of a stream in the latest playlist of its provider. Streams that aren't
+    /// part of it, like movies and episodes, are named by their id.
+    async fn get_channel_name(&self, path: &Path) -> String {
+        match self.get_stream_extinf(path).await {
+            Ok((extinf, _)) => extinf.name,
+            Err(_) => path.id.clone(),
+        }
+    }
+
+    /// Fails with an `EntitlementError` unless the account is entitled to the group of the
+    /// stream. Streams that aren't part of the latest playlist of their provider can't be
+    /// matched to a group and are only available to accounts without groups.
+    async fn ensure_entitled_stream(
+        &self,
+        path: &Path,
+        account: &XtreamAccount,
+    ) -> Result<(), Error> {
+        if account.groups.is_empty() {
+            return Ok(());
+        }
+
+        let group_title = async {
+            let (extinf, provider) = self.get_stream_extinf(path).await?;
+
+            let mut tx = self.db.pool.begin().await?;
+
+            let group_title = self
+                .db
+                .attribute
+                .get_all_by_extinf_id(&mut tx, extinf.id)
+                .await?
+                .into_iter()
+                .find(|attr| attr.key == "group-title")
+                .map(|attr| match &provider.group_prefix {
+                    Some(group_prefix) => format!("{}{}", group_prefix, attr.value),
+                    None => attr.value,
+                });
+
+            Ok::<Option<String>, Error>(group_title)
+        };
+
+        match group_title.await.unwrap_or_default() {
+            Some(group_title) if account.is_entitled(&group_title) => Ok(()),
+            _ => Err(EntitlementError {
+                username: account.username.clone(),
+                id: path.id.clone(),
+            }
+            .into()),
+        }
+    }
+
+    async fn get_stream_extinf(
+        &self,
+        path: &Path,
+    ) -> Result<(ExtInfModel, &ProviderConfig), Error> {
+        let track = self.url_util.parse_track(path.id.clone())?;
+        let (slot, stream_id) = split_stream_id(track.id);
+
+        let provider = self
+            .config
+            .get_provider_by_slot(slot)
+            .context(format!("No provider configured for stream {}", track.id))?;
+
+        let latest_provider_entry = self
+            .provider_db_service
+            .get_latest_provider_entry(&provider.source)
+            .await
+            .context("Unable to get latest provider entry")?;
+
         let mut tx = self.db.pool.begin().await?;
 
-        match self
+        let m3u = self.db.m3u.get(&mut tx, latest_provider_entry.id).await?;
+        let extinf = self
+            .db
+            .extinf
+            .get_by_track_id(&mut tx, m3u.id, stream_id)
+            .await?;
+
+        Ok((extinf, provider))
+    }
+
+    /// Answers a HEAD request with the headers of the upstream, without opening a stream or
+    /// taking a provider connection.
+    pub async fn proxy_stream_head(
+        &self,
+        path: Path,
+        headers: HeaderMap,
+        account: XtreamAccount,
+    ) -> Result<Response<Body>, Error> {
+        self.ensure_entitled_stream(&path, &account).await?;
+
+        let (url, provider) = self.compose_provider_stream_url(path).await?;
+
+        let headers = self
+            .header_util
+            .compose_request_headers(&headers, Some(provider));
+
+        let res = self
+            .client
+            .request(Method::HEAD, url, headers.clone())
+            .await?;
+
+        self.response_util.compose_head_response(&headers, res)
+    }
+
+    async fn compose_provider_stream_url(
+        &self,
+        path: Path,
+    ) -> Result<(Url, &ProviderConfig), Error> {
+        let track = self.url_util.parse_track(path.id.clone())?;
+        let (slot, stream_id) = split_stream_id(track.id);
+
+        let provider = self
+            .config
+            .get_provider_by_slot(slot)
+            .context(format!("No provider configured for stream {}", track.id))?;
+
+        let xtream = provider.xtream.clone().unwrap_or_default();
+
+        let path = Path {
+            id: match track.extension {
+                Some(extension) => format!("{}.{}", stream_id, extension),
+                None => stream_id.to_string(),
+            },
+            ..path
+        };
+
+        let latest_provider_entry = self
             .provider_db_service
-            .get_latest_provider_entry(self.config.m3u_url.as_str())
+            .get_latest_provider_entry(&provider.source)
+            .await
+            .context("Unable to init provider service")?;
+
+        let mut tx = self.db.pool.begin().await?;
+
+        let m3u = self
+            .db
+            .m3u
+            .get(&mut tx, latest_provider_entry.id)
+            .await
+            .context(format!(
+                "Unable to get m3u entry with id: {}",
+                latest_provider_entry.id
+            ))?;
+
+        let url = self.url_util.compose_proxy_stream_url(
+            path,
+            m3u,
+            Some(xtream.username),
+            Some(xtream.password),
+        )?;
+
+        Ok((url, provider))
+    }
+
+    async fn proxy_provider_stream(
+        &self,
+        path: Path,
+        headers: HeaderMap,
+        account_id: u64,
+    ) -> Result<Response<Body>, Error> {
+        let (url, provider) = self.compose_provider_stream_url(path).await?;
+
+        let headers = self
+            .header_util
+            .compose_request_headers(&headers, Some(provider));
+
+        let share_guard = match self
+            .connection_manager
+            .subscribe(url.as_str(), &headers)
             .await
         {
-            Some(latest_provider_entry) => {
-                let m3u = self
-                    .db
-                    .m3u
-                    .get(&mut tx, latest_provider_entry.id.clone())
-                    .await
-                    .context(format!(
-                        "Unable to get m3u entry with id: {}",
-                        latest_provider_entry.id
-                    ))?;
-
-                let url = self.url_util.compose_proxy_stream_url(
-                    path.clone(),
-                    m3u.clone(),
-                    Some(self.config.xtream.xtream_username.clone()),
-                    Some(self.config.xtream.xtream_password.clone()),
-                )?;
+            Subscription::Shared(res) => return Ok(res),
+            Subscription::First(share_guard) => Some(share_guard),
+            Subscription::Exclusive => None,
+        };
 
-                let res = self
-                    .client
-                    .request(Method::GET, url.clone(), headers)
-                    .await?;
+        let permit = self.connection_manager.acquire(Some(provider)).await?;
 
-                if self.url_util.is_hls_stream(url.to_string()) {
-                    self.url_util
-                        .persist_final_response_url(res.url(), self.db.clone())
-                        .await?;
-                }
+        let res = self
+            .client
+            .request(Method::GET, url.clone(), headers.clone())
+            .await?;
 
-                let builder = self.response_util.compose_base_response(&res).await?;
+        if self.hls_util.is_playlist(res.url(), res.headers()) {
+            return self
+                .hls_util
+                .compose_playlist_response(res, Some(provider), account_id)
+                .await;
+        }
 
-                let res = self
-                    .response_util
-                    .compose_proxy_stream_response(res, builder)
-                    .await
-                    .context("error proxying stream")?;
+        let status = res.status();
+        let response_headers = self.header_util.compose_response_headers(res.headers());
 
-                return Ok(res);
-            }
-            None => bail!("Unable to init provider service"),
+        if let Some(share_guard) = share_guard.filter(|_| {
+            self.connection_manager
+                .is_shareable(url.as_str(), &headers, &response_headers)
+        }) {
+            let stream = match response_headers.contains_key(CONTENT_LENGTH) {
+                true => res.bytes_stream().boxed(),
+                false => self
+                    .response_util
+                    .compose_reconnecting_stream(
+                        self.client.clone(),
+                        url.clone(),
+                        headers,
+                        res.bytes_stream(),
+                    )
+                    .boxed(),
+            };
+
+            return Ok(self.connection_manager.share(
+                share_guard,
+                status,
+                response_headers,
+                stream,
+                permit,
+            ));
         }
+
+        let (builder, stream) = self.response_util.compose_range_response(
+            &headers,
+            status,
+            &response_headers,
+            res.bytes_stream(),
+        )?;
+
+        let body = self.connection_manager.guard_body(stream, permit);
+
+        let res = builder.body(body).context("error proxying stream")?;
+
+        Ok(res)
     }
 
     pub async fn proxy_type_output(
         &self,
         TypeOutput { type_, output }: TypeOutput,
+        account: XtreamAccount,
     ) -> Result<Response<Body>, Error> {
         let output = Output::from_str(&output)?;
 
@@ -135,13 +376,13 @@ impl XtreamService {
         );
 
         let response = match output {
-            Output::Ts => get_latest_m3u_file(Output::Ts)
+            Output::Ts => get_latest_m3u_file(Output::Ts, account, self.config.clone())
                 .await
                 .context("error getting ts m3u file")?,
-            Output::M3u8 => get_latest_m3u_file(Output::M3u8)
+            Output::M3u8 => get_latest_m3u_file(Output::M3u8, account, self.config.clone())
                 .await
                 .context("error getting m3u8 m3u file")?,
-            _ => get_latest_m3u_file(Output::Custom)
+            _ => get_latest_m3u_file(Output::Custom, account, self.config.clone())
                 .await
                 .context("error getting custom m3u file")?,
         };
@@ -149,18 +390,47 @@ impl XtreamService {
         Ok(response)
     }
 
-    pub async fn proxy_login(&self, full_path: &str) -> Result<Response<Body>, Error> {
-        let url = self.compose_login_url(full_path)?;
+    pub async fn proxy_login(
+        &self,
+        full_path: &str,
+        account: XtreamAccount,
+    ) -> Result<Response<Body>, Error> {
+        let xtream = self.get_primary_xtream()?;
+        let url = self.compose_login_url(&xtream, full_path)?;
 
         let mut res = self
             .proxy_util
             .proxy_request_json::<Login>(&url.original)
             .await?;
 
-        let status_code = res.status_code.clone();
+        let status_code = res.status_code;
+
+        res.data.user_info.username = account.username.clone();
+        res.data.user_info.password = account.password.clone();
+
+        if let Some(user) = &account.user {
+            let user_info = &mut res.data.user_info;
+
+            user_info.status = match user.enabled {
+                true => "Active".to_string(),
+                false => "Disabled".to_string(),
+            };
+            user_info.exp_date = user
+                .exp_date
+                .map(|exp_date| exp_date.timestamp().to_string())
+                .unwrap_or_else(|| "null".to_string());
+            user_info.created_at = user
+                .created_at
+                .map(|created_at| created_at.timestamp().to_string())
+                .unwrap_or_default();
+            user_info.max_connections = user.max_connections.unwrap_or_default().to_string();
+            user_info.active_cons = self
+                .connection_manager
+                .get_user_connections(&user.username)
+                .to_string();
+            user_info.is_trial = "0".to_string();
+        }
 
-        res.data.user_info.username = self.config.xtream.xtream_proxied_username.clone();
-        res.data.user_info.password = self.config.xtream.xtream_proxied_password.clone();
         res.data.server_info.url = self.config.xtream.xtream_proxied_domain.clone().unwrap();
         res.data.server_info.port = 3001.to_string();
         res.data.server_info.rtmp_port = 3001.to_string();
@@ -178,203 +448,359 @@ impl XtreamService {
         full_path: &str,
         Action { action }: Action,
         optional_params: OptionalParams,
+        account: XtreamAccount,
     ) -> Result<Response<Body>, Error> {
-        let query = self.compose_action_query_string(action.clone(), optional_params);
+        let action_type = ActionTypes::from_str(action.as_str());
 
-        let urls = self.compose_action_url(full_path, query)?;
-        let original_url = urls.original.clone();
+        let mut targets =
+            self.compose_action_targets(full_path, action.clone(), optional_params)?;
 
-        let response = match ActionTypes::from_str(action.as_str()) {
+        let response = match action_type {
             Ok(ActionTypes::GetLiveStreams) => {
-                self.proxy_streams::<LiveStream>(urls.original, "live")
+                self.proxy_streams::<LiveStream>(&targets, "live", &account)
                     .await?
             }
             Ok(ActionTypes::GetVodStreams) => {
-                self.proxy_streams::<VodStream>(urls.original, "movie")
+                self.proxy_streams::<VodStream>(&targets, "movie", &account)
                     .await?
             }
-            Ok(ActionTypes::GetSeriesInfo) => self.proxy_info::<SeriesInfo>(urls.original).await?,
-            Ok(ActionTypes::GetVodInfo) => self.proxy_info::<VodInfo>(urls.original).await?,
-            Ok(ActionTypes::GetSeries) => self.proxy_series(urls.original).await?,
-            Ok(ActionTypes::GetLiveCategories) => self.proxy_categories(urls.original).await?,
-            Ok(ActionTypes::GetVodCategories) => self.proxy_categories(urls.original).await?,
+            Ok(ActionTypes::GetSeriesInfo) => {
+                self.proxy_info::<SeriesInfo>(&targets, &account).await?
+            }
+            Ok(ActionTypes::GetVodInfo) => self.proxy_info::<VodInfo>(&targets, &account).await?,
+            Ok(ActionTypes::GetSeries) => self.proxy_series(&targets, &account).await?,
+            Ok(ActionTypes::GetLiveCategories) => self.proxy_categories(&targets, &account).await?,
+            Ok(ActionTypes::GetVodCategories) => self.proxy_categories(&targets, &account).await?,
+
+            Ok(ActionTypes::GetSeriesCategories) => {
+                self.proxy_categories(&targets, &account).await?
+            }
 
-            Ok(ActionTypes::GetSeriesCategories) => self.proxy_categories(urls.original).await?,
+            _ => {
+                targets.truncate(1);
 
-            _ => self.proxy_util.proxy_request_bytes(&urls.original).await?,
+                self.proxy_util
+                    .proxy_request_bytes(&targets[0].urls.original)
+                    .await?
+            }
         };
 
         let status_code = response.status();
 
-        info!("[{}] {} => {}", status_code, urls.proxied, original_url);
+        for target in targets {
+            info!(
+                "[{}] {} => {}",
+                status_code, target.urls.proxied, target.urls.original
+            );
+        }
 
         Ok(response)
     }
 
-    async fn proxy_categories(&self, proxy_url: Url) -> Result<Response<Body>, Error> {
-        let mut json = self
-            .proxy_util
-            .proxy_request_json::<Categories>(&proxy_url)
-            .await
-            .context("getting categories json")?;
+    async fn proxy_categories(
+        &self,
+        targets: &[XtreamTarget],
+        account: &XtreamAccount,
+    ) -> Result<Response<Body>, Error> {
+        let mut merged = None;
 
-        match self
-            .provider_db_service
-            .get_latest_provider_entry(self.config.m3u_url.as_str())
-            .await
-        {
-            Some(latest_provider_entry) => {
-                let mut group_service = GroupDBService::new();
-                group_service.initialize_db(self.db.clone());
+        for target in targets {
+            let mut json = self
+                .proxy_util
+                .proxy_request_json::<Categories>(&target.urls.original)
+                .await
+                .context("getting categories json")?;
 
-                let groups = group_service
-                    .get_groups(latest_provider_entry.id)
-                    .await
-                    .context("getting groups")?;
+            let latest_provider_entry = self.get_latest_provider_entry(&target.provider).await?;
 
-                let included_groups: Vec<String> = groups
-                    .into_iter()
-                    .filter(|group| !group.exclude)
-                    .map(|group| group.name)
-                    .collect();
+            let mut group_service = GroupDBService::new();
+            group_service.initialize_db(self.db.clone());
 
-                json.data
-                    .retain(|group| included_groups.contains(&group.category_name));
+            let groups = group_service
+                .get_groups(latest_provider_entry.id)
+                .await
+                .context("getting groups")?;
 
-                let res = self
-                    .response_util
-                    .compose_json_response(json)
-                    .context("composing get categories json response")?;
+            let included_groups: Vec<String> = groups
+                .into_iter()
+                .filter(|group| !group.exclude)
+                .map(|group| group.name)
+                .collect();
+
+            json.data
+                .retain(|group| included_groups.contains(&group.category_name));
+
+            json.data.retain_mut(|category| {
+                match namespace_stream_id(target.provider.slot, &category.category_id) {
+                    Ok(category_id) => category.category_id = category_id,
+                    Err(err) => {
+                        error!("Skipped category {}: {}", category.category_name, err);
+                        return false;
+                    }
+                }
 
-                Ok(res)
-            }
-            None => bail!("No provider entry found"),
+                if let Some(group_prefix) = &target.provider.group_prefix {
+                    category.category_name = format!("{}{}", group_prefix, category.category_name);
+                }
+
+                true
+            });
+
+            json.data
+                .retain(|category| account.is_entitled(&category.category_name));
+
+            self.merge_response_data(&mut merged, json);
         }
+
+        let res = self
+            .response_util
+            .compose_json_response(merged.context("No xtream provider configured")?)
+            .context("composing get categories json response")?;
+
+        Ok(res)
     }
 
-    async fn proxy_streams<T>(&self, proxy_url: Url, prefix: &str) -> Result<Response<Body>, Error>
+    async fn proxy_streams<T>(
+        &self,
+        targets: &[XtreamTarget],
+        prefix: &str,
+        account: &XtreamAccount,
+    ) -> Result<Response<Body>, Error>
     where
         T: DeserializeOwned + Send + Serialize + Clone + HasId,
     {
-        let mut json = self
-            .proxy_util
-            .proxy_request_json::<Vec<T>>(&proxy_url)
-            .await?;
+        let mut merged = None;
 
-        match self
-            .provider_db_service
-            .get_latest_provider_entry(self.config.m3u_url.as_str())
-            .await
-        {
-            Some(latest_provider_entry) => {
-                let excluded_extinfs_ids = self
-                    .provider_db_service
-                    .get_exclude_eligible_by_m3u_id(
-                        latest_provider_entry.id,
-                        prefix,
-                        self.db.clone(),
-                    )
-                    .await?;
+        for target in targets {
+            let mut json = self
+                .proxy_util
+                .proxy_request_json::<Vec<T>>(&target.urls.original)
+                .await?;
 
-                let excluded_extinfs_ids = excluded_extinfs_ids
-                    .iter()
-                    .map(|extinf| serde_json::to_value(extinf).unwrap_or_default())
-                    .collect();
+            let latest_provider_entry = self.get_latest_provider_entry(&target.provider).await?;
 
-                let processed_json = self
-                    .process_json_entries(json.data, excluded_extinfs_ids, latest_provider_entry.id)
-                    .await?;
+            let excluded_extinfs_ids = self
+                .provider_db_service
+                .get_exclude_eligible_by_m3u_id(latest_provider_entry.id, prefix, self.db.clone())
+                .await?;
 
-                json.data = processed_json;
+            let excluded_extinfs_ids = excluded_extinfs_ids
+                .iter()
+                .map(|extinf| serde_json::to_value(extinf).unwrap_or_default())
+                .collect();
+
+            let processed_json = self
+                .process_json_entries(
+                    json.data,
+                    excluded_extinfs_ids,
+                    latest_provider_entry.id,
+                    account.id(),
+                )
+                .await?;
 
-                let res = self
-                    .response_util
-                    .compose_json_response(json)
-                    .context("composing streams json response")?;
+            let overridden_json = self
+                .apply_overrides(processed_json, latest_provider_entry.id)
+                .await
+                .context("applying channel overrides")?;
+
+            let entitled_json = self
+                .retain_entitled_entries(
+                    overridden_json,
+                    &target.provider,
+                    latest_provider_entry.id,
+                    account,
+                )
+                .await
+                .context("applying group entitlements")?;
 
-                Ok(res)
-            }
-            None => bail!("No provider entry found"),
+            json.data = self.namespace_entries(entitled_json, target.provider.slot)?;
+
+            self.merge_response_data(&mut merged, json);
         }
+
+        let res = self
+            .response_util
+            .compose_json_response(merged.context("No xtream provider configured")?)
+            .context("composing streams json response")?;
+
+        Ok(res)
     }
 
-    async fn proxy_series(&self, proxy_url: Url) -> Result<Response<Body>, Error> {
-        let mut json = self
-            .proxy_util
-            .proxy_request_json::<Vec<Series>>(&proxy_url)
-            .await
-            .context("getting series json")?;
+    async fn proxy_series(
+        &self,
+        targets: &[XtreamTarget],
+        account: &XtreamAccount,
+    ) -> Result<Response<Body>, Error> {
+        let mut merged = None;
 
-        match self
-            .provider_db_service
-            .get_latest_provider_entry(self.config.m3u_url.as_str())
-            .await
-        {
-            Some(latest_provider_entry) => {
-                let mut group_service = GroupDBService::new();
-                group_service.initialize_db(self.db.clone());
+        for target in targets {
+            let mut json = self
+                .proxy_util
+                .proxy_request_json::<Vec<Series>>(&target.urls.original)
+                .await
+                .context("getting series json")?;
 
-                let exclude_groups = group_service
-                    .get_excluded_groups(latest_provider_entry.id)
-                    .await?;
+            let latest_provider_entry = self.get_latest_provider_entry(&target.provider).await?;
+
+            let mut group_service = GroupDBService::new();
+            group_service.initialize_db(self.db.clone());
 
-                let exclude_groups: Vec<serde_json::Value> = exclude_groups
+            let exclude_groups: Vec<serde_json::Value> = if self.config.group_includes.is_empty() {
+                group_service
+                    .get_excluded_groups(latest_provider_entry.id)
+                    .await?
                     .iter()
                     .map(|group| {
                         serde_json::to_value(group.xtream_cat_id.unwrap_or_default())
                             .unwrap_or_default()
                     })
+                    .collect()
+            } else {
+                let included_cat_ids: Vec<String> = group_service
+                    .get_groups(latest_provider_entry.id)
+                    .await?
+                    .into_iter()
+                    .filter(|group| !group.exclude)
+                    .filter_map(|group| group.xtream_cat_id)
+                    .map(|cat_id| cat_id.to_string())
                     .collect();
 
-                let processed_json = self
-                    .process_json_entries(json.data, exclude_groups, latest_provider_entry.id)
-                    .await?;
+                json.data
+                    .iter()
+                    .filter_map(|series| series.category_id.clone())
+                    .filter(|cat_id| {
+                        !included_cat_ids
+                            .contains(&self.match_json_values(cat_id).unwrap_or_default())
+                    })
+                    .collect()
+            };
+
+            let processed_json = self
+                .process_json_entries(
+                    json.data,
+                    exclude_groups,
+                    latest_provider_entry.id,
+                    account.id(),
+                )
+                .await?;
 
-                json.data = processed_json;
+            let entitled_json = self
+                .retain_entitled_entries(
+                    processed_json,
+                    &target.provider,
+                    latest_provider_entry.id,
+                    account,
+                )
+                .await
+                .context("applying group entitlements")?;
 
-                let res = self
-                    .response_util
-                    .compose_json_response(json)
-                    .context("composing series json response")?;
+            json.data = self.namespace_entries(entitled_json, target.provider.slot)?;
 
-                Ok(res)
-            }
-            None => bail!("No provider entry found"),
+            self.merge_response_data(&mut merged, json);
         }
+
+        let res = self
+            .response_util
+            .compose_json_response(merged.context("No xtream provider configured")?)
+            .context("composing series json response")?;
+
+        Ok(res)
     }
 
-    async fn proxy_info<T>(&self, proxy_url: Url) -> Result<Response<Body>, Error>
+    async fn proxy_info<T>(
+        &self,
+        targets: &[XtreamTarget],
+        account: &XtreamAccount,
+    ) -> Result<Response<Body>, Error>
     where
         T: DeserializeOwned + Send + Serialize + Clone + HasId,
     {
+        let target = targets.first().context("No xtream provider configured")?;
+
         let mut json = self
             .proxy_util
-            .proxy_request_json::<SeriesInfo>(&proxy_url)
+            .proxy_request_json::<SeriesInfo>(&target.urls.original)
             .await?;
 
+        let latest_provider_entry = self.get_latest_provider_entry(&target.provider).await?;
+
+        if !account.groups.is_empty() {
+            let info = serde_json::to_value(&json.data)?;
+            let cat_id = ["info", "movie_data"]
+                .iter()
+                .filter_map(|key| info.get(key).and_then(|value| value.get("category_id")))
+                .find_map(|cat_id| self.match_json_values(cat_id).ok());
+
+            let entitled_cat_ids = self
+                .get_entitled_cat_ids(&target.provider, latest_provider_entry.id, account)
+                .await?;
+
+            if !cat_id.is_some_and(|cat_id| entitled_cat_ids.contains(&cat_id)) {
+                return Err(EntitlementError {
+                    username: account.username.clone(),
+                    id: target.urls.proxied.to_string(),
+                }
+                .into());
+            }
+        }
+
+        let processed_json = self
+            .process_json_entries(
+                vec![json.data],
+                vec![],
+                latest_provider_entry.id,
+                account.id(),
+            )
+            .await?;
+
+        let namespaced_json = self.namespace_entries(processed_json, target.provider.slot)?;
+
+        json.data = namespaced_json
+            .into_iter()
+            .next()
+            .context("Could not namespace info ids")?;
+
+        let res = self
+            .response_util
+            .compose_json_response(json)
+            .context("composing series json response")?;
+
+        Ok(res)
+    }
+
+    async fn get_latest_provider_entry(
+        &self,
+        provider: &ProviderConfig,
+    ) -> Result<ProviderModel, Error> {
         match self
             .provider_db_service
-            .get_latest_provider_entry(self.config.m3u_url.as_str())
+            .get_latest_provider_entry(&provider.source)
             .await
         {
-            Some(latest_provider_entry) => {
-                let mut group_service = GroupDBService::new();
-                group_service.initialize_db(self.db.clone());
-
-                let processed_json = self
-                    .process_json_entries(vec![json.data], vec![], latest_provider_entry.id)
-                    .await?;
-
-                json.data = processed_json.first().unwrap().to_owned();
+            Some(latest_provider_entry) => Ok(latest_provider_entry),
+            None => bail!("No provider entry found for provider {}", provider.name),
+        }
+    }
 
-                let res = self
-                    .response_util
-                    .compose_json_response(json)
-                    .context("composing series json response")?;
+    fn get_primary_xtream(&self) -> Result<XtreamConfig, Error> {
+        self.config
+            .xtream_providers()
+            .first()
+            .and_then(|provider| provider.xtream.clone())
+            .context("No xtream provider configured")
+    }
 
-                Ok(res)
+    fn merge_response_data<T>(
+        &self,
+        merged: &mut Option<ResponseData<Vec<T>>>,
+        json: ResponseData<Vec<T>>,
+    ) {
+        match merged {
+            Some(merged) => {
+                merged.headers.remove(CONTENT_LENGTH);
+                merged.data.extend(json.data);
             }
-            None => bail!("No provider entry found"),
+            None => *merged = Some(json),
         }
     }
 
@@ -413,6 +839,7 @@ impl XtreamService {
         json: Vec<T>,
         exclude_ids: Vec<serde_json::Value>,
         m3u_id: u64,
+        account_id: u64,
     ) -> Result<Vec<T>, Error>
     where
         T: DeserializeOwned + Send + Serialize + Clone + HasId,
@@ -436,16 +863,20 @@ impl XtreamService {
                 .match_json_values(entry.get_set_id())
                 .context("matching entry json value")?;
 
-            if exclude_ids
-                .clone()
-                .into_iter()
-                .find(|val| self.match_json_values(val).unwrap_or_default() == id)
-                == None
+            if !exclude_ids
+                .iter()
+                .any(|val| self.match_json_values(val).unwrap_or_default() == id)
             {
                 let yaml = to_value(&entry).context("serde_yaml::to_value not working")?;
 
                 let res = self
-                    .process_json_entry(yaml, &mut base_proxy_url.clone(), m3u_id, &mut tx)
+                    .process_json_entry(
+                        yaml,
+                        &mut base_proxy_url.clone(),
+                        m3u_id,
+                        account_id,
+                        &mut tx,
+                    )
                     .await
                     .context("proxying urls")?;
 
@@ -460,6 +891,227 @@ impl XtreamService {
         Ok(json_filtered)
     }
 
+    /// Drops the entries whose category the account isn't entitled to. Categories are matched by
+    /// the group title clients see, including the provider's group prefix.
+    async fn retain_entitled_entries<T>(
+        &self,
+        entries: Vec<T>,
+        provider: &ProviderConfig,
+        m3u_id: u64,
+        account: &XtreamAccount,
+    ) -> Result<Vec<T>, Error>
+    where
+        T: Serialize,
+    {
+        if account.groups.is_empty() {
+            return Ok(entries);
+        }
+
+        let entitled_cat_ids = self.get_entitled_cat_ids(provider, m3u_id, account).await?;
+
+        let entries = entries
+            .into_iter()
+            .filter(|entry| {
+                serde_json::to_value(entry)
+                    .ok()
+                    .and_then(|value| {
+                        value
+                            .get("category_id")
+                            .map(|cat_id| self.match_json_values(cat_id).unwrap_or_default())
+                    })
+                    .is_some_and(|cat_id| entitled_cat_ids.contains(&cat_id))
+            })
+            .collect();
+
+        Ok(entries)
+    }
+
+    /// The xtream category ids of the groups the account is entitled to.
+    async fn get_entitled_cat_ids(
+        &self,
+        provider: &ProviderConfig,
+        m3u_id: u64,
+        account: &XtreamAccount,
+    ) -> Result<Vec<String>, Error> {
+        let mut group_service = GroupDBService::new();
+        group_service.initialize_db(self.db.clone());
+
+        let entitled_cat_ids = group_service
+            .get_groups(m3u_id)
+            .await?
+            .into_iter()
+            .filter(|group| {
+                let group_title = match &provider.group_prefix {
+                    Some(group_prefix) => format!("{}{}", group_prefix, group.name),
+                    None => group.name.clone(),
+                };
+
+                account.is_entitled(&group_title)
+            })
+            .filter_map(|group| group.xtream_cat_id)
+            .map(|cat_id| cat_id.to_string())
+            .collect();
+
+        Ok(entitled_cat_ids)
+    }
+
+    async fn apply_overrides<T>(&self, entries: Vec<T>, m3u_id: u64) -> Result<Vec<T>, Error>
+    where
+        T: DeserializeOwned + Serialize,
+    {
+        let mut channel_override_service = ChannelOverrideDBService::new();
+        channel_override_service.initialize_db(self.db.clone());
+
+        let overrides = channel_override_service.get_overrides().await?;
+
+        if overrides.is_empty() {
+            return Ok(entries);
+        }
+
+        let mut group_service = GroupDBService::new();
+        group_service.initialize_db(self.db.clone());
+
+        let groups = group_service.get_groups(m3u_id).await?;
+
+        let mut entries_overridden = vec![];
+
+        for entry in entries {
+            let mut value = serde_json::to_value(&entry)?;
+
+            if let Some(entry_json) = value.as_object_mut() {
+                let stream_id = entry_json
+                    .get("stream_id")
+                    .map(|stream_id| self.match_json_values(stream_id).unwrap_or_default());
+
+                let tvg_id = entry_json
+                    .get("epg_channel_id")
+                    .and_then(|tvg_id| tvg_id.as_str());
+
+                if let Some(channel_override) =
+                    find_override(&overrides, tvg_id, stream_id.as_deref())
+                {
+                    let channel_override = channel_override.clone();
+
+                    if let Some(name) = channel_override.name {
+                        entry_json.insert(String::from("name"), name.into());
+                    }
+
+                    if let Some(logo) = channel_override.logo {
+                        entry_json.insert(String::from("stream_icon"), logo.into());
+                    }
+
+                    if let Some(chno) = channel_override.chno {
+                        let num = chno
+                            .parse::<u64>()
+                            .map(serde_json::Value::from)
+                            .unwrap_or(chno.into());
+
+                        entry_json.insert(String::from("num"), num);
+                    }
+
+                    let category_id = channel_override.group_title.and_then(|group_title| {
+                        groups
+                            .iter()
+                            .find(|group| group.name == group_title)
+                            .and_then(|group| group.xtream_cat_id)
+                    });
+
+                    if let Some(category_id) = category_id {
+                        entry_json
+                            .insert(String::from("category_id"), category_id.to_string().into());
+                    }
+                }
+            }
+
+            entries_overridden.push(serde_json::from_value::<T>(value)?);
+        }
+
+        Ok(entries_overridden)
+    }
+
+    fn namespace_entries<T>(&self, entries: Vec<T>, slot: u64) -> Result<Vec<T>, Error>
+    where
+        T: DeserializeOwned + Serialize,
+    {
+        if slot == 0 {
+            return Ok(entries);
+        }
+
+        let mut entries_namespaced = vec![];
+
+        for entry in entries {
+            let mut value = serde_json::to_value(&entry)?;
+
+            if let Err(err) = self.namespace_json_ids(&mut value, slot) {
+                error!("Skipped xtream entry: {}", err);
+                continue;
+            }
+
+            entries_namespaced.push(serde_json::from_value::<T>(value)?);
+        }
+
+        Ok(entries_namespaced)
+    }
+
+    fn namespace_json_ids(&self, value: &mut serde_json::Value, slot: u64) -> Result<(), Error> {
+        match value {
+            serde_json::Value::Object(entry) => {
+                for (key, value) in entry.iter_mut() {
+                    match key.as_str() {
+                        "stream_id" | "series_id" | "category_id" => {
+                            self.namespace_json_id(value, slot)?
+                        }
+                        "episodes" => self.namespace_episode_ids(value, slot)?,
+                        _ => self.namespace_json_ids(value, slot)?,
+                    }
+                }
+            }
+            serde_json::Value::Array(values) => {
+                for value in values.iter_mut() {
+                    self.namespace_json_ids(value, slot)?;
+                }
+            }
+            _ => (),
+        }
+
+        Ok(())
+    }
+
+    fn namespace_episode_ids(&self, value: &mut serde_json::Value, slot: u64) -> Result<(), Error> {
+        match value {
+            serde_json::Value::Object(seasons) => {
+                for season in seasons.values_mut() {
+                    self.namespace_episode_ids(season, slot)?;
+                }
+            }
+            serde_json::Value::Array(episodes) => {
+                for episode in episodes.iter_mut() {
+                    match episode.get_mut("id") {
+                        Some(id) => self.namespace_json_id(id, slot)?,
+                        None => self.namespace_episode_ids(episode, slot)?,
+                    }
+                }
+            }
+            _ => (),
+        }
+
+        Ok(())
+    }
+
+    fn namespace_json_id(&self, value: &mut serde_json::Value, slot: u64) -> Result<(), Error> {
+        match value {
+            serde_json::Value::Number(id) => {
+                if let Some(id) = id.as_u64() {
+                    *value = namespace_id(slot, id)?.into();
+                }
+            }
+            serde_json::Value::String(id) => *id = namespace_stream_id(slot, id)?,
+            _ => (),
+        }
+
+        Ok(())
+    }
+
     fn match_json_values(&self, value: &serde_json::Value) -> Result<String, Error> {
         match value {
             serde_json::Value::Number(value) => Ok(value.to_string()),
@@ -473,16 +1125,14 @@ impl XtreamService {
         value: Value,
         proxy_url: &mut String,
         m3u_id: u64,
+        account_id: u64,
         tx: &mut Connection,
     ) -> Result<String, Error> {
         let mut mapping = Mapping::new();
 
-        match value {
-            Value::Mapping(val) => {
-                self.map_mapping(val, &mut mapping, proxy_url, m3u_id, tx)
-                    .await?
-            }
-            _ => (),
+        if let Value::Mapping(val) = value {
+            self.map_mapping(val, &mut mapping, proxy_url, m3u_id, account_id, tx)
+                .await?
         }
 
         let json_value: serde_json::Value =
@@ -500,31 +1150,37 @@ impl XtreamService {
         mut_mapping: &mut Mapping,
         proxy_url: &mut String,
         m3u_id: u64,
+        account_id: u64,
         tx: &mut Connection,
     ) -> Result<(), Error> {
-        for (_, (key, value)) in mapping.iter().enumerate() {
+        for (key, value) in mapping.iter() {
             match value {
                 Value::Sequence(value) => {
-                    self.map_sequence(
+                    let sequence = self
+                        .map_sequence(value, proxy_url, m3u_id, account_id, tx)
+                        .await?;
+
+                    mut_mapping.insert(key.to_owned(), sequence);
+                }
+                Value::Mapping(value) => {
+                    let mut mapping = Mapping::new();
+
+                    self.map_mapping(
                         value.to_owned(),
-                        key.to_owned(),
-                        mut_mapping,
+                        &mut mapping,
                         proxy_url,
                         m3u_id,
+                        account_id,
                         tx,
                     )
                     .await?;
-                }
-                Value::Mapping(value) => {
-                    let mut mapping = Mapping::new();
-
-                    self.map_mapping(value.to_owned(), &mut mapping, proxy_url, m3u_id, tx)
-                        .await?;
 
                     mut_mapping.insert(key.to_owned(), serde_yaml::Value::Mapping(mapping));
                 }
                 _ => {
-                    let value = self.try_proxify_entry(value, m3u_id, proxy_url, tx).await?;
+                    let value = self
+                        .try_proxify_entry(value, m3u_id, proxy_url, account_id, tx)
+                        .await?;
                     mut_mapping.insert(key.to_owned(), value);
                 }
             }
@@ -535,26 +1191,23 @@ impl XtreamService {
 
     async fn map_sequence(
         &self,
-        sequence: Sequence,
-        key: Value,
-        mapping: &mut Mapping,
+        sequence: &Sequence,
         proxy_url: &mut String,
         m3u_id: u64,
+        account_id: u64,
         tx: &mut Connection,
-    ) -> Result<(), Error> {
+    ) -> Result<Value, Error> {
         let mut vec = vec![];
 
         for entry in sequence {
             let value = self
-                .try_proxify_entry(&entry, m3u_id, proxy_url, tx)
+                .try_proxify_entry(entry, m3u_id, proxy_url, account_id, tx)
                 .await?;
 
             vec.push(value);
         }
 
-        mapping.insert(key, to_value(vec).context("vec to yaml cast failed")?);
-
-        Ok(())
+        to_value(vec).context("vec to yaml cast failed")
     }
 
     async fn try_proxify_entry(
@@ -562,6 +1215,7 @@ impl XtreamService {
         value: &Value,
         m3u_id: u64,
         proxy_url: &mut String,
+        account_id: u64,
         tx: &mut Connection,
     ) -> Result<Value, Error> {
         match Url::parse(value.as_str().unwrap_or_default()) {
@@ -590,61 +1244,112 @@ impl XtreamService {
 
                     write!(proxy_url, "{}", id)?;
 
-                    let proxy_url = &to_value(proxy_url).context("proxified url to yaml failed")?;
+                    let proxy_url =
+                        &to_value(self.stream_token_util.sign_url(proxy_url, account_id))
+                            .context("proxified url to yaml failed")?;
 
-                    return Ok(proxy_url.to_owned());
+                    Ok(proxy_url.to_owned())
                 } else {
-                    return Ok(value.to_owned());
+                    Ok(value.to_owned())
                 }
             }
-            Err(_) => return Ok(value.to_owned()),
+            Err(_) => Ok(value.to_owned()),
         }
     }
 
-    fn compose_credentials_query_string(&self) -> String {
-        let query = format!(
-            "?username={}&password={}",
-            self.config.xtream.xtream_username, self.config.xtream.xtream_password,
-        );
+    fn compose_action_targets(
+        &self,
+        full_path: &str,
+        action: String,
+        mut optional_params: OptionalParams,
+    ) -> Result<Vec<XtreamTarget>, Error> {
+        let providers = match self.split_optional_params(&mut optional_params) {
+            Some(slot) => self.config.get_provider_by_slot(slot).into_iter().collect(),
+            None => self.config.xtream_providers(),
+        };
+
+        ensure!(!providers.is_empty(), "No xtream provider configured");
+
+        providers
+            .into_iter()
+            .map(|provider| {
+                let xtream = provider.xtream.clone().unwrap_or_default();
+                let query =
+                    self.compose_action_query_string(&xtream, action.clone(), &optional_params);
+
+                Ok(XtreamTarget {
+                    provider: provider.clone(),
+                    urls: self.compose_action_url(&xtream, full_path, query)?,
+                })
+            })
+            .collect()
+    }
+
+    fn split_optional_params(&self, optional_params: &mut OptionalParams) -> Option<u64> {
+        let mut slot = None;
+
+        for param in [
+            &mut optional_params.category_id,
+            &mut optional_params.series_id,
+            &mut optional_params.vod_id,
+            &mut optional_params.stream_id,
+        ] {
+            if let Some(id) = param.as_ref().and_then(|id| id.parse::<u64>().ok()) {
+                let (id_slot, id) = split_stream_id(id);
+
+                *param = Some(id.to_string());
+                slot.get_or_insert(id_slot);
+            }
+        }
+
+        slot
+    }
+
+    fn compose_credentials_query_string(&self, xtream: &XtreamConfig) -> String {
+        let query = format!("?username={}&password={}", xtream.username, xtream.password,);
 
         query
     }
 
     fn compose_action_query_string(
         &self,
+        xtream: &XtreamConfig,
         action: String,
-        optional_params: OptionalParams,
+        optional_params: &OptionalParams,
     ) -> String {
         let mut query = format!(
             "?username={}&password={}&action={}",
-            self.config.xtream.xtream_username, self.config.xtream.xtream_password, action
+            xtream.username, xtream.password, action
         );
 
-        if let Some(category_id) = optional_params.category_id {
+        if let Some(category_id) = &optional_params.category_id {
             query = format!("{}&category_id={}", query, category_id);
         }
 
-        if let Some(vod_id) = optional_params.vod_id {
+        if let Some(vod_id) = &optional_params.vod_id {
             query = format!("{}&vod_id={}", query, vod_id);
         }
 
-        if let Some(series_id) = optional_params.series_id {
+        if let Some(series_id) = &optional_params.series_id {
             query = format!("{}&series_id={}", query, series_id);
         }
 
-        if let Some(stream_id) = optional_params.stream_id {
+        if let Some(stream_id) = &optional_params.stream_id {
             query = format!("{}&stream_id={}", query, stream_id);
         }
 
         query
     }
 
-    fn compose_action_url(&self, full_path: &str, query: String) -> Result<XtreamUrl, Error> {
-        let original = self.url_util.parse_url(
-            self.config.xtream.xtream_base_domain.clone(),
-            full_path,
-            Some(query.clone()),
-        )?;
+    fn compose_action_url(
+        &self,
+        xtream: &XtreamConfig,
+        full_path: &str,
+        query: String,
+    ) -> Result<XtreamUrl, Error> {
+        let original =
+            self.url_util
+                .parse_url(xtream.base_domain.clone(), full_path, Some(query.clone()))?;
 
         let proxied = self.url_util.parse_url(
             self.config
@@ -661,10 +1366,14 @@ impl XtreamService {
         Ok(urls)
     }
 
-    fn compose_login_url(&self, full_path: &str) -> Result<XtreamUrl, Error> {
+    fn compose_login_url(
+        &self,
+        xtream: &XtreamConfig,
+        full_path: &str,
+    ) -> Result<XtreamUrl, Error> {
         let original_query = Some(format!(
             "?username={}&password={}",
-            self.config.xtream.xtream_username, self.config.xtream.xtream_password
+            xtream.username, xtream.password
         ));
 
         let proxied_query = Some(format!(
@@ -673,7 +1382,7 @@ impl XtreamService {
         ));
 
         let original = self.url_util.parse_url(
-            self.config.xtream.xtream_base_domain.clone(),
+            xtream.base_domain.clone(),
             full_path,
             original_query.clone(),
         )?;
@@ -693,12 +1402,15 @@ impl XtreamService {
         Ok(urls)
     }
 
-    fn compose_xmltv_url(&self, full_path: &str, query: String) -> Result<XtreamUrl, Error> {
-        let original = self.url_util.parse_url(
-            self.config.xtream.xtream_base_domain.clone(),
-            full_path,
-            Some(query.clone()),
-        )?;
+    fn compose_xmltv_url(
+        &self,
+        xtream: &XtreamConfig,
+        full_path: &str,
+        query: String,
+    ) -> Result<XtreamUrl, Error> {
+        let original =
+            self.url_util
+                .parse_url(xtream.base_domain.clone(), full_path, Some(query.clone()))?;
 
         let proxied = self.url_util.parse_url(
             self.config
@@ -715,9 +1427,14 @@ impl XtreamService {
         Ok(urls)
     }
 
-    pub async fn proxy_xmltv(&self, full_path: &str) -> Result<Response<Body>, Error> {
-        let cred_query = self.compose_credentials_query_string();
-        let url = self.compose_xmltv_url(full_path, cred_query)?;
+    pub async fn proxy_xmltv(
+        &self,
+        full_path: &str,
+        account: &XtreamAccount,
+    ) -> Result<Response<Body>, Error> {
+        let xtream = self.get_primary_xtream()?;
+        let cred_query = self.compose_credentials_query_string(&xtream);
+        let url = self.compose_xmltv_url(&xtream, full_path, cred_query)?;
 
         let response = self.client.get(&url.original).await?;
         let status_code = response.status();
@@ -737,6 +1454,7 @@ impl XtreamService {
                     .xtream_proxied_domain
                     .clone()
                     .unwrap_or_default(),
+                account.id(),
             )
             .await?;
 
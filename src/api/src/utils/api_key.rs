@@ -0,0 +1,95 @@
+use std::fmt::Write;
+
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use sha2::{Digest, Sha256};
+
+use crate::models::ApiConfiguration;
+
+const API_KEY_LENGTH: usize = 48;
+
+#[derive(Clone)]
+pub struct ApiKeyUtil {
+    admin_key_hash: Option<String>,
+}
+
+impl ApiKeyUtil {
+    pub fn new(config: &ApiConfiguration) -> Self {
+        ApiKeyUtil {
+            admin_key_hash: config.admin_api_key.as_deref().map(hash_api_key),
+        }
+    }
+
+    pub fn generate(&self) -> String {
+        thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(API_KEY_LENGTH)
+            .map(char::from)
+            .collect()
+    }
+
+    pub fn hash(&self, key: &str) -> String {
+        hash_api_key(key)
+    }
+
+    /// Whether the hash belongs to the key configured through `ADMIN_API_KEY`.
+    pub fn is_admin_key(&self, key_hash: &str) -> bool {
+        self.admin_key_hash
+            .as_deref()
+            .is_some_and(|admin_key_hash| admin_key_hash == key_hash)
+    }
+}
+
+fn hash_api_key(key: &str) -> String {
+    Sha256::digest(key.as_bytes())
+        .iter()
+        .fold(String::with_capacity(64), |mut hex, byte| {
+            let _ = write!(hex, "{:02x}", byte);
+            hex
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn api_key_util(admin_api_key: Option<&str>) -> ApiKeyUtil {
+        ApiKeyUtil::new(&ApiConfiguration {
+            admin_api_key: admin_api_key.map(String::from),
+            ..ApiConfiguration::default()
+        })
+    }
+
+    #[test]
+    fn hashes_keys_with_sha256() {
+        assert_eq!(
+            api_key_util(None).hash("abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn generates_distinct_alphanumeric_keys() {
+        let api_key_util = api_key_util(None);
+        let key = api_key_util.generate();
+
+        assert_eq!(key.len(), API_KEY_LENGTH);
+        assert!(key.chars().all(|c| c.is_ascii_alphanumeric()));
+        assert_ne!(key, api_key_util.generate());
+    }
+
+    #[test]
+    fn recognizes_configured_admin_key() {
+        let api_key_util = api_key_util(Some("admin-key"));
+
+        assert!(api_key_util.is_admin_key(&api_key_util.hash("admin-key")));
+        assert!(!api_key_util.is_admin_key(&api_key_util.hash("other-key")));
+        assert!(!api_key_util.is_admin_key("admin-key"));
+    }
+
+    #[test]
+    fn has_no_admin_key_when_unset() {
+        let api_key_util = api_key_util(None);
+
+        assert!(!api_key_util.is_admin_key(&api_key_util.hash("")));
+    }
+}
@@ -0,0 +1,541 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use anyhow::Error;
+use futures::{stream, Stream, StreamExt};
+use iptv::models::ProviderConfig;
+use log::{debug, info, warn};
+use tokio::{
+    spawn,
+    sync::{broadcast, broadcast::error::RecvError, watch, OwnedSemaphorePermit, Semaphore},
+    time::timeout,
+};
+use warp::{
+    http::{
+        header::{CONTENT_LENGTH, CONTENT_TYPE, RANGE},
+        HeaderMap, StatusCode,
+    },
+    hyper::{body::Bytes, Body, Response},
+};
+
+use crate::models::{
+    error::{ConnectionLimitError, UserConnectionLimitError},
+    xtream::XtreamAccount,
+    ApiConfiguration,
+};
+
+const SHARED_STREAM_CAPACITY: usize = 512;
+
+#[derive(Clone)]
+pub struct ConnectionManager {
+    limits: Arc<HashMap<u64, (u32, Arc<Semaphore>)>>,
+    queue_timeout: Duration,
+    shared_streams: Arc<Mutex<HashMap<String, SharedSlot>>>,
+    next_stream_id: Arc<AtomicU64>,
+    user_connections: Arc<Mutex<HashMap<String, u32>>>,
+}
+
+/// Counts as one of the open connections of an xtream user until dropped.
+pub struct UserPermit {
+    username: String,
+    user_connections: Arc<Mutex<HashMap<String, u32>>>,
+}
+
+impl Drop for UserPermit {
+    fn drop(&mut self) {
+        let mut user_connections = self.user_connections.lock().unwrap();
+
+        if let Some(connections) = user_connections.get_mut(&self.username) {
+            *connections = connections.saturating_sub(1);
+
+            if *connections == 0 {
+                user_connections.remove(&self.username);
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+struct SharedStream {
+    id: u64,
+    status: StatusCode,
+    headers: HeaderMap,
+    sender: broadcast::Sender<Bytes>,
+}
+
+enum SharedSlot {
+    /// The first viewer is opening the upstream. The receiver is notified once it is shared or
+    /// given up.
+    Starting {
+        id: u64,
+        ready: watch::Receiver<()>,
+    },
+    Running(SharedStream),
+}
+
+/// How a viewer gets its stream.
+pub enum Subscription {
+    /// Another viewer already streams the same upstream.
+    Shared(Response<Body>),
+    /// The viewer opens the upstream and shares it through the guard.
+    First(ShareGuard),
+    /// The viewer streams on its own.
+    Exclusive,
+}
+
+/// Holds the starting slot of a shared stream, so that viewers arriving meanwhile wait for the
+/// upstream instead of opening their own. Dropped without sharing, the waiting viewers retry.
+pub struct ShareGuard {
+    key: String,
+    id: u64,
+    shared_streams: Arc<Mutex<HashMap<String, SharedSlot>>>,
+    _ready: watch::Sender<()>,
+}
+
+impl Drop for ShareGuard {
+    fn drop(&mut self) {
+        let mut shared_streams = self.shared_streams.lock().unwrap();
+
+        if let Some(SharedSlot::Starting { id, .. }) = shared_streams.get(&self.key) {
+            if *id == self.id {
+                shared_streams.remove(&self.key);
+            }
+        }
+    }
+}
+
+impl ConnectionManager {
+    pub fn new(config: &ApiConfiguration) -> Self {
+        let limits = config
+            .providers
+            .iter()
+            .filter_map(|provider| {
+                provider
+                    .max_connections
+                    .filter(|max_connections| *max_connections > 0)
+                    .map(|max_connections| {
+                        let semaphore = Arc::new(Semaphore::new(max_connections as usize));
+
+                        (provider.slot, (max_connections, semaphore))
+                    })
+            })
+            .collect();
+
+        ConnectionManager {
+            limits: Arc::new(limits),
+            queue_timeout: Duration::from_secs(config.connection_queue_timeout),
+            shared_streams: Arc::new(Mutex::new(HashMap::new())),
+            next_stream_id: Arc::new(AtomicU64::new(0)),
+            user_connections: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub async fn acquire(
+        &self,
+        provider: Option<&ProviderConfig>,
+    ) -> Result<Option<OwnedSemaphorePermit>, Error> {
+        let (provider, (max_connections, semaphore)) = match provider
+            .and_then(|provider| Some((provider, self.limits.get(&provider.slot)?)))
+        {
+            Some(limit) => limit,
+            None => return Ok(None),
+        };
+
+        let permit = if self.queue_timeout.is_zero() {
+            semaphore.clone().try_acquire_owned().ok()
+        } else {
+            timeout(self.queue_timeout, semaphore.clone().acquire_owned())
+                .await
+                .ok()
+                .and_then(Result::ok)
+        };
+
+        match permit {
+            Some(permit) => {
+                debug!(
+                    "{} of {} connections in use for provider {}",
+                    *max_connections as usize - semaphore.available_permits(),
+                    max_connections,
+                    provider.name
+                );
+
+                Ok(Some(permit))
+            }
+            None => Err(ConnectionLimitError {
+                provider: provider.name.clone(),
+                max_connections: *max_connections,
+            }
+            .into()),
+        }
+    }
+
+    /// Counts a connection of an xtream user against its connection limit. Accounts that are not
+    /// xtream users are not tracked.
+    pub fn acquire_user(&self, account: &XtreamAccount) -> Result<Option<UserPermit>, Error> {
+        let user = match &account.user {
+            Some(user) => user,
+            None => return Ok(None),
+        };
+
+        let mut user_connections = self.user_connections.lock().unwrap();
+        let open_connections = user_connections
+            .get(&user.username)
+            .copied()
+            .unwrap_or_default();
+
+        if let Some(max_connections) = user.max_connections.filter(|max| *max > 0) {
+            if open_connections >= max_connections {
+                return Err(UserConnectionLimitError {
+                    username: user.username.clone(),
+                    max_connections,
+                }
+                .into());
+            }
+        }
+
+        let connections = user_connections.entry(user.username.clone()).or_default();
+        *connections += 1;
+
+        debug!(
+            "{} connections in use for user {}",
+            connections, user.username
+        );
+
+        Ok(Some(UserPermit {
+            username: user.username.clone(),
+            user_connections: self.user_connections.clone(),
+        }))
+    }
+
+    pub fn get_user_connections(&self, username: &str) -> u32 {
+        self.user_connections
+            .lock()
+            .unwrap()
+            .get(username)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Keeps the user permit for as long as the client reads the response body.
+    pub fn guard_user_response(
+        &self,
+        res: Response<Body>,
+        permit: Option<UserPermit>,
+    ) -> Response<Body> {
+        match permit {
+            Some(permit) => res.map(|body| {
+                Body::wrap_stream(body.map(move |chunk| {
+                    let _permit = &permit;
+
+                    chunk
+                }))
+            }),
+            None => res,
+        }
+    }
+
+    pub fn is_shareable(
+        &self,
+        url: &str,
+        request_headers: &HeaderMap,
+        response_headers: &HeaderMap,
+    ) -> bool {
+        let is_mpeg_ts = response_headers
+            .get(CONTENT_TYPE)
+            .and_then(|content_type| content_type.to_str().ok())
+            .is_some_and(|content_type| content_type.contains("mp2t"))
+            || url.ends_with(".ts");
+
+        is_mpeg_ts && !request_headers.contains_key(RANGE)
+    }
+
+    /// Joins the shared stream of the key, or waits for it while another viewer opens it.
+    /// Ranged requests stream on their own, since a shared stream can only be watched from its
+    /// live position.
+    pub async fn subscribe(&self, key: &str, request_headers: &HeaderMap) -> Subscription {
+        if request_headers.contains_key(RANGE) {
+            return Subscription::Exclusive;
+        }
+
+        loop {
+            let mut ready = {
+                let mut shared_streams = self.shared_streams.lock().unwrap();
+
+                match shared_streams.get(key) {
+                    Some(SharedSlot::Running(shared_stream)) => {
+                        info!(
+                            "Sharing upstream stream with {} other viewers: {}",
+                            shared_stream.sender.receiver_count(),
+                            key
+                        );
+
+                        return Subscription::Shared(compose_shared_response(
+                            shared_stream.status,
+                            shared_stream.headers.clone(),
+                            shared_stream.sender.subscribe(),
+                        ));
+                    }
+                    Some(SharedSlot::Starting { ready, .. }) => ready.clone(),
+                    None => {
+                        let id = self.next_stream_id.fetch_add(1, Ordering::Relaxed);
+                        let (sender, ready) = watch::channel(());
+
+                        shared_streams.insert(key.to_string(), SharedSlot::Starting { id, ready });
+
+                        return Subscription::First(ShareGuard {
+                            key: key.to_string(),
+                            id,
+                            shared_streams: self.shared_streams.clone(),
+                            _ready: sender,
+                        });
+                    }
+                }
+            };
+
+            debug!("Waiting for the upstream stream of another viewer: {}", key);
+
+            let _ = ready.changed().await;
+        }
+    }
+
+    pub fn share<S>(
+        &self,
+        share_guard: ShareGuard,
+        status: StatusCode,
+        mut headers: HeaderMap,
+        upstream: S,
+        permit: Option<OwnedSemaphorePermit>,
+    ) -> Response<Body>
+    where
+        S: Stream<Item = Result<Bytes, reqwest::Error>> + Send + 'static,
+    {
+        let (sender, receiver) = broadcast::channel(SHARED_STREAM_CAPACITY);
+        let key = share_guard.key.clone();
+        let id = share_guard.id;
+
+        headers.remove(CONTENT_LENGTH);
+
+        self.shared_streams.lock().unwrap().insert(
+            key.clone(),
+            SharedSlot::Running(SharedStream {
+                id,
+                status,
+                headers: headers.clone(),
+                sender: sender.clone(),
+            }),
+        );
+
+        drop(share_guard);
+
+        let shared_streams = self.shared_streams.clone();
+
+        spawn(async move {
+            let _permit = permit;
+            let mut upstream = Box::pin(upstream);
+
+            while let Some(Ok(chunk)) = upstream.next().await {
+                if sender.send(chunk).is_err() {
+                    break;
+                }
+            }
+
+            let mut shared_streams = shared_streams.lock().unwrap();
+
+            if let Some(SharedSlot::Running(shared_stream)) = shared_streams.get(&key) {
+                if shared_stream.id == id {
+                    shared_streams.remove(&key);
+                }
+            }
+
+            debug!("Closed shared upstream stream {}", key);
+        });
+
+        compose_shared_response(status, headers, receiver)
+    }
+
+    pub fn guard_body<S>(&self, upstream: S, permit: Option<OwnedSemaphorePermit>) -> Body
+    where
+        S: Stream<Item = Result<Bytes, reqwest::Error>> + Send + 'static,
+    {
+        Body::wrap_stream(upstream.map(move |chunk| {
+            let _permit = &permit;
+
+            chunk
+        }))
+    }
+}
+
+/// Ends the response of a viewer that lags behind the upstream, since the chunks it missed would
+/// leave holes in its stream. The player reconnects and joins the shared stream again.
+fn compose_shared_response(
+    status: StatusCode,
+    headers: HeaderMap,
+    receiver: broadcast::Receiver<Bytes>,
+) -> Response<Body> {
+    let body = stream::unfold(receiver, |mut receiver| async move {
+        match receiver.recv().await {
+            Ok(chunk) => Some((Ok::<Bytes, RecvError>(chunk), receiver)),
+            Err(RecvError::Lagged(skipped)) => {
+                warn!(
+                    "Shared stream viewer lagged behind by {} chunks, closing its response",
+                    skipped
+                );
+
+                None
+            }
+            Err(RecvError::Closed) => None,
+        }
+    });
+
+    let mut response = Response::new(Body::wrap_stream(body));
+
+    *response.status_mut() = status;
+    *response.headers_mut() = headers;
+
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use warp::hyper::body::to_bytes;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn forwards_shared_chunks() {
+        let (sender, receiver) = broadcast::channel(4);
+        let response = compose_shared_response(StatusCode::OK, HeaderMap::new(), receiver);
+
+        sender.send(Bytes::from_static(b"first ")).unwrap();
+        sender.send(Bytes::from_static(b"second")).unwrap();
+        drop(sender);
+
+        let body = to_bytes(response.into_body()).await.unwrap();
+
+        assert_eq!(body, Bytes::from_static(b"first second"));
+    }
+
+    #[tokio::test]
+    async fn ends_response_of_lagging_viewer() {
+        let (sender, receiver) = broadcast::channel(2);
+        let response = compose_shared_response(StatusCode::OK, HeaderMap::new(), receiver);
+
+        for chunk in [b"1", b"2", b"3", b"4"] {
+            sender.send(Bytes::from_static(chunk)).unwrap();
+        }
+
+        let body = to_bytes(response.into_body()).await.unwrap();
+
+        assert!(body.is_empty());
+        assert!(sender.send(Bytes::from_static(b"5")).is_err());
+    }
+
+    fn share_pending(connection_manager: &ConnectionManager, share_guard: ShareGuard) {
+        let _ = connection_manager.share(
+            share_guard,
+            StatusCode::OK,
+            HeaderMap::new(),
+            stream::pending::<Result<Bytes, reqwest::Error>>(),
+            None,
+        );
+    }
+
+    #[tokio::test]
+    async fn keeps_ranged_requests_off_shared_streams() {
+        let connection_manager = ConnectionManager::new(&ApiConfiguration::default());
+
+        match connection_manager
+            .subscribe("http://host/movie/1.ts", &HeaderMap::new())
+            .await
+        {
+            Subscription::First(share_guard) => share_pending(&connection_manager, share_guard),
+            _ => panic!("first viewer should open the upstream"),
+        }
+
+        let mut ranged_headers = HeaderMap::new();
+        ranged_headers.insert(RANGE, "bytes=1000-".parse().unwrap());
+
+        assert!(matches!(
+            connection_manager
+                .subscribe("http://host/movie/1.ts", &ranged_headers)
+                .await,
+            Subscription::Exclusive
+        ));
+        assert!(matches!(
+            connection_manager
+                .subscribe("http://host/movie/1.ts", &HeaderMap::new())
+                .await,
+            Subscription::Shared(_)
+        ));
+    }
+
+    #[tokio::test]
+    async fn waits_for_upstream_of_first_viewer() {
+        let connection_manager = ConnectionManager::new(&ApiConfiguration::default());
+
+        let share_guard = match connection_manager
+            .subscribe("http://host/live/1.ts", &HeaderMap::new())
+            .await
+        {
+            Subscription::First(share_guard) => share_guard,
+            _ => panic!("first viewer should open the upstream"),
+        };
+
+        let second_viewer = spawn({
+            let connection_manager = connection_manager.clone();
+
+            async move {
+                connection_manager
+                    .subscribe("http://host/live/1.ts", &HeaderMap::new())
+                    .await
+            }
+        });
+
+        tokio::task::yield_now().await;
+        assert!(!second_viewer.is_finished());
+
+        share_pending(&connection_manager, share_guard);
+
+        assert!(matches!(
+            second_viewer.await.unwrap(),
+            Subscription::Shared(_)
+        ));
+    }
+
+    #[tokio::test]
+    async fn lets_waiting_viewer_open_upstream_when_first_viewer_gives_up() {
+        let connection_manager = ConnectionManager::new(&ApiConfiguration::default());
+
+        let share_guard = match connection_manager
+            .subscribe("http://host/live/1.ts", &HeaderMap::new())
+            .await
+        {
+            Subscription::First(share_guard) => share_guard,
+            _ => panic!("first viewer should open the upstream"),
+        };
+
+        let second_viewer = spawn({
+            let connection_manager = connection_manager.clone();
+
+            async move {
+                connection_manager
+                    .subscribe("http://host/live/1.ts", &HeaderMap::new())
+                    .await
+            }
+        });
+
+        tokio::task::yield_now().await;
+        drop(share_guard);
+
+        assert!(matches!(
+            second_viewer.await.unwrap(),
+            Subscription::First(_)
+        ));
+    }
+}
@@ -0,0 +1,171 @@
+use iptv::models::ProviderConfig;
+use warp::http::{
+    header::{HeaderName, CONNECTION, CONTENT_LENGTH, HOST},
+    HeaderMap,
+};
+
+const HOP_BY_HOP_HEADERS: [&str; 8] = [
+    "connection",
+    "keep-alive",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+];
+
+const SERVER_IDENTITY_HEADERS: [&str; 5] = [
+    "server",
+    "via",
+    "x-powered-by",
+    "x-aspnet-version",
+    "x-aspnetmvc-version",
+];
+
+#[derive(Default, Debug, Clone, PartialEq, Copy)]
+pub struct HeaderUtil;
+
+impl HeaderUtil {
+    pub fn new() -> Self {
+        HeaderUtil
+    }
+
+    /// Client headers as they are sent upstream: without hop-by-hop headers and `Host`, and with
+    /// the headers of the provider's HTTP profile taking precedence.
+    pub fn compose_request_headers(
+        &self,
+        headers: &HeaderMap,
+        provider: Option<&ProviderConfig>,
+    ) -> HeaderMap {
+        let mut headers = self.strip_hop_by_hop_headers(headers);
+
+        headers.remove(HOST);
+        headers.remove(CONTENT_LENGTH);
+
+        if let Some(provider) = provider {
+            for (name, value) in provider.http.compose_headers().iter() {
+                headers.insert(name, value.clone());
+            }
+        }
+
+        headers
+    }
+
+    /// Upstream headers as they are sent to clients: without hop-by-hop headers and without
+    /// headers that identify the upstream server.
+    pub fn compose_response_headers(&self, headers: &HeaderMap) -> HeaderMap {
+        let mut headers = self.strip_hop_by_hop_headers(headers);
+
+        for name in SERVER_IDENTITY_HEADERS {
+            headers.remove(name);
+        }
+
+        headers
+    }
+
+    fn strip_hop_by_hop_headers(&self, headers: &HeaderMap) -> HeaderMap {
+        let mut headers = headers.clone();
+
+        let connection_headers = headers
+            .get_all(CONNECTION)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .filter_map(|name| HeaderName::from_bytes(name.trim().as_bytes()).ok())
+            .collect::<Vec<HeaderName>>();
+
+        for name in connection_headers {
+            headers.remove(name);
+        }
+
+        for name in HOP_BY_HOP_HEADERS {
+            headers.remove(name);
+        }
+
+        headers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use rest_client::HttpProfile;
+    use warp::http::{
+        header::{RANGE, SERVER, TRANSFER_ENCODING, USER_AGENT},
+        HeaderValue,
+    };
+
+    use super::*;
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+
+        for (name, value) in pairs {
+            headers.append(*name, HeaderValue::from_static(value));
+        }
+
+        headers
+    }
+
+    #[test]
+    fn strips_hop_by_hop_and_host_from_request() {
+        let request = headers(&[
+            ("host", "proxy.local"),
+            ("connection", "keep-alive, x-session"),
+            ("keep-alive", "timeout=5"),
+            ("x-session", "abc"),
+            ("content-length", "0"),
+            ("te", "trailers"),
+            ("range", "bytes=0-"),
+            ("user-agent", "player"),
+        ]);
+
+        let headers = HeaderUtil::new().compose_request_headers(&request, None);
+
+        assert_eq!(headers.len(), 2);
+        assert_eq!(headers[RANGE], "bytes=0-");
+        assert_eq!(headers[USER_AGENT], "player");
+    }
+
+    #[test]
+    fn applies_provider_profile_to_request() {
+        let provider = ProviderConfig {
+            http: HttpProfile {
+                user_agent: Some(String::from("provider-agent")),
+                headers: HashMap::from([(String::from("x-token"), String::from("secret"))]),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let request = headers(&[("user-agent", "player"), ("range", "bytes=0-")]);
+
+        let headers = HeaderUtil::new().compose_request_headers(&request, Some(&provider));
+
+        assert_eq!(headers[USER_AGENT], "provider-agent");
+        assert_eq!(headers["x-token"], "secret");
+        assert_eq!(headers[RANGE], "bytes=0-");
+    }
+
+    #[test]
+    fn strips_hop_by_hop_and_server_identity_from_response() {
+        let response = headers(&[
+            ("server", "nginx"),
+            ("via", "1.1 upstream"),
+            ("x-powered-by", "PHP"),
+            ("transfer-encoding", "chunked"),
+            ("connection", "close"),
+            ("content-type", "video/mp2t"),
+            ("content-length", "188"),
+        ]);
+
+        let headers = HeaderUtil::new().compose_response_headers(&response);
+
+        assert!(!headers.contains_key(SERVER));
+        assert!(!headers.contains_key(TRANSFER_ENCODING));
+        assert!(!headers.contains_key("x-powered-by"));
+        assert_eq!(headers.len(), 2);
+        assert_eq!(headers[CONTENT_LENGTH], "188");
+    }
+}
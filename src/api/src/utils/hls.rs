@@ -0,0 +1,681 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, ensure, Context, Error};
+use futures::StreamExt;
+use iptv::models::ProviderConfig;
+use log::{debug, warn};
+use reqwest::{Method, Url};
+use rest_client::RestClient;
+use tokio::{sync::mpsc::Sender, time::sleep};
+use warp::{
+    http::{
+        header::{CONTENT_LENGTH, CONTENT_TYPE},
+        HeaderMap,
+    },
+    hyper::{body::Bytes, Body, Response},
+};
+
+use crate::{
+    models::ApiConfiguration,
+    utils::{header::HeaderUtil, token::StreamTokenUtil},
+};
+
+const HLS_URI_TTL: Duration = Duration::from_secs(60 * 60);
+const HLS_SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+const LIVE_EDGE_SEGMENTS: usize = 3;
+const MAX_PLAYLIST_FAILURES: u32 = 5;
+
+/// Rewrites HLS playlists so that every variant, segment and key URI points at
+/// `/hls/{token}` on this proxy, with each token mapping back to its exact upstream URL.
+#[derive(Clone)]
+pub struct HlsUtil {
+    header_util: HeaderUtil,
+    stream_token_util: StreamTokenUtil,
+    proxy_domain: String,
+    uris: Arc<Mutex<HlsUris>>,
+}
+
+struct HlsUris {
+    entries: HashMap<String, HlsUri>,
+    last_sweep: Instant,
+}
+
+struct HlsUri {
+    url: Url,
+    slot: Option<u64>,
+    last_access: Instant,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MediaPlaylist {
+    pub media_sequence: u64,
+    pub target_duration: Duration,
+    pub segments: Vec<Url>,
+    pub ended: bool,
+}
+
+impl MediaPlaylist {
+    /// Sequence number to start remuxing at: the first segment of ended playlists, and a few
+    /// segments behind the live edge otherwise.
+    fn start_sequence(&self) -> u64 {
+        match self.ended {
+            true => self.media_sequence,
+            false => {
+                self.media_sequence + self.segments.len().saturating_sub(LIVE_EDGE_SEGMENTS) as u64
+            }
+        }
+    }
+}
+
+impl HlsUtil {
+    pub fn new(config: &ApiConfiguration) -> Self {
+        HlsUtil {
+            header_util: HeaderUtil::new(),
+            stream_token_util: StreamTokenUtil::new(config),
+            proxy_domain: config
+                .xtream
+                .xtream_proxied_domain
+                .clone()
+                .unwrap_or_default(),
+            uris: Arc::new(Mutex::new(HlsUris {
+                entries: HashMap::new(),
+                last_sweep: Instant::now(),
+            })),
+        }
+    }
+
+    pub fn is_playlist(&self, url: &Url, headers: &HeaderMap) -> bool {
+        let content_type = headers
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+            .to_lowercase();
+
+        content_type.contains("mpegurl") || url.path().ends_with(".m3u8")
+    }
+
+    /// Returns the upstream URL of a token along with the slot of the provider it belongs to.
+    pub fn resolve(&self, token: &str) -> Option<(Url, Option<u64>)> {
+        let token = token.split('.').next().unwrap_or_default();
+        let mut uris = self.uris.lock().unwrap();
+
+        uris.entries.get_mut(token).map(|uri| {
+            uri.last_access = Instant::now();
+            (uri.url.clone(), uri.slot)
+        })
+    }
+
+    pub async fn compose_playlist_response(
+        &self,
+        res: reqwest::Response,
+        provider: Option<&ProviderConfig>,
+        account_id: u64,
+    ) -> Result<Response<Body>, Error> {
+        let status = res.status();
+        let headers = self.header_util.compose_response_headers(res.headers());
+        let slot = provider.map(|provider| provider.slot);
+        let base_url = res.url().clone();
+
+        let playlist = res.text().await.context("reading hls playlist")?;
+
+        ensure!(
+            playlist.trim_start().starts_with("#EXTM3U"),
+            "upstream sent an invalid hls playlist"
+        );
+
+        let mut builder = Response::builder().status(status);
+
+        for (key, val) in headers.iter().filter(|(key, _)| *key != CONTENT_LENGTH) {
+            builder = builder.header(key, val);
+        }
+
+        let res = builder
+            .body(Body::from(
+                self.rewrite_playlist(&playlist, &base_url, slot, account_id),
+            ))
+            .context("error composing hls playlist")?;
+
+        Ok(res)
+    }
+
+    /// Rewrites every URI of a playlist to a signed `/hls/{token}` URL of the account.
+    pub fn rewrite_playlist(
+        &self,
+        playlist: &str,
+        base_url: &Url,
+        slot: Option<u64>,
+        account_id: u64,
+    ) -> String {
+        playlist
+            .lines()
+            .map(|line| {
+                let trimmed = line.trim();
+
+                if trimmed.is_empty() {
+                    line.to_string()
+                } else if trimmed.starts_with('#') {
+                    self.rewrite_tag(line, base_url, slot, account_id)
+                } else {
+                    self.rewrite_uri(trimmed, base_url, slot, account_id)
+                        .unwrap_or_else(|| line.to_string())
+                }
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    fn rewrite_tag(
+        &self,
+        line: &str,
+        base_url: &Url,
+        slot: Option<u64>,
+        account_id: u64,
+    ) -> String {
+        let mut rewritten = String::with_capacity(line.len());
+        let mut rest = line;
+
+        while let Some(index) = rest.find("URI=\"") {
+            let start = index + 5;
+
+            let end = match rest[start..].find('"') {
+                Some(index) => start + index,
+                None => break,
+            };
+
+            rewritten.push_str(&rest[..start]);
+
+            match self.rewrite_uri(&rest[start..end], base_url, slot, account_id) {
+                Some(uri) => rewritten.push_str(&uri),
+                None => rewritten.push_str(&rest[start..end]),
+            }
+
+            rest = &rest[end..];
+        }
+
+        rewritten.push_str(rest);
+
+        rewritten
+    }
+
+    fn rewrite_uri(
+        &self,
+        uri: &str,
+        base_url: &Url,
+        slot: Option<u64>,
+        account_id: u64,
+    ) -> Option<String> {
+        let url = base_url.join(uri).ok()?;
+
+        if url.scheme() != "http" && url.scheme() != "https" {
+            return None;
+        }
+
+        let extension = url
+            .path_segments()
+            .and_then(|mut segments| segments.next_back())
+            .and_then(|segment| segment.rsplit_once('.'))
+            .map(|(_, extension)| extension.to_string())
+            .filter(|extension| {
+                extension.len() <= 5 && extension.chars().all(|c| c.is_ascii_alphanumeric())
+            });
+
+        let token = self.tokenize(url, slot);
+
+        let uri = match extension {
+            Some(extension) => format!("http://{}/hls/{}.{}", self.proxy_domain, token, extension),
+            None => format!("http://{}/hls/{}", self.proxy_domain, token),
+        };
+
+        Some(self.stream_token_util.sign_url(&uri, account_id))
+    }
+
+    fn tokenize(&self, url: Url, slot: Option<u64>) -> String {
+        let mut hasher = DefaultHasher::new();
+        url.as_str().hash(&mut hasher);
+        let token = format!("{:016x}", hasher.finish());
+
+        let mut uris = self.uris.lock().unwrap();
+        let now = Instant::now();
+
+        if now.duration_since(uris.last_sweep) > HLS_SWEEP_INTERVAL {
+            uris.entries
+                .retain(|_, uri| now.duration_since(uri.last_access) < HLS_URI_TTL);
+            uris.last_sweep = now;
+        }
+
+        uris.entries.insert(
+            token.clone(),
+            HlsUri {
+                url,
+                slot,
+                last_access: now,
+            },
+        );
+
+        token
+    }
+
+    pub async fn fetch_playlist(
+        &self,
+        client: &RestClient,
+        url: &Url,
+        headers: &HeaderMap,
+    ) -> Result<(Url, String), Error> {
+        let res = client
+            .request(Method::GET, url.clone(), headers.clone())
+            .await?;
+
+        ensure!(
+            res.status().is_success(),
+            "upstream responded with {}",
+            res.status()
+        );
+
+        let url = res.url().clone();
+        let playlist = res.text().await.context("reading hls playlist")?;
+
+        ensure!(
+            playlist.trim_start().starts_with("#EXTM3U"),
+            "upstream sent an invalid hls playlist"
+        );
+
+        Ok((url, playlist))
+    }
+
+    pub fn select_variant(&self, playlist: &str, base_url: &Url) -> Option<Url> {
+        let mut bandwidth = None;
+        let mut variants = vec![];
+
+        for line in playlist
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+        {
+            if let Some(attributes) = line.strip_prefix("#EXT-X-STREAM-INF:") {
+                bandwidth = Some(
+                    attributes
+                        .split(',')
+                        .find_map(|attribute| attribute.strip_prefix("BANDWIDTH="))
+                        .and_then(|value| value.parse::<u64>().ok())
+                        .unwrap_or_default(),
+                );
+            } else if !line.starts_with('#') {
+                if let (Some(bandwidth), Ok(url)) = (bandwidth.take(), base_url.join(line)) {
+                    variants.push((bandwidth, url));
+                }
+            }
+        }
+
+        variants
+            .into_iter()
+            .max_by_key(|(bandwidth, _)| *bandwidth)
+            .map(|(_, url)| url)
+    }
+
+    pub fn parse_media_playlist(
+        &self,
+        playlist: &str,
+        base_url: &Url,
+    ) -> Result<MediaPlaylist, Error> {
+        let mut media_playlist = MediaPlaylist {
+            target_duration: Duration::from_secs(10),
+            ..MediaPlaylist::default()
+        };
+
+        for line in playlist
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+        {
+            if let Some(value) = line.strip_prefix("#EXT-X-MEDIA-SEQUENCE:") {
+                media_playlist.media_sequence = value.parse().context("parsing media sequence")?;
+            } else if let Some(value) = line.strip_prefix("#EXT-X-TARGETDURATION:") {
+                let target_duration = value.parse::<f64>().context("parsing target duration")?;
+
+                ensure!(
+                    target_duration.is_finite() && target_duration > 0.0,
+                    "invalid target duration {}",
+                    value
+                );
+
+                media_playlist.target_duration = Duration::try_from_secs_f64(target_duration)
+                    .context(format!("invalid target duration {}", value))?;
+            } else if let Some(attributes) = line.strip_prefix("#EXT-X-KEY:") {
+                if !attributes.contains("METHOD=NONE") {
+                    bail!("encrypted hls segments cannot be remuxed");
+                }
+            } else if line.starts_with("#EXT-X-MAP:") {
+                bail!("fragmented mp4 hls segments cannot be remuxed");
+            } else if line == "#EXT-X-ENDLIST" {
+                media_playlist.ended = true;
+            } else if !line.starts_with('#') {
+                media_playlist.segments.push(
+                    base_url
+                        .join(line)
+                        .context(format!("resolving segment uri {}", line))?,
+                );
+            }
+        }
+
+        Ok(media_playlist)
+    }
+
+    /// Follows a media playlist and forwards its segments in order as one continuous MPEG-TS
+    /// stream until the playlist ends, the upstream keeps failing or the receiver goes away.
+    pub async fn remux_segments(
+        self,
+        client: Arc<RestClient>,
+        headers: HeaderMap,
+        mut playlist_url: Url,
+        mut media_playlist: MediaPlaylist,
+        sender: Sender<Bytes>,
+    ) {
+        let mut next_sequence = media_playlist.start_sequence();
+        let mut failures = 0;
+
+        loop {
+            let mut has_new_segments = false;
+
+            for (index, segment_url) in media_playlist.segments.iter().enumerate() {
+                let sequence = media_playlist.media_sequence + index as u64;
+
+                if sequence < next_sequence {
+                    continue;
+                }
+
+                next_sequence = sequence + 1;
+                has_new_segments = true;
+
+                let mut segment = match client
+                    .request(Method::GET, segment_url.clone(), headers.clone())
+                    .await
+                {
+                    Ok(segment) if segment.status().is_success() => segment.bytes_stream(),
+                    Ok(segment) => {
+                        warn!(
+                            "Skipping hls segment {}: upstream responded with {}",
+                            segment_url,
+                            segment.status()
+                        );
+                        continue;
+                    }
+                    Err(err) => {
+                        warn!("Skipping hls segment {}: {:#}", segment_url, err);
+                        continue;
+                    }
+                };
+
+                while let Some(chunk) = segment.next().await {
+                    match chunk {
+                        Ok(chunk) => {
+                            if sender.send(chunk).await.is_err() {
+                                debug!("Remux receiver closed for {}", playlist_url);
+                                return;
+                            }
+                        }
+                        Err(err) => {
+                            warn!("Error reading hls segment {}: {}", segment_url, err);
+                            break;
+                        }
+                    }
+                }
+            }
+
+            if media_playlist.ended || sender.is_closed() {
+                return;
+            }
+
+            if !has_new_segments {
+                sleep(media_playlist.target_duration / 2).await;
+            }
+
+            match self
+                .fetch_playlist(&client, &playlist_url, &headers)
+                .await
+                .and_then(|(url, playlist)| {
+                    let media_playlist = self.parse_media_playlist(&playlist, &url)?;
+                    Ok((url, media_playlist))
+                }) {
+                Ok((url, playlist)) => {
+                    if playlist.media_sequence + (playlist.segments.len() as u64) < next_sequence {
+                        warn!(
+                            "Media sequence of hls playlist {} went back, resyncing to its live edge",
+                            url
+                        );
+
+                        next_sequence = playlist.start_sequence();
+                    }
+
+                    playlist_url = url;
+                    media_playlist = playlist;
+                    failures = 0;
+                }
+                Err(err) => {
+                    failures += 1;
+                    warn!(
+                        "Failed to reload hls playlist {} ({}/{}): {:#}",
+                        playlist_url, failures, MAX_PLAYLIST_FAILURES, err
+                    );
+
+                    if failures >= MAX_PLAYLIST_FAILURES {
+                        return;
+                    }
+
+                    sleep(media_playlist.target_duration / 2).await;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::mpsc::channel;
+    use warp::{http::StatusCode, Filter};
+
+    use super::*;
+    use crate::models::xtream::XtreamConfig;
+
+    fn hls_util() -> HlsUtil {
+        HlsUtil::new(&ApiConfiguration {
+            stream_token_secret: String::from("secret"),
+            stream_token_ttl: 60,
+            xtream: XtreamConfig {
+                xtream_proxied_domain: Some(String::from("proxy.local")),
+                ..XtreamConfig::default()
+            },
+            ..ApiConfiguration::default()
+        })
+    }
+
+    fn resolve_uri(hls_util: &HlsUtil, uri: &str) -> Url {
+        let token = uri
+            .strip_prefix("http://proxy.local/hls/")
+            .and_then(|uri| uri.split('?').next())
+            .unwrap();
+
+        hls_util.resolve(token).unwrap().0
+    }
+
+    #[test]
+    fn rewrites_variant_and_relative_segment_uris() {
+        let hls_util = hls_util();
+        let base_url = Url::parse("http://upstream.example/live/master.m3u8").unwrap();
+
+        let playlist = hls_util.rewrite_playlist(
+            "#EXTM3U\n#EXT-X-STREAM-INF:BANDWIDTH=800000\nlow/index.m3u8\n#EXTINF:6.0,\n/abs/seg1.ts",
+            &base_url,
+            Some(1),
+            0,
+        );
+        let lines: Vec<&str> = playlist.lines().collect();
+
+        assert_eq!(lines[0], "#EXTM3U");
+        assert_eq!(lines[1], "#EXT-X-STREAM-INF:BANDWIDTH=800000");
+        assert!(lines[2].contains(".m3u8?token="));
+        assert_eq!(
+            resolve_uri(&hls_util, lines[2]).as_str(),
+            "http://upstream.example/live/low/index.m3u8"
+        );
+        assert_eq!(
+            resolve_uri(&hls_util, lines[4]).as_str(),
+            "http://upstream.example/abs/seg1.ts"
+        );
+    }
+
+    #[test]
+    fn rewrites_every_uri_of_a_tag() {
+        let hls_util = hls_util();
+        let base_url = Url::parse("http://upstream.example/live/index.m3u8").unwrap();
+
+        let line = hls_util.rewrite_playlist(
+            r#"#EXT-X-KEY:METHOD=AES-128,URI="key.bin",KEYFORMAT="identity",ALT-URI="https://keys.example/alt.bin""#,
+            &base_url,
+            None,
+            0,
+        );
+
+        let uris: Vec<&str> = line
+            .split('"')
+            .filter(|part| part.starts_with("http://proxy.local/hls/"))
+            .collect();
+
+        assert_eq!(uris.len(), 2);
+        assert!(line.contains(r#"KEYFORMAT="identity""#));
+        assert_eq!(
+            resolve_uri(&hls_util, uris[0]).as_str(),
+            "http://upstream.example/live/key.bin"
+        );
+        assert_eq!(
+            resolve_uri(&hls_util, uris[1]).as_str(),
+            "https://keys.example/alt.bin"
+        );
+    }
+
+    #[test]
+    fn parses_media_playlist() {
+        let base_url = Url::parse("http://upstream.example/live/index.m3u8").unwrap();
+
+        let media_playlist = hls_util()
+            .parse_media_playlist(
+                "#EXTM3U\n#EXT-X-TARGETDURATION:6\n#EXT-X-MEDIA-SEQUENCE:42\n#EXTINF:6.0,\nseg42.ts\n#EXTINF:6.0,\nseg43.ts\n#EXT-X-ENDLIST",
+                &base_url,
+            )
+            .unwrap();
+
+        assert_eq!(media_playlist.media_sequence, 42);
+        assert_eq!(media_playlist.target_duration, Duration::from_secs(6));
+        assert_eq!(media_playlist.segments.len(), 2);
+        assert_eq!(
+            media_playlist.segments[1].as_str(),
+            "http://upstream.example/live/seg43.ts"
+        );
+        assert!(media_playlist.ended);
+    }
+
+    #[test]
+    fn rejects_invalid_target_durations() {
+        let base_url = Url::parse("http://upstream.example/live/index.m3u8").unwrap();
+
+        for target_duration in ["-6", "0", "NaN", "inf", "1e300", "six"] {
+            let playlist = format!("#EXTM3U\n#EXT-X-TARGETDURATION:{}\nseg.ts", target_duration);
+
+            assert!(
+                hls_util()
+                    .parse_media_playlist(&playlist, &base_url)
+                    .is_err(),
+                "accepted target duration {}",
+                target_duration
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn remux_skips_failed_segments() {
+        let routes = warp::path!(String).map(|segment: String| match segment.as_str() {
+            "missing.ts" => warp::reply::with_status(
+                String::from("<html>not found</html>"),
+                StatusCode::NOT_FOUND,
+            ),
+            _ => warp::reply::with_status(segment, StatusCode::OK),
+        });
+        let (addr, server) = warp::serve(routes).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+
+        let base_url = Url::parse(&format!("http://{}/index.m3u8", addr)).unwrap();
+        let media_playlist = MediaPlaylist {
+            media_sequence: 0,
+            target_duration: Duration::from_secs(1),
+            segments: ["first.ts", "missing.ts", "last.ts"]
+                .iter()
+                .map(|segment| base_url.join(segment).unwrap())
+                .collect(),
+            ended: true,
+        };
+
+        let (sender, mut receiver) = channel(8);
+
+        hls_util()
+            .remux_segments(
+                Arc::new(RestClient::new()),
+                HeaderMap::new(),
+                base_url,
+                media_playlist,
+                sender,
+            )
+            .await;
+
+        let mut output = vec![];
+
+        while let Some(chunk) = receiver.recv().await {
+            output.extend_from_slice(&chunk);
+        }
+
+        assert_eq!(output, b"first.tslast.ts");
+    }
+
+    #[tokio::test]
+    async fn remux_resyncs_when_media_sequence_goes_back() {
+        let routes = warp::path!(String).map(|segment: String| match segment.as_str() {
+            "index.m3u8" => String::from(
+                "#EXTM3U\n#EXT-X-TARGETDURATION:1\n#EXT-X-MEDIA-SEQUENCE:0\nnew.ts\n#EXT-X-ENDLIST\n",
+            ),
+            _ => segment,
+        });
+        let (addr, server) = warp::serve(routes).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+
+        let base_url = Url::parse(&format!("http://{}/index.m3u8", addr)).unwrap();
+        let media_playlist = MediaPlaylist {
+            media_sequence: 100,
+            target_duration: Duration::from_secs(1),
+            segments: vec![base_url.join("old.ts").unwrap()],
+            ended: false,
+        };
+
+        let (sender, mut receiver) = channel(8);
+
+        hls_util()
+            .remux_segments(
+                Arc::new(RestClient::new()),
+                HeaderMap::new(),
+                base_url,
+                media_playlist,
+                sender,
+            )
+            .await;
+
+        let mut output = vec![];
+
+        while let Some(chunk) = receiver.recv().await {
+            output.extend_from_slice(&chunk);
+        }
+
+        assert_eq!(output, b"old.tsnew.ts");
+    }
+}
@@ -1,4 +1,12 @@
+pub mod api_key;
+pub mod connection;
+pub mod header;
+pub mod hls;
+pub mod playlist;
 pub mod proxy;
 pub mod response;
+pub mod segmenter;
+pub mod session;
+pub mod token;
 pub mod url;
 pub mod xml;
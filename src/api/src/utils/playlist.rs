@@ -0,0 +1,70 @@
+use crate::{
+    models::{
+        xtream::{XtreamAccount, XtreamConfig},
+        ApiConfiguration,
+    },
+    utils::token::StreamTokenUtil,
+};
+
+/// Adapts the generated m3u files to the account that requests them.
+#[derive(Clone)]
+pub struct PlaylistUtil {
+    stream_token_util: StreamTokenUtil,
+    xtream: XtreamConfig,
+}
+
+impl PlaylistUtil {
+    pub fn new(config: &ApiConfiguration) -> Self {
+        PlaylistUtil {
+            stream_token_util: StreamTokenUtil::new(config),
+            xtream: config.xtream.clone(),
+        }
+    }
+
+    /// Drops the channels the account isn't entitled to, replaces the proxied credentials the
+    /// file was generated with by the account's own and signs all proxy urls.
+    pub fn compose_account_playlist(&self, playlist: &str, account: &XtreamAccount) -> String {
+        let mut playlist = self.retain_entitled_channels(playlist, account);
+
+        if account.username != self.xtream.xtream_proxied_username
+            || account.password != self.xtream.xtream_proxied_password
+        {
+            playlist = playlist.replace(
+                &format!(
+                    "/{}/{}/",
+                    self.xtream.xtream_proxied_username, self.xtream.xtream_proxied_password
+                ),
+                &format!("/{}/{}/", account.username, account.password),
+            );
+        }
+
+        self.stream_token_util
+            .sign_playlist(&playlist, account.id())
+    }
+
+    fn retain_entitled_channels(&self, playlist: &str, account: &XtreamAccount) -> String {
+        if account.groups.is_empty() {
+            return playlist.to_string();
+        }
+
+        let mut is_entitled = true;
+
+        playlist
+            .lines()
+            .filter(|line| {
+                if line.starts_with("#EXTINF") {
+                    let group_title = line
+                        .split_once("group-title=\"")
+                        .and_then(|(_, rest)| rest.split_once('"'))
+                        .map(|(group_title, _)| group_title)
+                        .unwrap_or_default();
+
+                    is_entitled = account.is_entitled(group_title);
+                }
+
+                is_entitled
+            })
+            .collect::<Vec<&str>>()
+            .join("\n")
+    }
+}
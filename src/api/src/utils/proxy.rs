@@ -13,7 +13,7 @@ use warp::{
     Reply,
 };
 
-use super::response::ResponseUtil;
+use super::{header::HeaderUtil, response::ResponseUtil};
 
 #[derive(Clone)]
 pub struct ProxyUtil {
@@ -35,9 +35,9 @@ impl ProxyUtil {
     where
         T: DeserializeOwned + Send,
     {
-        let res = self.client.get(&url).await.context("error on proxy")?;
+        let res = self.client.get(url).await.context("error on proxy")?;
         let headers = res.headers().clone();
-        let status_code = res.status().clone();
+        let status_code = res.status();
 
         let data = match res.text().await {
             Ok(data) => serde_json::from_str::<T>(&data).context("deserializing json to type T")?,
@@ -55,8 +55,8 @@ impl ProxyUtil {
     }
 
     pub async fn proxy_request_bytes(&self, url: &Url) -> Result<Response<Body>, Error> {
-        let res = self.client.get(&url).await.context("error on proxy")?;
-        let headers = res.headers();
+        let res = self.client.get(url).await.context("error on proxy")?;
+        let headers = HeaderUtil::new().compose_response_headers(res.headers());
         let status = res.status();
 
         let mut builder = Response::builder().status(status);
@@ -94,7 +94,7 @@ impl ProxyUtil {
             .await
             .context("error proxying attribute")?;
 
-        return Ok(res);
+        Ok(res)
     }
 
     pub async fn proxy_xtream_url(&self, id: u64) -> Result<Response<Body>, Error> {
@@ -119,6 +119,6 @@ impl ProxyUtil {
             .await
             .context("error proxying attribute")?;
 
-        return Ok(res);
+        Ok(res)
     }
 }
@@ -1,20 +1,60 @@
-use anyhow::Error;
+use std::{mem::replace, sync::Arc, time::Duration};
 
-use warp::http::response::Builder;
+use anyhow::{Context, Error};
+
+use futures::{
+    stream::{empty, unfold, BoxStream},
+    Stream, StreamExt,
+};
+use log::{info, warn};
+use reqwest::{Method, Url};
+use rest_client::RestClient;
+use tokio::time::{sleep, Instant};
+use warp::http::{
+    header::{ACCEPT_RANGES, CONTENT_LENGTH, CONTENT_RANGE, RANGE},
+    response::Builder,
+    HeaderMap, HeaderValue, StatusCode,
+};
 use warp::reply::with_status;
 use warp::{
-    hyper::{Body, Response},
+    hyper::{body::Bytes, Body, Response},
     reply::json,
     Reply,
 };
 
 use serde::Serialize;
 
-use crate::models::ResponseData;
+use crate::{models::ResponseData, utils::header::HeaderUtil};
+
+const TS_PACKET_SIZE: usize = 188;
+const TS_SYNC_BYTE: u8 = 0x47;
+const MAX_RECONNECT_ATTEMPTS: u32 = 5;
+const INITIAL_RECONNECT_DELAY: Duration = Duration::from_millis(500);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(8);
+const STABLE_CONNECTION_BYTES: u64 = 4 * 1024 * 1024;
+const STABLE_CONNECTION_DURATION: Duration = Duration::from_secs(60);
 
 #[derive(Default, Debug, Clone, PartialEq)]
 pub struct ResponseUtil;
 
+struct SlicedStream {
+    upstream: BoxStream<'static, Result<Bytes, reqwest::Error>>,
+    skip: u64,
+    remaining: u64,
+}
+
+struct ReconnectingStream {
+    client: Arc<RestClient>,
+    url: Url,
+    headers: HeaderMap,
+    upstream: Option<BoxStream<'static, Result<Bytes, reqwest::Error>>>,
+    remainder: Vec<u8>,
+    synced: bool,
+    attempts: u32,
+    connected_at: Instant,
+    received: u64,
+}
+
 impl ResponseUtil {
     pub fn new() -> Self {
         ResponseUtil
@@ -22,7 +62,7 @@ impl ResponseUtil {
 
     pub async fn compose_base_response(&self, res: &reqwest::Response) -> Result<Builder, Error> {
         let status = res.status();
-        let headers = res.headers();
+        let headers = HeaderUtil::new().compose_response_headers(res.headers());
         let mut builder = Response::builder();
 
         builder = builder.status(status);
@@ -71,4 +111,548 @@ impl ResponseUtil {
 
         Ok(response)
     }
+
+    /// Composes the response for a VOD upstream. When the player asked for a byte range that
+    /// the upstream ignored, e.g. after a redirect, the range is cut from the full response and
+    /// answered with `206 Partial Content`.
+    pub fn compose_range_response<S>(
+        &self,
+        request_headers: &HeaderMap,
+        status: StatusCode,
+        response_headers: &HeaderMap,
+        upstream: S,
+    ) -> Result<(Builder, BoxStream<'static, Result<Bytes, reqwest::Error>>), Error>
+    where
+        S: Stream<Item = Result<Bytes, reqwest::Error>> + Send + 'static,
+    {
+        let total = response_headers
+            .get(CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<u64>().ok());
+
+        let mut builder = Response::builder();
+
+        for (key, val) in response_headers.iter() {
+            builder = builder.header(key, val);
+        }
+
+        let total = match total {
+            Some(total) if status == StatusCode::OK => total,
+            _ => return Ok((builder.status(status), upstream.boxed())),
+        };
+
+        let headers = builder.headers_mut().context("composing range headers")?;
+        headers.insert(ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+
+        let range = match request_headers
+            .get(RANGE)
+            .and_then(|range| range.to_str().ok())
+            .and_then(|range| range.trim().strip_prefix("bytes="))
+            .filter(|range| !range.contains(','))
+        {
+            Some(range) => self.parse_range(range, total),
+            None => return Ok((builder.status(status), upstream.boxed())),
+        };
+
+        match range {
+            Some((start, end)) => {
+                headers.insert(
+                    CONTENT_RANGE,
+                    HeaderValue::from_str(&format!("bytes {}-{}/{}", start, end, total))?,
+                );
+                headers.insert(CONTENT_LENGTH, HeaderValue::from(end - start + 1));
+
+                let state = SlicedStream {
+                    upstream: upstream.boxed(),
+                    skip: start,
+                    remaining: end - start + 1,
+                };
+
+                Ok((
+                    builder.status(StatusCode::PARTIAL_CONTENT),
+                    unfold(state, SlicedStream::next).boxed(),
+                ))
+            }
+            None => {
+                headers.insert(
+                    CONTENT_RANGE,
+                    HeaderValue::from_str(&format!("bytes */{}", total))?,
+                );
+                headers.insert(CONTENT_LENGTH, HeaderValue::from(0));
+
+                Ok((
+                    builder.status(StatusCode::RANGE_NOT_SATISFIABLE),
+                    empty().boxed(),
+                ))
+            }
+        }
+    }
+
+    /// Resolves a single `start-end`, `start-` or `-suffix` byte range against the total
+    /// length. Returns `None` when the range can't be satisfied.
+    pub fn parse_range(&self, range: &str, total: u64) -> Option<(u64, u64)> {
+        let (start, end) = range.split_once('-')?;
+        let last = total.checked_sub(1)?;
+
+        let (start, end) = match (start.trim(), end.trim()) {
+            ("", suffix) => (total.saturating_sub(suffix.parse().ok()?), last),
+            (start, "") => (start.parse().ok()?, last),
+            (start, end) => (start.parse().ok()?, end.parse::<u64>().ok()?.min(last)),
+        };
+
+        if start > end || start >= total {
+            return None;
+        }
+
+        Some((start, end))
+    }
+
+    /// Composes the response to a HEAD request from the upstream's answer to a HEAD request,
+    /// with the same range handling as the GET response.
+    pub fn compose_head_response(
+        &self,
+        request_headers: &HeaderMap,
+        res: reqwest::Response,
+    ) -> Result<Response<Body>, Error> {
+        let response_headers = HeaderUtil::new().compose_response_headers(res.headers());
+
+        let (builder, _) =
+            self.compose_range_response(request_headers, res.status(), &response_headers, empty())?;
+
+        let res = builder
+            .body(Body::empty())
+            .context("composing head response")?;
+
+        Ok(res)
+    }
+
+    /// Wraps a live MPEG-TS upstream so that it is re-requested with backoff when it ends or
+    /// fails, keeping the downstream response open. Only whole TS packets are passed on, and
+    /// every new upstream connection is re-synced on a packet boundary.
+    pub fn compose_reconnecting_stream<S>(
+        &self,
+        client: Arc<RestClient>,
+        url: Url,
+        headers: HeaderMap,
+        upstream: S,
+    ) -> impl Stream<Item = Result<Bytes, reqwest::Error>> + Send + 'static
+    where
+        S: Stream<Item = Result<Bytes, reqwest::Error>> + Send + 'static,
+    {
+        let state = ReconnectingStream {
+            client,
+            url,
+            headers,
+            upstream: Some(upstream.boxed()),
+            remainder: vec![],
+            synced: false,
+            attempts: 0,
+            connected_at: Instant::now(),
+            received: 0,
+        };
+
+        unfold(state, |mut state| async move {
+            loop {
+                if let Some(upstream) = &mut state.upstream {
+                    match upstream.next().await {
+                        Some(Ok(chunk)) => {
+                            state.received += chunk.len() as u64;
+
+                            if state.is_stable() {
+                                state.attempts = 0;
+                            }
+
+                            if let Some(packets) = state.align(&chunk) {
+                                return Some((Ok(packets), state));
+                            }
+
+                            continue;
+                        }
+                        Some(Err(err)) => warn!("Upstream {} failed: {}", state.url, err),
+                        None => info!("Upstream {} ended", state.url),
+                    }
+
+                    state.upstream = None;
+                }
+
+                if state.attempts >= MAX_RECONNECT_ATTEMPTS {
+                    warn!(
+                        "Giving up on upstream {} after {} reconnect attempts",
+                        state.url, state.attempts
+                    );
+                    return None;
+                }
+
+                sleep(
+                    INITIAL_RECONNECT_DELAY
+                        .saturating_mul(2u32.pow(state.attempts))
+                        .min(MAX_RECONNECT_DELAY),
+                )
+                .await;
+
+                state.attempts += 1;
+                state.remainder.clear();
+                state.synced = false;
+
+                match state
+                    .client
+                    .request(Method::GET, state.url.clone(), state.headers.clone())
+                    .await
+                {
+                    Ok(res) if res.status().is_success() => {
+                        info!(
+                            "Reconnected to upstream {} (attempt {}/{})",
+                            state.url, state.attempts, MAX_RECONNECT_ATTEMPTS
+                        );
+                        state.upstream = Some(res.bytes_stream().boxed());
+                        state.connected_at = Instant::now();
+                        state.received = 0;
+                    }
+                    Ok(res) => warn!(
+                        "Reconnecting to upstream {} failed with {}",
+                        state.url,
+                        res.status()
+                    ),
+                    Err(err) => warn!("Reconnecting to upstream {} failed: {}", state.url, err),
+                }
+            }
+        })
+    }
+}
+
+impl SlicedStream {
+    async fn next(mut self) -> Option<(Result<Bytes, reqwest::Error>, Self)> {
+        while self.remaining > 0 {
+            let mut chunk = match self.upstream.next().await? {
+                Ok(chunk) => chunk,
+                Err(err) => return Some((Err(err), self)),
+            };
+
+            if self.skip >= chunk.len() as u64 {
+                self.skip -= chunk.len() as u64;
+                continue;
+            }
+
+            chunk = chunk.slice(self.skip as usize..);
+            chunk.truncate(self.remaining.min(chunk.len() as u64) as usize);
+
+            self.skip = 0;
+            self.remaining -= chunk.len() as u64;
+
+            return Some((Ok(chunk), self));
+        }
+
+        None
+    }
+}
+
+impl ReconnectingStream {
+    /// Upstreams that keep dropping right after connecting use up the reconnect attempts, only
+    /// a connection that delivered enough data or lasted long enough resets them.
+    fn is_stable(&self) -> bool {
+        self.received >= STABLE_CONNECTION_BYTES
+            || self.connected_at.elapsed() >= STABLE_CONNECTION_DURATION
+    }
+
+    fn align(&mut self, chunk: &[u8]) -> Option<Bytes> {
+        self.remainder.extend_from_slice(chunk);
+
+        if !self.synced {
+            let offset = match (0..self.remainder.len()).find(|offset| {
+                self.remainder[*offset] == TS_SYNC_BYTE
+                    && self
+                        .remainder
+                        .get(offset + TS_PACKET_SIZE)
+                        .is_none_or(|byte| *byte == TS_SYNC_BYTE)
+            }) {
+                Some(offset) => offset,
+                None => {
+                    let length = self.remainder.len().saturating_sub(TS_PACKET_SIZE);
+                    self.remainder.drain(..length);
+                    return None;
+                }
+            };
+
+            self.remainder.drain(..offset);
+            self.synced = true;
+        }
+
+        let length = self.remainder.len() / TS_PACKET_SIZE * TS_PACKET_SIZE;
+
+        if length == 0 {
+            return None;
+        }
+
+        let remainder = self.remainder.split_off(length);
+
+        Some(Bytes::from(replace(&mut self.remainder, remainder)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::SocketAddr,
+        sync::atomic::{AtomicU32, Ordering},
+    };
+
+    use futures::stream::iter;
+    use warp::{http::Uri, Filter};
+
+    use super::*;
+
+    const VOD_SIZE: u8 = 100;
+
+    fn vod() -> Vec<u8> {
+        (0..VOD_SIZE).collect()
+    }
+
+    /// Serves a VOD that honors ranges on `/vod`, one that ignores them on `/full` and a
+    /// redirect to the latter on `/redirect`.
+    fn serve_vod() -> SocketAddr {
+        let ranged = warp::path("vod")
+            .and(warp::header::optional::<String>("range"))
+            .map(|range: Option<String>| {
+                let total = VOD_SIZE as u64;
+                let range = range
+                    .as_deref()
+                    .and_then(|range| range.strip_prefix("bytes="))
+                    .and_then(|range| ResponseUtil::new().parse_range(range, total));
+
+                match range {
+                    Some((start, end)) => Response::builder()
+                        .status(StatusCode::PARTIAL_CONTENT)
+                        .header(CONTENT_RANGE, format!("bytes {}-{}/{}", start, end, total))
+                        .body(vod()[start as usize..=end as usize].to_vec())
+                        .unwrap(),
+                    None => Response::builder().body(vod()).unwrap(),
+                }
+            });
+        let full = warp::path("full").map(vod);
+        let redirect =
+            warp::path("redirect").map(|| warp::redirect::found(Uri::from_static("/full")));
+
+        let (addr, server) =
+            warp::serve(ranged.or(full).or(redirect)).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+
+        addr
+    }
+
+    async fn proxy(
+        addr: SocketAddr,
+        path: &str,
+        method: Method,
+        range: Option<&str>,
+    ) -> (StatusCode, HeaderMap, Vec<u8>) {
+        let url = Url::parse(&format!("http://{}{}", addr, path)).unwrap();
+        let mut headers = HeaderMap::new();
+
+        if let Some(range) = range {
+            headers.insert(RANGE, HeaderValue::from_str(range).unwrap());
+        }
+
+        let res = RestClient::new()
+            .request(method.clone(), url, headers.clone())
+            .await
+            .unwrap();
+
+        let response_util = ResponseUtil::new();
+
+        if method == Method::HEAD {
+            let res = response_util.compose_head_response(&headers, res).unwrap();
+            let (parts, body) = res.into_parts();
+            let body = warp::hyper::body::to_bytes(body).await.unwrap();
+
+            return (parts.status, parts.headers, body.to_vec());
+        }
+
+        let status = res.status();
+        let response_headers = HeaderUtil::new().compose_response_headers(res.headers());
+
+        let (builder, stream) = response_util
+            .compose_range_response(&headers, status, &response_headers, res.bytes_stream())
+            .unwrap();
+        let (parts, _) = builder.body(()).unwrap().into_parts();
+
+        (parts.status, parts.headers, collect(stream).await)
+    }
+
+    #[test]
+    fn parses_byte_ranges() {
+        let response_util = ResponseUtil::new();
+
+        assert_eq!(response_util.parse_range("0-9", 100), Some((0, 9)));
+        assert_eq!(response_util.parse_range("90-", 100), Some((90, 99)));
+        assert_eq!(response_util.parse_range("90-200", 100), Some((90, 99)));
+        assert_eq!(response_util.parse_range("-5", 100), Some((95, 99)));
+        assert_eq!(response_util.parse_range("-500", 100), Some((0, 99)));
+        assert_eq!(response_util.parse_range("100-", 100), None);
+        assert_eq!(response_util.parse_range("9-0", 100), None);
+        assert_eq!(response_util.parse_range("0-9", 0), None);
+        assert_eq!(response_util.parse_range("a-9", 100), None);
+    }
+
+    #[tokio::test]
+    async fn passes_through_range_honored_by_upstream() {
+        let addr = serve_vod();
+
+        let (status, headers, body) = proxy(addr, "/vod", Method::GET, Some("bytes=0-9")).await;
+
+        assert_eq!(status, StatusCode::PARTIAL_CONTENT);
+        assert_eq!(headers[CONTENT_RANGE], "bytes 0-9/100");
+        assert_eq!(body, vod()[..10]);
+    }
+
+    #[tokio::test]
+    async fn passes_through_response_without_range() {
+        let addr = serve_vod();
+
+        let (status, headers, body) = proxy(addr, "/full", Method::GET, None).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(headers[ACCEPT_RANGES], "bytes");
+        assert!(!headers.contains_key(CONTENT_RANGE));
+        assert_eq!(body, vod());
+    }
+
+    #[tokio::test]
+    async fn cuts_range_ignored_by_upstream() {
+        let addr = serve_vod();
+
+        let (status, headers, body) = proxy(addr, "/full", Method::GET, Some("bytes=10-19")).await;
+
+        assert_eq!(status, StatusCode::PARTIAL_CONTENT);
+        assert_eq!(headers[CONTENT_RANGE], "bytes 10-19/100");
+        assert_eq!(headers[CONTENT_LENGTH], "10");
+        assert_eq!(body, vod()[10..20]);
+    }
+
+    #[tokio::test]
+    async fn cuts_suffix_range() {
+        let addr = serve_vod();
+
+        let (status, headers, body) = proxy(addr, "/full", Method::GET, Some("bytes=-5")).await;
+
+        assert_eq!(status, StatusCode::PARTIAL_CONTENT);
+        assert_eq!(headers[CONTENT_RANGE], "bytes 95-99/100");
+        assert_eq!(body, vod()[95..]);
+    }
+
+    #[tokio::test]
+    async fn rejects_unsatisfiable_range() {
+        let addr = serve_vod();
+
+        let (status, headers, body) =
+            proxy(addr, "/full", Method::GET, Some("bytes=200-300")).await;
+
+        assert_eq!(status, StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(headers[CONTENT_RANGE], "bytes */100");
+        assert_eq!(headers[CONTENT_LENGTH], "0");
+        assert!(body.is_empty());
+    }
+
+    #[tokio::test]
+    async fn cuts_range_dropped_by_redirect() {
+        let addr = serve_vod();
+
+        let (status, headers, body) =
+            proxy(addr, "/redirect", Method::GET, Some("bytes=10-19")).await;
+
+        assert_eq!(status, StatusCode::PARTIAL_CONTENT);
+        assert_eq!(headers[CONTENT_RANGE], "bytes 10-19/100");
+        assert_eq!(body, vod()[10..20]);
+    }
+
+    #[tokio::test]
+    async fn answers_head_with_range_headers() {
+        let addr = serve_vod();
+
+        let (status, headers, body) = proxy(addr, "/full", Method::HEAD, None).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(headers[CONTENT_LENGTH], "100");
+        assert_eq!(headers[ACCEPT_RANGES], "bytes");
+        assert!(body.is_empty());
+
+        let (status, headers, body) = proxy(addr, "/full", Method::HEAD, Some("bytes=10-19")).await;
+
+        assert_eq!(status, StatusCode::PARTIAL_CONTENT);
+        assert_eq!(headers[CONTENT_RANGE], "bytes 10-19/100");
+        assert_eq!(headers[CONTENT_LENGTH], "10");
+        assert!(body.is_empty());
+    }
+
+    fn ts_packet(marker: u8) -> Vec<u8> {
+        let mut packet = vec![marker; TS_PACKET_SIZE];
+        packet[0] = TS_SYNC_BYTE;
+
+        packet
+    }
+
+    async fn collect<S>(stream: S) -> Vec<u8>
+    where
+        S: Stream<Item = Result<Bytes, reqwest::Error>>,
+    {
+        stream.map(|chunk| chunk.unwrap().to_vec()).concat().await
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn aligns_reconnected_stream_on_packet_boundaries() {
+        let routes = warp::any().map(|| [vec![0x00, 0x01], ts_packet(2)].concat());
+        let (addr, server) = warp::serve(routes).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+
+        let url = Url::parse(&format!("http://{}/live.ts", addr)).unwrap();
+        let first = [ts_packet(1), ts_packet(1)[..100].to_vec()].concat();
+        let upstream = iter(vec![Ok(Bytes::from(first))]);
+
+        let stream = ResponseUtil::new().compose_reconnecting_stream(
+            Arc::new(RestClient::new()),
+            url,
+            HeaderMap::new(),
+            upstream,
+        );
+
+        let output = collect(stream).await;
+        let expected = [
+            vec![ts_packet(1)],
+            vec![ts_packet(2); MAX_RECONNECT_ATTEMPTS as usize],
+        ]
+        .concat()
+        .concat();
+
+        assert_eq!(output, expected);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn gives_up_on_upstream_that_drops_after_every_chunk() {
+        let requests = Arc::new(AtomicU32::new(0));
+        let counter = requests.clone();
+
+        let routes = warp::any().map(move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+            ts_packet(3)
+        });
+        let (addr, server) = warp::serve(routes).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+
+        let url = Url::parse(&format!("http://{}/live.ts", addr)).unwrap();
+        let upstream = iter(vec![Ok(Bytes::from(ts_packet(3)))]);
+
+        let stream = ResponseUtil::new().compose_reconnecting_stream(
+            Arc::new(RestClient::new()),
+            url,
+            HeaderMap::new(),
+            upstream,
+        );
+
+        let output = collect(stream).await;
+
+        assert_eq!(requests.load(Ordering::SeqCst), MAX_RECONNECT_ATTEMPTS);
+        assert_eq!(
+            output.len(),
+            TS_PACKET_SIZE * (MAX_RECONNECT_ATTEMPTS as usize + 1)
+        );
+    }
 }
@@ -0,0 +1,793 @@
+use std::{
+    collections::{hash_map::Entry, HashMap, VecDeque},
+    mem::take,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Error};
+use log::{debug, warn};
+use tokio::{sync::watch, time::timeout};
+use warp::{
+    http::{header::CONTENT_TYPE, StatusCode},
+    hyper::{
+        body::{Bytes, HttpBody},
+        Body, Response,
+    },
+};
+
+use crate::{
+    models::ApiConfiguration,
+    utils::{connection::UserPermit, session::SessionHandle, token::StreamTokenUtil},
+};
+
+const TS_PACKET_SIZE: usize = 188;
+const TS_SYNC_BYTE: u8 = 0x47;
+const PTS_CLOCK_RATE: f64 = 90_000.0;
+const TARGET_SEGMENT_DURATION: Duration = Duration::from_secs(4);
+const MAX_SEGMENT_DURATION: Duration = Duration::from_secs(12);
+const PLAYLIST_WINDOW: usize = 5;
+const SEGMENT_CAPACITY: usize = 8;
+const FIRST_SEGMENT_TIMEOUT: Duration = Duration::from_secs(30);
+const READ_TIMEOUT: Duration = Duration::from_secs(15);
+const IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Keeps one local HLS output per channel, cut from its continuous MPEG-TS upstream into a
+/// bounded ring buffer of segments.
+#[derive(Clone)]
+pub struct SegmenterManager {
+    stream_token_util: StreamTokenUtil,
+    proxy_domain: String,
+    streams: Arc<Mutex<HashMap<u64, Arc<SegmentedStream>>>>,
+}
+
+pub struct SegmentedStream {
+    channel: String,
+    segments: Mutex<VecDeque<Segment>>,
+    last_access: Mutex<Instant>,
+    receiver: watch::Receiver<u64>,
+    viewers: Mutex<HashMap<String, HlsViewer>>,
+}
+
+/// A client polling an hls output. It holds its user connection and session until it stops
+/// polling for longer than the idle timeout, or the output ends.
+pub struct HlsViewer {
+    last_access: Instant,
+    session: SessionHandle,
+    _permit: Option<UserPermit>,
+}
+
+struct Segment {
+    sequence: u64,
+    duration: Duration,
+    data: Bytes,
+}
+
+impl SegmenterManager {
+    pub fn new(config: &ApiConfiguration) -> Self {
+        SegmenterManager {
+            stream_token_util: StreamTokenUtil::new(config),
+            proxy_domain: config
+                .xtream
+                .xtream_proxied_domain
+                .clone()
+                .unwrap_or_default(),
+            streams: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Returns the segmented stream of a channel, along with the sender that has to be
+    /// passed to [`SegmenterManager::run`] when the stream was newly created.
+    pub fn get_or_create(
+        &self,
+        id: u64,
+        channel: &str,
+    ) -> (Arc<SegmentedStream>, Option<watch::Sender<u64>>) {
+        let mut streams = self.streams.lock().unwrap();
+
+        if let Some(stream) = streams.get(&id) {
+            stream.touch();
+            return (stream.clone(), None);
+        }
+
+        let (sender, receiver) = watch::channel(0);
+        let stream = Arc::new(SegmentedStream {
+            channel: channel.to_owned(),
+            segments: Mutex::new(VecDeque::with_capacity(SEGMENT_CAPACITY)),
+            last_access: Mutex::new(Instant::now()),
+            receiver,
+            viewers: Mutex::new(HashMap::new()),
+        });
+
+        streams.insert(id, stream.clone());
+
+        (stream, Some(sender))
+    }
+
+    pub fn get(&self, id: u64) -> Option<Arc<SegmentedStream>> {
+        let stream = self.streams.lock().unwrap().get(&id).cloned();
+
+        if let Some(stream) = &stream {
+            stream.touch();
+        }
+
+        stream
+    }
+
+    pub fn remove(&self, id: u64, stream: &Arc<SegmentedStream>) {
+        stream.viewers.lock().unwrap().clear();
+
+        let mut streams = self.streams.lock().unwrap();
+
+        if streams
+            .get(&id)
+            .is_some_and(|current| Arc::ptr_eq(current, stream))
+        {
+            streams.remove(&id);
+        }
+    }
+
+    pub async fn run(
+        self,
+        id: u64,
+        stream: Arc<SegmentedStream>,
+        sender: watch::Sender<u64>,
+        mut body: Body,
+    ) {
+        let mut segmenter = TsSegmenter::new();
+        let mut sequence = 0;
+
+        loop {
+            if stream.last_access.lock().unwrap().elapsed() > IDLE_TIMEOUT {
+                debug!("Stopping idle hls output for stream {}", id);
+                break;
+            }
+
+            stream.expire_viewers();
+
+            let chunk = match timeout(READ_TIMEOUT, body.data()).await {
+                Ok(Some(Ok(chunk))) => chunk,
+                Ok(Some(Err(err))) => {
+                    warn!("Error reading upstream of hls output {}: {}", id, err);
+                    break;
+                }
+                Ok(None) => break,
+                Err(_) => {
+                    warn!("Upstream of hls output {} stalled", id);
+                    break;
+                }
+            };
+
+            for (duration, data) in segmenter.push(&chunk) {
+                stream.push(Segment {
+                    sequence,
+                    duration,
+                    data,
+                });
+
+                let _ = sender.send(sequence);
+                sequence += 1;
+            }
+        }
+
+        self.remove(id, &stream);
+    }
+
+    pub async fn compose_playlist(
+        &self,
+        id: u64,
+        stream: Arc<SegmentedStream>,
+        account_id: u64,
+    ) -> Result<Response<Body>, Error> {
+        let mut receiver = stream.receiver.clone();
+
+        while stream.segments.lock().unwrap().is_empty() {
+            match timeout(FIRST_SEGMENT_TIMEOUT, receiver.changed()).await {
+                Ok(Ok(())) => {}
+                Ok(Err(_)) => bail!("upstream of hls output {} ended", id),
+                Err(_) => bail!("timed out waiting for the first segment of {}", id),
+            }
+        }
+
+        let segments = stream.segments.lock().unwrap();
+        let window = segments
+            .iter()
+            .skip(segments.len().saturating_sub(PLAYLIST_WINDOW))
+            .collect::<Vec<&Segment>>();
+
+        let target_duration = window
+            .iter()
+            .map(|segment| segment.duration.as_secs_f64().ceil() as u64)
+            .max()
+            .unwrap_or_default()
+            .max(1);
+
+        let mut playlist = format!(
+            "#EXTM3U\n#EXT-X-VERSION:3\n#EXT-X-TARGETDURATION:{}\n#EXT-X-MEDIA-SEQUENCE:{}\n",
+            target_duration,
+            window.first().map_or(0, |segment| segment.sequence)
+        );
+
+        for segment in window {
+            let uri = self.stream_token_util.sign_url(
+                &format!(
+                    "http://{}/stream/{}/{}.ts",
+                    self.proxy_domain, id, segment.sequence
+                ),
+                account_id,
+            );
+
+            playlist.push_str(&format!(
+                "#EXTINF:{:.3},\n{}\n",
+                segment.duration.as_secs_f64(),
+                uri
+            ));
+        }
+
+        let res = Response::builder()
+            .header(CONTENT_TYPE, "application/vnd.apple.mpegurl")
+            .body(Body::from(playlist))?;
+
+        Ok(res)
+    }
+
+    pub fn compose_segment(&self, data: Option<Bytes>) -> Result<Response<Body>, Error> {
+        let res = match data {
+            Some(data) => Response::builder()
+                .header(CONTENT_TYPE, "video/mp2t")
+                .body(Body::from(data))?,
+            None => Response::builder()
+                .status(StatusCode::NOT_FOUND)
+                .body(Body::empty())?,
+        };
+
+        Ok(res)
+    }
+
+    /// Answers a viewer whose session was ended.
+    pub fn compose_ended(&self) -> Result<Response<Body>, Error> {
+        let res = Response::builder()
+            .status(StatusCode::GONE)
+            .body(Body::empty())?;
+
+        Ok(res)
+    }
+}
+
+impl HlsViewer {
+    pub fn new(session: SessionHandle, permit: Option<UserPermit>) -> Self {
+        HlsViewer {
+            last_access: Instant::now(),
+            session,
+            _permit: permit,
+        }
+    }
+}
+
+impl SegmentedStream {
+    pub fn segment(&self, sequence: u64) -> Option<Bytes> {
+        self.segments
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|segment| segment.sequence == sequence)
+            .map(|segment| segment.data.clone())
+    }
+
+    /// Registers a request of a viewer along with the bytes sent to it. `open` is called on
+    /// the first request of a viewer, and again once it timed out. Returns false when the
+    /// session of the viewer was ended, which drops the viewer.
+    pub fn watch<F>(&self, key: String, bytes: u64, open: F) -> Result<bool, Error>
+    where
+        F: FnOnce(&str) -> Result<HlsViewer, Error>,
+    {
+        let mut viewers = self.viewers.lock().unwrap();
+
+        if let Some(viewer) = viewers.get_mut(&key) {
+            if viewer.session.is_cancelled() {
+                viewers.remove(&key);
+                return Ok(false);
+            }
+        }
+
+        let viewer = match viewers.entry(key) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(open(&self.channel)?),
+        };
+
+        viewer.last_access = Instant::now();
+        viewer.session.add_bytes_transferred(bytes);
+
+        Ok(true)
+    }
+
+    fn expire_viewers(&self) {
+        self.viewers
+            .lock()
+            .unwrap()
+            .retain(|_, viewer| viewer.last_access.elapsed() <= IDLE_TIMEOUT);
+    }
+
+    fn touch(&self) {
+        *self.last_access.lock().unwrap() = Instant::now();
+    }
+
+    fn push(&self, segment: Segment) {
+        let mut segments = self.segments.lock().unwrap();
+
+        if segments.len() == SEGMENT_CAPACITY {
+            segments.pop_front();
+        }
+
+        segments.push_back(segment);
+    }
+}
+
+/// Cuts a continuous MPEG-TS stream into segments on keyframes, or on PAT packets for streams
+/// that don't flag random access points. Every segment starts with the latest PAT and PMT.
+struct TsSegmenter {
+    remainder: Vec<u8>,
+    current: Vec<u8>,
+    start_pts: Option<u64>,
+    last_pts: Option<u64>,
+    started_at: Instant,
+    pat: Option<Vec<u8>>,
+    pmt: Option<Vec<u8>>,
+    pmt_pid: Option<u16>,
+    has_random_access: bool,
+}
+
+impl TsSegmenter {
+    fn new() -> Self {
+        TsSegmenter {
+            remainder: vec![],
+            current: vec![],
+            start_pts: None,
+            last_pts: None,
+            started_at: Instant::now(),
+            pat: None,
+            pmt: None,
+            pmt_pid: None,
+            has_random_access: false,
+        }
+    }
+
+    fn push(&mut self, chunk: &[u8]) -> Vec<(Duration, Bytes)> {
+        let mut segments = vec![];
+        let mut offset = 0;
+
+        self.remainder.extend_from_slice(chunk);
+
+        while self.remainder.len() - offset >= TS_PACKET_SIZE {
+            if self.remainder[offset] != TS_SYNC_BYTE {
+                offset += 1;
+                continue;
+            }
+
+            let packet = self.remainder[offset..offset + TS_PACKET_SIZE].to_vec();
+            offset += TS_PACKET_SIZE;
+
+            if let Some(segment) = self.push_packet(&packet) {
+                segments.push(segment);
+            }
+        }
+
+        self.remainder.drain(..offset);
+
+        segments
+    }
+
+    fn push_packet(&mut self, packet: &[u8]) -> Option<(Duration, Bytes)> {
+        let pid = ((packet[1] as u16 & 0x1f) << 8) | packet[2] as u16;
+        let payload_start = packet[1] & 0x40 != 0;
+        let random_access = is_random_access(packet);
+
+        self.has_random_access |= random_access;
+
+        if pid == 0 && payload_start {
+            self.pmt_pid = parse_pmt_pid(packet).or(self.pmt_pid);
+            self.pat = Some(packet.to_vec());
+        } else if Some(pid) == self.pmt_pid && payload_start {
+            self.pmt = Some(packet.to_vec());
+        }
+
+        let pts = match payload_start {
+            true => parse_video_pts(packet),
+            false => None,
+        };
+
+        let duration = self.duration(pts);
+        let is_cut_point = (random_access || (!self.has_random_access && pid == 0))
+            && duration >= TARGET_SEGMENT_DURATION;
+
+        let mut segment = None;
+
+        if !self.current.is_empty()
+            && (is_cut_point || (pid == 0 && duration >= MAX_SEGMENT_DURATION))
+        {
+            segment = Some((duration, Bytes::from(take(&mut self.current))));
+            self.start_pts = None;
+            self.started_at = Instant::now();
+
+            if pid != 0 {
+                if let (Some(pat), Some(pmt)) = (&self.pat, &self.pmt) {
+                    self.current.extend_from_slice(pat);
+                    self.current.extend_from_slice(pmt);
+                }
+            }
+        }
+
+        if let Some(pts) = pts {
+            self.start_pts.get_or_insert(pts);
+            self.last_pts = Some(pts);
+        }
+
+        self.current.extend_from_slice(packet);
+
+        segment
+    }
+
+    fn duration(&self, pts: Option<u64>) -> Duration {
+        match (self.start_pts, pts.or(self.last_pts)) {
+            (Some(start), Some(end)) if end >= start => {
+                Duration::from_secs_f64((end - start) as f64 / PTS_CLOCK_RATE)
+            }
+            _ => self.started_at.elapsed(),
+        }
+    }
+}
+
+fn get_payload(packet: &[u8]) -> Option<&[u8]> {
+    let offset = match (packet[3] >> 4) & 0x03 {
+        0x01 => 4,
+        0x03 => 5 + packet[4] as usize,
+        _ => return None,
+    };
+
+    packet.get(offset..)
+}
+
+fn is_random_access(packet: &[u8]) -> bool {
+    (packet[3] >> 4) & 0x02 != 0 && packet[4] > 0 && packet[5] & 0x40 != 0
+}
+
+fn parse_pmt_pid(packet: &[u8]) -> Option<u16> {
+    let payload = get_payload(packet)?;
+    let section = payload.get(1 + *payload.first()? as usize..)?;
+    let section_length = ((section.get(1)? & 0x0f) as usize) << 8 | *section.get(2)? as usize;
+    let programs = section.get(8..(3 + section_length).saturating_sub(4))?;
+
+    programs
+        .chunks_exact(4)
+        .find(|program| program[0] != 0 || program[1] != 0)
+        .map(|program| ((program[2] as u16 & 0x1f) << 8) | program[3] as u16)
+}
+
+fn parse_video_pts(packet: &[u8]) -> Option<u64> {
+    let payload = get_payload(packet)?;
+
+    if payload.get(0..3)? != [0x00, 0x00, 0x01] || !(0xe0..=0xef).contains(payload.get(3)?) {
+        return None;
+    }
+
+    if payload.get(7)? & 0x80 == 0 {
+        return None;
+    }
+
+    let pts = payload.get(9..14)?;
+
+    Some(
+        ((pts[0] as u64 >> 1) & 0x07) << 30
+            | (pts[1] as u64) << 22
+            | (pts[2] as u64 >> 1) << 15
+            | (pts[3] as u64) << 7
+            | pts[4] as u64 >> 1,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use warp::hyper::body::to_bytes;
+
+    use super::*;
+    use crate::{
+        models::{session::SessionClient, xtream::XtreamConfig},
+        utils::session::SessionManager,
+    };
+
+    const PMT_PID: u16 = 0x1000;
+    const VIDEO_PID: u16 = 0x0100;
+
+    fn packet(pid: u16, payload_start: bool, adaptation: Option<u8>, payload: &[u8]) -> Vec<u8> {
+        let mut packet = vec![
+            TS_SYNC_BYTE,
+            (payload_start as u8) << 6 | (pid >> 8) as u8,
+            pid as u8,
+            0x10,
+        ];
+
+        if let Some(flags) = adaptation {
+            packet[3] = 0x30;
+            packet.extend_from_slice(&[1, flags]);
+        }
+
+        packet.extend_from_slice(payload);
+        packet.resize(TS_PACKET_SIZE, 0xff);
+
+        packet
+    }
+
+    fn pat() -> Vec<u8> {
+        packet(
+            0,
+            true,
+            None,
+            &[
+                0x00,
+                0x00,
+                0xb0,
+                0x0d,
+                0x00,
+                0x01,
+                0xc1,
+                0x00,
+                0x00,
+                0x00,
+                0x01,
+                0xe0 | (PMT_PID >> 8) as u8,
+                PMT_PID as u8,
+                0x00,
+                0x00,
+                0x00,
+                0x00,
+            ],
+        )
+    }
+
+    fn pmt() -> Vec<u8> {
+        packet(PMT_PID, true, None, &[0x00, 0x02])
+    }
+
+    fn video(seconds: f64, keyframe: bool) -> Vec<u8> {
+        let pts = (seconds * PTS_CLOCK_RATE) as u64;
+        let adaptation = match keyframe {
+            true => Some(0x40),
+            false => None,
+        };
+
+        packet(
+            VIDEO_PID,
+            true,
+            adaptation,
+            &[
+                0x00,
+                0x00,
+                0x01,
+                0xe0,
+                0x00,
+                0x00,
+                0x80,
+                0x80,
+                0x05,
+                0x21 | ((pts >> 29) & 0x0e) as u8,
+                (pts >> 22) as u8,
+                0x01 | ((pts >> 14) & 0xfe) as u8,
+                (pts >> 7) as u8,
+                0x01 | ((pts << 1) & 0xfe) as u8,
+            ],
+        )
+    }
+
+    fn continuation() -> Vec<u8> {
+        packet(VIDEO_PID, false, None, &[0x00])
+    }
+
+    fn push_all(segmenter: &mut TsSegmenter, packets: &[Vec<u8>]) -> Vec<(Duration, Bytes)> {
+        segmenter.push(&packets.concat())
+    }
+
+    #[test]
+    fn parses_pmt_pid_and_pts() {
+        assert_eq!(parse_pmt_pid(&pat()), Some(PMT_PID));
+        assert_eq!(parse_video_pts(&video(4.0, false)), Some(360_000));
+        assert_eq!(parse_video_pts(&continuation()), None);
+        assert!(is_random_access(&video(0.0, true)));
+        assert!(!is_random_access(&video(0.0, false)));
+    }
+
+    #[test]
+    fn cuts_on_keyframes_after_target_duration() {
+        let mut segmenter = TsSegmenter::new();
+
+        let segments = push_all(
+            &mut segmenter,
+            &[
+                pat(),
+                pmt(),
+                video(0.0, true),
+                continuation(),
+                video(2.0, true),
+                video(4.0, true),
+                continuation(),
+            ],
+        );
+
+        assert_eq!(segments.len(), 1);
+        assert_eq!(segments[0].0, Duration::from_secs(4));
+        assert_eq!(
+            segments[0].1,
+            [
+                pat(),
+                pmt(),
+                video(0.0, true),
+                continuation(),
+                video(2.0, true)
+            ]
+            .concat()
+        );
+
+        let segments = push_all(&mut segmenter, &[video(8.0, true)]);
+
+        assert_eq!(segments.len(), 1);
+        assert_eq!(
+            segments[0].1,
+            [pat(), pmt(), video(4.0, true), continuation()].concat()
+        );
+    }
+
+    #[test]
+    fn cuts_on_pat_without_random_access_flags() {
+        let mut segmenter = TsSegmenter::new();
+
+        let segments = push_all(
+            &mut segmenter,
+            &[
+                pat(),
+                pmt(),
+                video(0.0, false),
+                video(4.5, false),
+                pat(),
+                pmt(),
+                video(5.0, false),
+            ],
+        );
+
+        assert_eq!(segments.len(), 1);
+        assert_eq!(segments[0].0, Duration::from_secs_f64(4.5));
+        assert_eq!(
+            segments[0].1,
+            [pat(), pmt(), video(0.0, false), video(4.5, false)].concat()
+        );
+    }
+
+    #[test]
+    fn cuts_on_pat_after_max_duration_without_keyframe() {
+        let mut segmenter = TsSegmenter::new();
+
+        let segments = push_all(
+            &mut segmenter,
+            &[
+                pat(),
+                pmt(),
+                video(0.0, true),
+                video(6.0, false),
+                pat(),
+                video(13.0, false),
+            ],
+        );
+
+        assert!(segments.is_empty());
+
+        let segments = push_all(&mut segmenter, &[pat()]);
+
+        assert_eq!(segments.len(), 1);
+        assert_eq!(segments[0].0, Duration::from_secs(13));
+    }
+
+    #[test]
+    fn resyncs_after_garbage_and_split_chunks() {
+        let mut segmenter = TsSegmenter::new();
+        let stream = [
+            vec![0x00, 0x12, 0x34],
+            pat(),
+            pmt(),
+            video(0.0, true),
+            vec![0xde, 0xad],
+            video(4.0, true),
+        ]
+        .concat();
+
+        let (first, second) = stream.split_at(200);
+
+        assert!(segmenter.push(first).is_empty());
+
+        let segments = segmenter.push(second);
+
+        assert_eq!(segments.len(), 1);
+        assert_eq!(segments[0].1, [pat(), pmt(), video(0.0, true)].concat());
+        assert!(segmenter.remainder.is_empty());
+    }
+
+    #[tokio::test]
+    async fn lists_sliding_window_of_segments() {
+        let manager = SegmenterManager::new(&ApiConfiguration {
+            stream_token_secret: String::from("secret"),
+            stream_token_ttl: 60,
+            xtream: XtreamConfig {
+                xtream_proxied_domain: Some(String::from("proxy.local")),
+                ..XtreamConfig::default()
+            },
+            ..ApiConfiguration::default()
+        });
+
+        let (stream, sender) = manager.get_or_create(7, "News");
+
+        assert!(sender.is_some());
+        assert!(manager.get_or_create(7, "News").1.is_none());
+
+        for sequence in 0..10 {
+            stream.push(Segment {
+                sequence,
+                duration: Duration::from_secs_f64(3.5 + sequence as f64 / 10.0),
+                data: Bytes::from(vec![sequence as u8]),
+            });
+        }
+
+        let playlist = manager.compose_playlist(7, stream, 0).await.unwrap();
+        let playlist = to_bytes(playlist.into_body()).await.unwrap();
+        let playlist = String::from_utf8_lossy(&playlist);
+        let lines: Vec<&str> = playlist.lines().collect();
+
+        assert_eq!(lines[2], "#EXT-X-TARGETDURATION:5");
+        assert_eq!(lines[3], "#EXT-X-MEDIA-SEQUENCE:5");
+        assert_eq!(lines[4], "#EXTINF:4.000,");
+        assert!(lines[5].starts_with("http://proxy.local/stream/7/5.ts?token="));
+        assert!(lines[13].starts_with("http://proxy.local/stream/7/9.ts?token="));
+        assert_eq!(lines.len(), 14);
+
+        let stream = manager.get(7).unwrap();
+
+        let evicted = manager.compose_segment(stream.segment(1)).unwrap();
+        assert_eq!(evicted.status(), StatusCode::NOT_FOUND);
+
+        let segment = manager.compose_segment(stream.segment(9)).unwrap();
+        assert_eq!(to_bytes(segment.into_body()).await.unwrap(), vec![9]);
+    }
+
+    #[test]
+    fn keeps_viewer_session_until_it_is_ended() {
+        let manager = SegmenterManager::new(&ApiConfiguration::default());
+        let session_manager = SessionManager::new();
+        let client = || SessionClient {
+            ip: Some(String::from("10.0.0.1")),
+            user_agent: None,
+        };
+        let open = |channel: &str| {
+            Ok(HlsViewer::new(
+                session_manager.open(channel.to_owned(), None, client()),
+                None,
+            ))
+        };
+
+        let (stream, _) = manager.get_or_create(7, "News");
+
+        assert!(stream.watch(String::from("alice"), 0, open).unwrap());
+        assert!(stream
+            .watch(String::from("alice"), 42, |_| panic!("viewer opened twice"))
+            .unwrap());
+
+        let sessions = session_manager.get_sessions();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].channel, "News");
+        assert_eq!(sessions[0].bytes_transferred, 42);
+
+        session_manager.cancel(sessions[0].id);
+
+        assert!(!stream.watch(String::from("alice"), 0, open).unwrap());
+        assert!(session_manager.get_sessions().is_empty());
+
+        assert!(stream.watch(String::from("alice"), 0, open).unwrap());
+        manager.remove(7, &stream);
+        assert!(session_manager.get_sessions().is_empty());
+    }
+}
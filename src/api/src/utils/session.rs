@@ -0,0 +1,333 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use chrono::Utc;
+use futures::StreamExt;
+use log::{debug, info};
+use tokio::sync::{oneshot, oneshot::error::TryRecvError};
+use warp::{
+    http::header::CONTENT_TYPE,
+    hyper::{Body, Response},
+};
+
+use crate::models::session::{Session, SessionClient};
+
+/// Keeps track of the streams that are currently proxied to clients.
+#[derive(Clone)]
+pub struct SessionManager {
+    sessions: Arc<Mutex<HashMap<u64, SessionEntry>>>,
+    next_session_id: Arc<AtomicU64>,
+}
+
+struct SessionEntry {
+    session: Session,
+    bytes_transferred: Arc<AtomicU64>,
+    cancel: oneshot::Sender<()>,
+}
+
+/// A registered session, listed until dropped.
+pub struct SessionHandle {
+    bytes_transferred: Arc<AtomicU64>,
+    cancelled: oneshot::Receiver<()>,
+    guard: SessionGuard,
+}
+
+impl SessionHandle {
+    pub fn add_bytes_transferred(&self, bytes: u64) {
+        self.bytes_transferred.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Whether the session was ended through [`SessionManager::cancel`].
+    pub fn is_cancelled(&mut self) -> bool {
+        !matches!(self.cancelled.try_recv(), Err(TryRecvError::Empty))
+    }
+}
+
+/// Removes a session from the registry once its response body is dropped.
+struct SessionGuard {
+    id: u64,
+    sessions: Arc<Mutex<HashMap<u64, SessionEntry>>>,
+}
+
+impl Drop for SessionGuard {
+    fn drop(&mut self) {
+        if self.sessions.lock().unwrap().remove(&self.id).is_some() {
+            debug!("Closed session {}", self.id);
+        }
+    }
+}
+
+impl Default for SessionManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SessionManager {
+    pub fn new() -> Self {
+        SessionManager {
+            sessions: Arc::new(Mutex::new(HashMap::new())),
+            next_session_id: Arc::new(AtomicU64::new(1)),
+        }
+    }
+
+    pub fn get_sessions(&self) -> Vec<Session> {
+        let mut sessions: Vec<Session> = self
+            .sessions
+            .lock()
+            .unwrap()
+            .values()
+            .map(|entry| Session {
+                bytes_transferred: entry.bytes_transferred.load(Ordering::Relaxed),
+                ..entry.session.clone()
+            })
+            .collect();
+
+        sessions.sort_by_key(|session| session.id);
+
+        sessions
+    }
+
+    /// Ends the stream of a session. Returns false if there is no such session.
+    pub fn cancel(&self, id: u64) -> bool {
+        match self.sessions.lock().unwrap().remove(&id) {
+            Some(entry) => {
+                info!(
+                    "Kicked session {} of {} watching {}",
+                    id,
+                    entry.session.username.as_deref().unwrap_or("-"),
+                    entry.session.channel
+                );
+                let _ = entry.cancel.send(());
+
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Registers a session, which is listed until the returned handle is dropped.
+    pub fn open(
+        &self,
+        channel: String,
+        username: Option<String>,
+        client: SessionClient,
+    ) -> SessionHandle {
+        let id = self.next_session_id.fetch_add(1, Ordering::Relaxed);
+        let bytes_transferred = Arc::new(AtomicU64::new(0));
+        let (cancel, cancelled) = oneshot::channel();
+
+        debug!(
+            "Opened session {} for {} from {}",
+            id,
+            channel,
+            client.ip.as_deref().unwrap_or("-")
+        );
+
+        self.sessions.lock().unwrap().insert(
+            id,
+            SessionEntry {
+                session: Session {
+                    id,
+                    username,
+                    channel,
+                    client_ip: client.ip,
+                    user_agent: client.user_agent,
+                    started_at: Utc::now().naive_utc(),
+                    bytes_transferred: 0,
+                },
+                bytes_transferred: bytes_transferred.clone(),
+                cancel,
+            },
+        );
+
+        SessionHandle {
+            bytes_transferred,
+            cancelled,
+            guard: SessionGuard {
+                id,
+                sessions: self.sessions.clone(),
+            },
+        }
+    }
+
+    /// Registers the stream of a response for as long as the client reads its body. Playlists
+    /// and failed responses are not streams and are returned as is.
+    pub fn track(
+        &self,
+        res: Response<Body>,
+        channel: String,
+        username: Option<String>,
+        client: SessionClient,
+    ) -> Response<Body> {
+        let is_playlist = res
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|content_type| content_type.to_str().ok())
+            .is_some_and(|content_type| content_type.to_lowercase().contains("mpegurl"));
+
+        if is_playlist || !res.status().is_success() {
+            return res;
+        }
+
+        let SessionHandle {
+            bytes_transferred,
+            cancelled,
+            guard,
+        } = self.open(channel, username, client);
+
+        res.map(|body| {
+            Body::wrap_stream(body.take_until(cancelled).map(move |chunk| {
+                let _guard = &guard;
+
+                if let Ok(chunk) = &chunk {
+                    bytes_transferred.fetch_add(chunk.len() as u64, Ordering::Relaxed);
+                }
+
+                chunk
+            }))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use warp::hyper::body::{to_bytes, Bytes};
+
+    use super::*;
+
+    fn response(status: u16, content_type: &str, body: Body) -> Response<Body> {
+        Response::builder()
+            .status(status)
+            .header(CONTENT_TYPE, content_type)
+            .body(body)
+            .unwrap()
+    }
+
+    fn client() -> SessionClient {
+        SessionClient {
+            ip: Some(String::from("10.0.0.1")),
+            user_agent: Some(String::from("player")),
+        }
+    }
+
+    #[tokio::test]
+    async fn tracks_stream_until_body_is_read() {
+        let session_manager = SessionManager::new();
+
+        let res = session_manager.track(
+            response(200, "video/mp2t", Body::from("stream")),
+            String::from("News"),
+            Some(String::from("alice")),
+            client(),
+        );
+
+        let sessions = session_manager.get_sessions();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].channel, "News");
+        assert_eq!(sessions[0].username.as_deref(), Some("alice"));
+        assert_eq!(sessions[0].client_ip.as_deref(), Some("10.0.0.1"));
+
+        assert_eq!(to_bytes(res.into_body()).await.unwrap(), "stream");
+        assert!(session_manager.get_sessions().is_empty());
+    }
+
+    #[tokio::test]
+    async fn counts_transferred_bytes() {
+        let session_manager = SessionManager::new();
+        let (mut sender, body) = Body::channel();
+
+        let mut body = session_manager
+            .track(
+                response(200, "video/mp2t", body),
+                String::from("News"),
+                None,
+                client(),
+            )
+            .into_body();
+
+        sender.send_data(Bytes::from("0123456789")).await.unwrap();
+        body.next().await.unwrap().unwrap();
+
+        assert_eq!(session_manager.get_sessions()[0].bytes_transferred, 10);
+    }
+
+    #[test]
+    fn ignores_playlists_and_failed_responses() {
+        let session_manager = SessionManager::new();
+
+        session_manager.track(
+            response(200, "application/vnd.apple.mpegURL", Body::empty()),
+            String::from("News"),
+            None,
+            client(),
+        );
+        session_manager.track(
+            response(404, "video/mp2t", Body::empty()),
+            String::from("News"),
+            None,
+            client(),
+        );
+
+        assert!(session_manager.get_sessions().is_empty());
+    }
+
+    #[test]
+    fn removes_session_when_response_is_dropped() {
+        let session_manager = SessionManager::new();
+
+        let res = session_manager.track(
+            response(200, "video/mp2t", Body::empty()),
+            String::from("News"),
+            None,
+            client(),
+        );
+        assert_eq!(session_manager.get_sessions().len(), 1);
+
+        drop(res);
+        assert!(session_manager.get_sessions().is_empty());
+    }
+
+    #[test]
+    fn lists_opened_session_until_its_handle_is_dropped() {
+        let session_manager = SessionManager::new();
+
+        let mut handle = session_manager.open(String::from("News"), None, client());
+        handle.add_bytes_transferred(42);
+
+        let sessions = session_manager.get_sessions();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].bytes_transferred, 42);
+        assert!(!handle.is_cancelled());
+
+        assert!(session_manager.cancel(sessions[0].id));
+        assert!(handle.is_cancelled());
+
+        drop(handle);
+        assert!(session_manager.get_sessions().is_empty());
+    }
+
+    #[tokio::test]
+    async fn kicks_session_by_ending_its_stream() {
+        let session_manager = SessionManager::new();
+        let (_sender, body) = Body::channel();
+
+        let res = session_manager.track(
+            response(200, "video/mp2t", body),
+            String::from("News"),
+            None,
+            client(),
+        );
+        let id = session_manager.get_sessions()[0].id;
+
+        assert!(session_manager.cancel(id));
+        assert!(session_manager.get_sessions().is_empty());
+        assert!(to_bytes(res.into_body()).await.unwrap().is_empty());
+        assert!(!session_manager.cancel(id));
+    }
+}
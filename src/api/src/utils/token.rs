@@ -0,0 +1,271 @@
+use std::sync::Arc;
+
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::models::ApiConfiguration;
+
+type HmacSha256 = Hmac<Sha256>;
+
+const PROTECTED_PATHS: [&str; 6] = ["/stream/", "/attr/", "/url/", "/xmltv/", "/hls/", "/epg"];
+
+/// Signs proxy URLs with HMAC-SHA256 tokens, so that they can only be used by clients that got
+/// them from an authenticated playlist or API response. Every token is bound to the account it
+/// was issued to, which is checked again whenever the token is used. Tokens expire after the
+/// configured ttl, or never with a ttl of 0.
+#[derive(Clone)]
+pub struct StreamTokenUtil {
+    secret: Arc<Vec<u8>>,
+    ttl: i64,
+    proxy_domain: String,
+}
+
+impl StreamTokenUtil {
+    pub fn new(config: &ApiConfiguration) -> Self {
+        StreamTokenUtil {
+            secret: Arc::new(config.stream_token_secret.as_bytes().to_vec()),
+            ttl: config.stream_token_ttl as i64,
+            proxy_domain: config
+                .xtream
+                .xtream_proxied_domain
+                .clone()
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Creates a token for the resource of a path and an account, valid for the configured ttl.
+    pub fn sign(&self, path: &str, account_id: u64) -> String {
+        let expires = match self.ttl {
+            0 => 0,
+            ttl => Utc::now().timestamp() + ttl,
+        };
+
+        let signature = self
+            .compose_mac(path, account_id, expires)
+            .finalize()
+            .into_bytes();
+
+        format!("{}.{}.{}", account_id, expires, hex::encode(signature))
+    }
+
+    /// Returns the id of the account a valid, unexpired token for the path was issued to.
+    pub fn verify(&self, path: &str, token: &str) -> Option<u64> {
+        let mut parts = token.splitn(3, '.');
+        let account_id = parts.next()?.parse::<u64>().ok()?;
+        let expires = parts.next()?.parse::<i64>().ok()?;
+        let signature = hex::decode(parts.next()?).ok()?;
+
+        if expires != 0 && expires < Utc::now().timestamp() {
+            return None;
+        }
+
+        self.compose_mac(path, account_id, expires)
+            .verify_slice(&signature)
+            .ok()?;
+
+        Some(account_id)
+    }
+
+    /// Appends a token to a URL of this proxy. Any other URL is returned as is.
+    pub fn sign_url(&self, url: &str, account_id: u64) -> String {
+        let path = match url
+            .strip_prefix("http://")
+            .and_then(|url| url.strip_prefix(self.proxy_domain.as_str()))
+        {
+            Some(path)
+                if PROTECTED_PATHS
+                    .iter()
+                    .any(|prefix| path.starts_with(prefix)) =>
+            {
+                path
+            }
+            _ => return url.to_string(),
+        };
+
+        let separator = match path.contains('?') {
+            true => '&',
+            false => '?',
+        };
+
+        format!("{}{}token={}", url, separator, self.sign(path, account_id))
+    }
+
+    /// Signs every URL of this proxy in a playlist, both on URI lines and in attributes.
+    pub fn sign_playlist(&self, playlist: &str, account_id: u64) -> String {
+        let prefix = format!("http://{}/", self.proxy_domain);
+        let mut signed = String::with_capacity(playlist.len());
+        let mut rest = playlist;
+
+        while let Some(start) = rest.find(&prefix) {
+            let end = rest[start..]
+                .find(|c: char| c == '"' || c.is_whitespace())
+                .map_or(rest.len(), |end| start + end);
+
+            signed.push_str(&rest[..start]);
+            signed.push_str(&self.sign_url(&rest[start..end], account_id));
+            rest = &rest[end..];
+        }
+
+        signed.push_str(rest);
+
+        signed
+    }
+
+    /// Tokens are bound to a path without its query and without the extension of its last
+    /// segment, so that `/stream/{id}`, `/stream/{id}.ts` and `/stream/{id}.m3u8` share one.
+    fn compose_mac(&self, path: &str, account_id: u64, expires: i64) -> HmacSha256 {
+        let path = path.split('?').next().unwrap_or_default();
+        let path = match path.rsplit_once('/') {
+            Some((parent, segment)) => match segment.split_once('.') {
+                Some((segment, _)) => format!("{}/{}", parent, segment),
+                None => path.to_string(),
+            },
+            None => path.to_string(),
+        };
+
+        let mut mac =
+            HmacSha256::new_from_slice(&self.secret).expect("HMAC accepts keys of any length");
+        mac.update(format!("{}:{}:{}", path, account_id, expires).as_bytes());
+
+        mac
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::xtream::XtreamConfig;
+
+    fn stream_token_util(ttl: u64) -> StreamTokenUtil {
+        StreamTokenUtil::new(&ApiConfiguration {
+            stream_token_secret: String::from("secret"),
+            stream_token_ttl: ttl,
+            xtream: XtreamConfig {
+                xtream_proxied_domain: Some(String::from("proxy.local")),
+                ..XtreamConfig::default()
+            },
+            ..ApiConfiguration::default()
+        })
+    }
+
+    fn token(url: &str) -> &str {
+        url.split_once("token=").unwrap().1
+    }
+
+    #[test]
+    fn verifies_token_of_its_account() {
+        let stream_token_util = stream_token_util(60);
+        let token = stream_token_util.sign("/stream/42", 7);
+
+        assert_eq!(stream_token_util.verify("/stream/42", &token), Some(7));
+        assert_eq!(stream_token_util.verify("/stream/43", &token), None);
+    }
+
+    #[test]
+    fn rejects_tampered_account_and_signature() {
+        let stream_token_util = stream_token_util(60);
+        let token = stream_token_util.sign("/stream/42", 7);
+        let (_, rest) = token.split_once('.').unwrap();
+
+        assert_eq!(
+            stream_token_util.verify("/stream/42", &format!("8.{}", rest)),
+            None
+        );
+        assert_eq!(
+            stream_token_util.verify("/stream/42", &format!("{}0", token)),
+            None
+        );
+        assert_eq!(stream_token_util.verify("/stream/42", "7.abc"), None);
+        assert_eq!(stream_token_util.verify("/stream/42", ""), None);
+    }
+
+    #[test]
+    fn rejects_token_of_other_secret() {
+        let token = stream_token_util(60).sign("/stream/42", 7);
+        let other = StreamTokenUtil {
+            secret: Arc::new(b"other".to_vec()),
+            ..stream_token_util(60)
+        };
+
+        assert_eq!(other.verify("/stream/42", &token), None);
+    }
+
+    #[test]
+    fn rejects_expired_token() {
+        let stream_token_util = StreamTokenUtil {
+            ttl: -1,
+            ..stream_token_util(60)
+        };
+        let token = stream_token_util.sign("/stream/42", 7);
+
+        assert_eq!(stream_token_util.verify("/stream/42", &token), None);
+    }
+
+    #[test]
+    fn keeps_token_valid_without_ttl() {
+        let stream_token_util = stream_token_util(0);
+        let token = stream_token_util.sign("/stream/42", 7);
+
+        assert!(token.starts_with("7.0."));
+        assert_eq!(stream_token_util.verify("/stream/42", &token), Some(7));
+    }
+
+    #[test]
+    fn shares_token_across_extensions_and_queries() {
+        let stream_token_util = stream_token_util(60);
+        let token = stream_token_util.sign("/stream/42", 7);
+
+        assert_eq!(stream_token_util.verify("/stream/42.ts", &token), Some(7));
+        assert_eq!(stream_token_util.verify("/stream/42.m3u8", &token), Some(7));
+        assert_eq!(
+            stream_token_util.verify("/stream/42?token=old", &token),
+            Some(7)
+        );
+        assert_eq!(stream_token_util.verify("/stream/420.ts", &token), None);
+        assert_eq!(stream_token_util.verify("/attr/42", &token), None);
+    }
+
+    #[test]
+    fn signs_only_protected_urls_of_this_proxy() {
+        let stream_token_util = stream_token_util(60);
+
+        let signed = stream_token_util.sign_url("http://proxy.local/stream/42.ts", 7);
+        assert!(signed.starts_with("http://proxy.local/stream/42.ts?token="));
+        assert_eq!(
+            stream_token_util.verify("/stream/42.ts", token(&signed)),
+            Some(7)
+        );
+
+        let signed = stream_token_util.sign_url("http://proxy.local/url/5?x=1", 7);
+        assert!(signed.starts_with("http://proxy.local/url/5?x=1&token="));
+
+        for url in [
+            "http://proxy.local/live/user/pass/42.ts",
+            "http://other.local/stream/42",
+            "https://proxy.local/stream/42",
+        ] {
+            assert_eq!(stream_token_util.sign_url(url, 7), url);
+        }
+    }
+
+    #[test]
+    fn signs_every_proxy_url_of_a_playlist() {
+        let stream_token_util = stream_token_util(60);
+        let playlist = stream_token_util.sign_playlist(
+            "#EXTM3U url-tvg=\"http://proxy.local/epg\"\n#EXTINF:-1 tvg-logo=\"http://proxy.local/attr/3\",One\nhttp://proxy.local/stream/1\nhttp://upstream.example/2.ts\n",
+            7,
+        );
+        let lines: Vec<&str> = playlist.lines().collect();
+
+        assert!(lines[0].starts_with("#EXTM3U url-tvg=\"http://proxy.local/epg?token="));
+        assert!(lines[0].ends_with('"'));
+        assert!(lines[1].contains("tvg-logo=\"http://proxy.local/attr/3?token="));
+        assert!(lines[1].ends_with("\",One"));
+        assert_eq!(
+            stream_token_util.verify("/stream/1", token(lines[2])),
+            Some(7)
+        );
+        assert_eq!(lines[3], "http://upstream.example/2.ts");
+    }
+}
@@ -1,12 +1,9 @@
 use crate::models::{Path, Track};
 use anyhow::{Context, Error};
-use db::{
-    models::{HlsUrlRequest, M3uModel},
-    CRUD, DB,
-};
+use db::models::M3uModel;
 use iptv::m3u::parser::{parse_extension, parse_track_id};
 use reqwest::Url;
-use std::{fmt::Write, sync::Arc};
+use std::fmt::Write;
 
 #[derive(Default, Debug, Clone, PartialEq, Copy)]
 pub struct UrlUtil;
@@ -83,7 +80,7 @@ impl UrlUtil {
 
         self.compose_host(&mut url, m3u.domain, m3u.port)?;
 
-        if None == path.segment3 {
+        if path.segment3.is_none() {
             self.compose_two_segment_url(
                 &mut url,
                 path,
@@ -115,18 +112,12 @@ impl UrlUtil {
         segment1_replacement: Option<String>,
         segment2_replacement: Option<String>,
     ) -> Result<(), Error> {
-        if path.segment1.is_some() {
-            match segment1_replacement {
-                Some(segment) => write!(url, "/{}", segment)?,
-                None => write!(url, "/{}", path.segment1.unwrap())?,
-            }
+        if let Some(segment1) = path.segment1 {
+            write!(url, "/{}", segment1_replacement.unwrap_or(segment1))?;
         }
 
-        if path.segment2.is_some() {
-            match segment2_replacement {
-                Some(segment) => write!(url, "/{}", segment)?,
-                None => write!(url, "/{}", path.segment2.unwrap())?,
-            }
+        if let Some(segment2) = path.segment2 {
+            write!(url, "/{}", segment2_replacement.unwrap_or(segment2))?;
         }
 
         Ok(())
@@ -143,52 +134,14 @@ impl UrlUtil {
             write!(url, "/{}", segment1)?;
         }
 
-        if path.segment2.is_some() {
-            match segment2_replacement {
-                Some(segment) => write!(url, "/{}", segment)?,
-                None => write!(url, "/{}", path.segment2.unwrap())?,
-            }
-        }
-
-        if path.segment3.is_some() {
-            match segment3_replacement {
-                Some(segment) => write!(url, "/{}", segment)?,
-                None => write!(url, "/{}", path.segment3.unwrap())?,
-            }
+        if let Some(segment2) = path.segment2 {
+            write!(url, "/{}", segment2_replacement.unwrap_or(segment2))?;
         }
 
-        Ok(())
-    }
-
-    pub fn compose_final_response_url(&self, response_url: &Url) -> Result<String, Error> {
-        let mut url = String::new();
-
-        write!(url, "{}://", response_url.scheme()).context("writing scheme")?;
-
-        if let Some(host) = response_url.host_str() {
-            write!(url, "{}", host).context("writing host")?;
+        if let Some(segment3) = path.segment3 {
+            write!(url, "/{}", segment3_replacement.unwrap_or(segment3))?;
         }
 
-        if let Some(port) = response_url.port() {
-            write!(url, ":{}", port).context("writing host scheme")?;
-        }
-
-        Ok(url)
-    }
-
-    pub fn is_hls_stream(&self, url: String) -> bool {
-        url.ends_with(".m3u8")
-    }
-
-    pub async fn persist_final_response_url(&self, url: &Url, db: Arc<DB>) -> Result<(), Error> {
-        let mut tx = db.pool.begin().await?;
-
-        db.hls_url.truncate(&mut tx).await?;
-
-        let url = self.compose_final_response_url(url)?;
-
-        db.hls_url.insert(&mut tx, HlsUrlRequest { url }).await?;
-
         Ok(())
     }
 }
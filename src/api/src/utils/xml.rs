@@ -8,17 +8,28 @@ use quick_xml::{
 use reqwest::Url;
 use std::{io::Cursor, sync::Arc};
 
+use crate::utils::token::StreamTokenUtil;
+
 #[derive(Clone)]
 pub struct XmlUtil {
     db: Arc<DB>,
+    stream_token_util: StreamTokenUtil,
 }
 
 impl XmlUtil {
-    pub fn new(db: Arc<DB>) -> Self {
-        XmlUtil { db }
+    pub fn new(db: Arc<DB>, stream_token_util: StreamTokenUtil) -> Self {
+        XmlUtil {
+            db,
+            stream_token_util,
+        }
     }
 
-    pub async fn proxify_xmltv(&self, xml: &str, domain: String) -> Result<Vec<u8>, Error> {
+    pub async fn proxify_xmltv(
+        &self,
+        xml: &str,
+        domain: String,
+        account_id: u64,
+    ) -> Result<Vec<u8>, Error> {
         let mut reader = Reader::from_reader(xml.as_bytes());
 
         reader.trim_text(true);
@@ -34,30 +45,25 @@ impl XmlUtil {
                     if e.name().as_ref() == b"icon" {
                         let event = e.to_owned();
 
-                        match event.try_get_attribute("src")? {
-                            Some(src) => {
-                                let value = src.unescape_value()?;
-
-                                match Url::parse(&value) {
-                                    Ok(url) => {
-                                        if url.to_string().starts_with("http") {
-                                            let icon_element = self
-                                                .clone()
-                                                .persist_proxify_xml_url(
-                                                    e.attributes(),
-                                                    url,
-                                                    domain.clone(),
-                                                    &mut tx,
-                                                )
-                                                .await?;
-
-                                            writer.write_event(Event::Empty(icon_element))?;
-                                        }
-                                    }
-                                    _ => (),
+                        if let Some(src) = event.try_get_attribute("src")? {
+                            let value = src.unescape_value()?;
+
+                            if let Ok(url) = Url::parse(&value) {
+                                if url.to_string().starts_with("http") {
+                                    let icon_element = self
+                                        .clone()
+                                        .persist_proxify_xml_url(
+                                            e.attributes(),
+                                            url,
+                                            domain.clone(),
+                                            account_id,
+                                            &mut tx,
+                                        )
+                                        .await?;
+
+                                    writer.write_event(Event::Empty(icon_element))?;
                                 }
                             }
-                            None => (),
                         }
                     }
                 }
@@ -80,7 +86,8 @@ impl XmlUtil {
         attributes: Attributes<'_>,
         url: Url,
         domain: String,
-        mut tx: &'a mut Connection,
+        account_id: u64,
+        tx: &'a mut Connection,
     ) -> Result<BytesStart<'a>, Error> {
         let mut icon_element = BytesStart::new("icon");
 
@@ -91,7 +98,7 @@ impl XmlUtil {
                 .db
                 .xmltv_url
                 .insert(
-                    &mut tx,
+                    tx,
                     XmltvUrlRequest {
                         url: url.to_string(),
                     },
@@ -99,8 +106,11 @@ impl XmlUtil {
                 .await?;
 
             if attr.key == QName(b"src") {
-                icon_element
-                    .push_attribute(("src", format!("http://{}/xmltv/{}", domain, id).as_str()));
+                let src = self
+                    .stream_token_util
+                    .sign_url(&format!("http://{}/xmltv/{}", domain, id), account_id);
+
+                icon_element.push_attribute(("src", src.as_str()));
             } else {
                 icon_element.push_attribute(attr);
             }
@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS xtream_user_group (
+     id BIGINT UNSIGNED PRIMARY KEY NOT NULL AUTO_INCREMENT,
+     xtream_user_id BIGINT UNSIGNED NOT NULL,
+    `name` TEXT NOT NULL,
+     created_at DATETIME,
+     modified_at DATETIME,
+     FOREIGN KEY (xtream_user_id) REFERENCES xtream_user(id) ON DELETE CASCADE
+);
@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS api_key (
+     id BIGINT UNSIGNED PRIMARY KEY NOT NULL AUTO_INCREMENT,
+    `name` TEXT NOT NULL,
+     key_hash TEXT NOT NULL,
+     scope TEXT NOT NULL,
+     created_at DATETIME,
+     modified_at DATETIME
+);
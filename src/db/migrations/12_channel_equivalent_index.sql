@@ -0,0 +1,3 @@
+ALTER TABLE extinf MODIFY normalized_name VARCHAR(255);
+CREATE INDEX extinf_normalized_name ON extinf (normalized_name);
+CREATE INDEX attribute_key_value ON attribute (`key`(32), `value`(191));
@@ -0,0 +1,4 @@
+ALTER TABLE xtream_user MODIFY username VARCHAR(255) NOT NULL;
+CREATE UNIQUE INDEX xtream_user_username ON xtream_user (username);
+ALTER TABLE api_key MODIFY key_hash VARCHAR(64) NOT NULL;
+CREATE UNIQUE INDEX api_key_key_hash ON api_key (key_hash);
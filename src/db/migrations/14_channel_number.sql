@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS channel_number (
+     id BIGINT UNSIGNED PRIMARY KEY NOT NULL AUTO_INCREMENT,
+     tvg_id VARCHAR(191) NOT NULL,
+     track_id VARCHAR(191) NOT NULL,
+     chno INT UNSIGNED NOT NULL,
+     created_at DATETIME,
+     modified_at DATETIME,
+     UNIQUE KEY channel_number_channel (tvg_id, track_id)
+);
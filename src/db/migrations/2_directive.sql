@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS directive (
+     id BIGINT UNSIGNED PRIMARY KEY NOT NULL AUTO_INCREMENT,
+    `tag` TEXT NOT NULL,
+    `value` TEXT NOT NULL,
+     extinf_id BIGINT UNSIGNED,
+     FOREIGN KEY (extinf_id) REFERENCES extinf(id)
+);
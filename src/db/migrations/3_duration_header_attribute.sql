@@ -0,0 +1,9 @@
+ALTER TABLE extinf ADD COLUMN duration TEXT;
+
+CREATE TABLE IF NOT EXISTS header_attribute (
+     id BIGINT UNSIGNED PRIMARY KEY NOT NULL AUTO_INCREMENT,
+    `key` TEXT NOT NULL,
+    `value` TEXT NOT NULL,
+     m3u_id BIGINT UNSIGNED,
+     FOREIGN KEY (m3u_id) REFERENCES m3u(id)
+);
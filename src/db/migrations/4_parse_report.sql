@@ -0,0 +1,14 @@
+CREATE TABLE IF NOT EXISTS parse_report (
+     id BIGINT UNSIGNED PRIMARY KEY NOT NULL AUTO_INCREMENT,
+     total_lines INT UNSIGNED NOT NULL,
+     invalid_lines INT UNSIGNED NOT NULL,
+     invalid_extinf_entries INT UNSIGNED NOT NULL,
+     duplicate_channels INT UNSIGNED NOT NULL,
+     discovered_groups INT UNSIGNED NOT NULL,
+     excluded_channels INT UNSIGNED NOT NULL,
+     excluded_groups INT UNSIGNED NOT NULL,
+     details LONGTEXT NOT NULL,
+     created_at DATETIME,
+     provider_id BIGINT UNSIGNED,
+     FOREIGN KEY (provider_id) REFERENCES provider(id)
+);
@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS channel_override (
+     id BIGINT UNSIGNED PRIMARY KEY NOT NULL AUTO_INCREMENT,
+     tvg_id TEXT,
+     track_id TEXT,
+    `name` TEXT,
+     group_title TEXT,
+     chno TEXT,
+     logo TEXT,
+     created_at DATETIME,
+     modified_at DATETIME
+);
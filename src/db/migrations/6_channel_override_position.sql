@@ -0,0 +1 @@
+ALTER TABLE channel_override ADD COLUMN position INT UNSIGNED;
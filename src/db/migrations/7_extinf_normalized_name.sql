@@ -0,0 +1 @@
+ALTER TABLE extinf ADD COLUMN normalized_name TEXT;
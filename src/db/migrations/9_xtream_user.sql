@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS xtream_user (
+     id BIGINT UNSIGNED PRIMARY KEY NOT NULL AUTO_INCREMENT,
+     username TEXT NOT NULL,
+     password TEXT NOT NULL,
+     enabled TINYINT NOT NULL DEFAULT 1,
+     exp_date DATETIME,
+     max_connections INT UNSIGNED,
+     created_at DATETIME,
+     modified_at DATETIME
+);
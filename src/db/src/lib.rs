@@ -2,9 +2,11 @@ pub mod models;
 pub mod services;
 use log::LevelFilter;
 use models::{
-    Attribute, ExtInf, Group, HlsUrl, M3u, Provider, XmltvUrl, XtreamMetadata, XtreamUrl,
+    ApiKey, Attribute, ChannelEquivalent, ChannelNumber, ChannelOverride, Directive, ExtInf, Group,
+    HeaderAttribute, M3u, ParseReport, Provider, XmltvUrl, XtreamMetadata, XtreamUrl, XtreamUser,
+    XtreamUserGroup,
 };
-use sqlx::mysql::{MySqlConnectOptions, MySqlPoolOptions};
+use sqlx::mysql::{MySqlConnectOptions, MySqlDatabaseError, MySqlPoolOptions};
 use sqlx::{migrate, ConnectOptions, Error, MySql, MySqlConnection, Pool};
 use std::fmt::Debug;
 use std::str::FromStr;
@@ -14,6 +16,8 @@ use std::time::Duration;
 pub type ConnectionPool = Pool<MySql>;
 pub type Connection = MySqlConnection;
 
+const ER_DUP_ENTRY: u16 = 1062;
+
 pub async fn connect(database_url: String) -> ConnectionPool {
     let connection_options = MySqlConnectOptions::from_str(&database_url)
         .expect("creating connection options")
@@ -27,6 +31,13 @@ pub async fn connect(database_url: String) -> ConnectionPool {
         .expect("creating database connection")
 }
 
+/// Whether a query failed on a unique key, e.g. a username that is already taken.
+pub fn is_duplicate_entry(err: &Error) -> bool {
+    err.as_database_error()
+        .and_then(|err| err.try_downcast_ref::<MySqlDatabaseError>())
+        .is_some_and(|err| err.number() == ER_DUP_ENTRY)
+}
+
 pub async fn handle_migrations(pool: &ConnectionPool) {
     migrate!("./migrations")
         .run(pool)
@@ -46,14 +57,22 @@ pub struct DB {
     pub pool: Arc<ConnectionPool>,
 
     pub provider: Provider,
+    pub parse_report: ParseReport,
     pub m3u: M3u,
+    pub header_attribute: HeaderAttribute,
     pub extinf: ExtInf,
     pub attribute: Attribute,
+    pub directive: Directive,
     pub group: Group,
     pub xtream_url: XtreamUrl,
     pub xtream_metadata: XtreamMetadata,
-    pub hls_url: HlsUrl,
     pub xmltv_url: XmltvUrl,
+    pub channel_override: ChannelOverride,
+    pub channel_equivalent: ChannelEquivalent,
+    pub channel_number: ChannelNumber,
+    pub xtream_user: XtreamUser,
+    pub xtream_user_group: XtreamUserGroup,
+    pub api_key: ApiKey,
 }
 
 pub async fn init_db(pool: ConnectionPool) -> DB {
@@ -61,13 +80,21 @@ pub async fn init_db(pool: ConnectionPool) -> DB {
         pool: Arc::new(pool.clone()),
 
         provider: Provider {},
+        parse_report: ParseReport {},
         attribute: Attribute {},
+        directive: Directive {},
         m3u: M3u {},
+        header_attribute: HeaderAttribute {},
         extinf: ExtInf {},
         group: Group {},
         xtream_url: XtreamUrl {},
         xtream_metadata: XtreamMetadata {},
-        hls_url: HlsUrl {},
         xmltv_url: XmltvUrl {},
+        channel_override: ChannelOverride {},
+        channel_equivalent: ChannelEquivalent {},
+        channel_number: ChannelNumber {},
+        xtream_user: XtreamUser {},
+        xtream_user_group: XtreamUserGroup {},
+        api_key: ApiKey {},
     }
 }
@@ -0,0 +1,89 @@
+use chrono::{NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{query_as, Error, FromRow};
+
+use crate::{Connection, CRUD};
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ApiKeyRequest {
+    pub name: String,
+    pub key_hash: String,
+    pub scope: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ApiKeyModel {
+    pub id: u64,
+    pub name: String,
+    #[serde(skip_serializing)]
+    pub key_hash: String,
+    pub scope: String,
+    pub created_at: Option<NaiveDateTime>,
+    modified_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ApiKey {}
+
+impl ApiKey {
+    pub async fn get_all(&self, tx: &mut Connection) -> Result<Vec<ApiKeyModel>, Error> {
+        let res = query_as!(ApiKeyModel, "select * from api_key order by id")
+            .fetch_all(tx)
+            .await;
+
+        res
+    }
+
+    pub async fn get_by_hash(
+        &self,
+        tx: &mut Connection,
+        key_hash: &str,
+    ) -> Result<Option<ApiKeyModel>, Error> {
+        let res = query_as!(
+            ApiKeyModel,
+            "select * from api_key where key_hash = ?",
+            key_hash
+        )
+        .fetch_optional(tx)
+        .await;
+
+        res
+    }
+}
+
+#[async_trait::async_trait]
+impl CRUD<ApiKeyModel, ApiKeyRequest> for ApiKey {
+    async fn get(&self, tx: &mut Connection, id: u64) -> Result<ApiKeyModel, Error> {
+        let res = query_as!(ApiKeyModel, "select * from api_key where id = ?", id)
+            .fetch_one(tx)
+            .await;
+
+        res
+    }
+
+    async fn insert(&self, tx: &mut Connection, api_key: ApiKeyRequest) -> Result<u64, Error> {
+        let res = query_as!(
+            ApiKeyModel,
+            r#"insert into api_key (name, key_hash, scope, created_at, modified_at) values (?, ?, ?, ?, ?)"#,
+            api_key.name,
+            api_key.key_hash,
+            api_key.scope,
+            Utc::now(),
+            Utc::now(),
+        )
+        .execute(tx)
+        .await?
+        .last_insert_id();
+
+        Ok(res)
+    }
+
+    async fn delete(&self, tx: &mut Connection, id: u64) -> Result<u64, Error> {
+        let res = query_as!(u64, r#"delete from api_key where id = ?"#, id)
+            .execute(tx)
+            .await?
+            .rows_affected();
+
+        Ok(res)
+    }
+}
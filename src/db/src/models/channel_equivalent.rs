@@ -0,0 +1,49 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{query_as, Error, FromRow};
+
+use crate::Connection;
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ChannelEquivalentModel {
+    pub id: u64,
+    pub url: String,
+    pub source: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct ChannelEquivalent {}
+
+impl ChannelEquivalent {
+    pub async fn get_by_extinf_id(
+        &self,
+        tx: &mut Connection,
+        extinf_id: u64,
+    ) -> Result<Vec<ChannelEquivalentModel>, Error> {
+        let res = query_as!(
+            ChannelEquivalentModel,
+            r#"select extinf.id, extinf.url, provider.source
+            from extinf
+            inner join m3u on m3u.id = extinf.m3u_id
+            inner join provider on provider.id = m3u.provider_id
+            where extinf.m3u_id != (select m3u_id from extinf where id = ?)
+            and (extinf.exclude is null or extinf.exclude = 0)
+            and provider.id in (select max(id) from provider group by source)
+            and (
+                extinf.normalized_name = (select normalized_name from extinf where id = ? and normalized_name != '')
+                or extinf.id in (
+                    select attribute.extinf_id from attribute
+                    where attribute.`key` = 'tvg-id' and attribute.`value` in (
+                        select `value` from attribute where extinf_id = ? and `key` = 'tvg-id' and `value` != ''
+                    )
+                )
+            )"#,
+            extinf_id,
+            extinf_id,
+            extinf_id
+        )
+        .fetch_all(tx)
+        .await?;
+
+        Ok(res)
+    }
+}
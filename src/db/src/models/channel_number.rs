@@ -0,0 +1,62 @@
+use chrono::{NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{query_as, Error, FromRow};
+
+use crate::Connection;
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ChannelNumberRequest {
+    pub tvg_id: String,
+    pub track_id: String,
+    pub chno: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ChannelNumberModel {
+    pub id: u64,
+    pub tvg_id: String,
+    pub track_id: String,
+    pub chno: u32,
+    created_at: Option<NaiveDateTime>,
+    modified_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ChannelNumber {}
+
+impl ChannelNumber {
+    pub async fn get_all(&self, tx: &mut Connection) -> Result<Vec<ChannelNumberModel>, Error> {
+        let res = query_as!(
+            ChannelNumberModel,
+            "select * from channel_number order by id"
+        )
+        .fetch_all(tx)
+        .await;
+
+        res
+    }
+
+    /// Stores the number of a channel, replacing the number it had before.
+    pub async fn upsert(
+        &self,
+        tx: &mut Connection,
+        channel_number: ChannelNumberRequest,
+    ) -> Result<u64, Error> {
+        let res = query_as!(
+            ChannelNumberModel,
+            r#"insert into channel_number (tvg_id, track_id, chno, created_at, modified_at) values (?, ?, ?, ?, ?) on duplicate key update chno = ?, modified_at = ?"#,
+            channel_number.tvg_id,
+            channel_number.track_id,
+            channel_number.chno,
+            Utc::now(),
+            Utc::now(),
+            channel_number.chno,
+            Utc::now(),
+        )
+        .execute(tx)
+        .await?
+        .rows_affected();
+
+        Ok(res)
+    }
+}
@@ -0,0 +1,95 @@
+use chrono::{NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{query_as, Error, FromRow};
+
+use crate::{Connection, CRUD};
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ChannelOverrideRequest {
+    pub tvg_id: Option<String>,
+    pub track_id: Option<String>,
+    pub name: Option<String>,
+    pub group_title: Option<String>,
+    pub chno: Option<String>,
+    pub logo: Option<String>,
+    pub position: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ChannelOverrideModel {
+    pub id: u64,
+    pub tvg_id: Option<String>,
+    pub track_id: Option<String>,
+    pub name: Option<String>,
+    pub group_title: Option<String>,
+    pub chno: Option<String>,
+    pub logo: Option<String>,
+    pub position: Option<u32>,
+    created_at: Option<NaiveDateTime>,
+    modified_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ChannelOverride {}
+
+impl ChannelOverride {
+    pub async fn get_all(&self, tx: &mut Connection) -> Result<Vec<ChannelOverrideModel>, Error> {
+        let res = query_as!(
+            ChannelOverrideModel,
+            "select * from channel_override order by id"
+        )
+        .fetch_all(tx)
+        .await;
+
+        res
+    }
+}
+
+#[async_trait::async_trait]
+impl CRUD<ChannelOverrideModel, ChannelOverrideRequest> for ChannelOverride {
+    async fn get(&self, tx: &mut Connection, id: u64) -> Result<ChannelOverrideModel, Error> {
+        let res = query_as!(
+            ChannelOverrideModel,
+            "select * from channel_override where id = ?",
+            id
+        )
+        .fetch_one(tx)
+        .await;
+
+        res
+    }
+
+    async fn insert(
+        &self,
+        tx: &mut Connection,
+        channel_override: ChannelOverrideRequest,
+    ) -> Result<u64, Error> {
+        let res = query_as!(
+            ChannelOverrideModel,
+            r#"insert into channel_override (tvg_id, track_id, `name`, group_title, chno, logo, position, created_at, modified_at) values (?, ?, ?, ?, ?, ?, ?, ?, ?)"#,
+            channel_override.tvg_id,
+            channel_override.track_id,
+            channel_override.name,
+            channel_override.group_title,
+            channel_override.chno,
+            channel_override.logo,
+            channel_override.position,
+            Utc::now(),
+            Utc::now(),
+        )
+        .execute(tx)
+        .await?
+        .last_insert_id();
+
+        Ok(res)
+    }
+
+    async fn delete(&self, tx: &mut Connection, id: u64) -> Result<u64, Error> {
+        let res = query_as!(u64, r#"delete from channel_override where id = ?"#, id)
+            .execute(tx)
+            .await?
+            .rows_affected();
+
+        Ok(res)
+    }
+}
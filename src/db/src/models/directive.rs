@@ -0,0 +1,96 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{Error, FromRow};
+
+use crate::{Connection, CRUD};
+
+#[derive(Debug, Clone)]
+pub struct DirectiveRequest {
+    pub tag: String,
+    pub value: String,
+    pub extinf_id: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct DirectiveModel {
+    pub id: u64,
+    pub tag: String,
+    pub value: String,
+
+    #[serde(skip)]
+    pub extinf_id: Option<u64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Directive {}
+
+impl Directive {
+    pub async fn get_all_by_extinf_id(
+        &self,
+        tx: &mut Connection,
+        extinf_id: u64,
+    ) -> Result<Vec<DirectiveModel>, Error> {
+        let res = sqlx::query_as!(
+            DirectiveModel,
+            "select * from directive where extinf_id = ? order by id",
+            extinf_id
+        )
+        .fetch_all(tx)
+        .await;
+
+        res
+    }
+
+    pub async fn delete_by_provider_id(
+        &self,
+        tx: &mut Connection,
+        provider_id: u64,
+    ) -> Result<u64, Error> {
+        let res = sqlx::query_as!(
+            u64,
+            "delete directive from directive
+            join extinf on directive.extinf_id = extinf.id
+            where m3u_id in (select id from `m3u` where provider_id = ?)",
+            provider_id
+        )
+        .execute(tx)
+        .await?
+        .rows_affected();
+
+        Ok(res)
+    }
+}
+
+#[async_trait::async_trait]
+impl CRUD<DirectiveModel, DirectiveRequest> for Directive {
+    async fn get(&self, tx: &mut Connection, id: u64) -> Result<DirectiveModel, Error> {
+        let res = sqlx::query_as!(DirectiveModel, "select * from directive where id = ?", id)
+            .fetch_one(tx)
+            .await;
+
+        res
+    }
+
+    async fn insert(&self, tx: &mut Connection, directive: DirectiveRequest) -> Result<u64, Error> {
+        let res = sqlx::query_as!(
+            DirectiveModel,
+            r#"insert into directive (`tag`, `value`, extinf_id) values (?, ?, ?)"#,
+            directive.tag,
+            directive.value,
+            directive.extinf_id
+        )
+        .execute(tx)
+        .await?
+        .last_insert_id();
+
+        Ok(res)
+    }
+
+    async fn delete(&self, tx: &mut Connection, id: u64) -> Result<u64, Error> {
+        let res = sqlx::query_as!(u64, r#"delete from directive where id = ?"#, id)
+            .execute(tx)
+            .await?
+            .rows_affected();
+
+        Ok(res)
+    }
+}
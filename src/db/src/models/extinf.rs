@@ -7,6 +7,8 @@ use crate::{Connection, CRUD};
 #[derive(Debug, Clone)]
 pub struct ExtInfRequest {
     pub name: String,
+    pub normalized_name: Option<String>,
+    pub duration: Option<String>,
     pub url: String,
     pub track_id: Option<String>,
     pub prefix: Option<String>,
@@ -20,6 +22,7 @@ pub struct ExtInfRequest {
 pub struct ExtInfModel {
     pub id: u64,
     pub name: String,
+    pub duration: Option<String>,
     pub url: String,
     pub track_id: Option<String>,
     pub prefix: Option<String>,
@@ -41,7 +44,7 @@ impl ExtInf {
     ) -> Result<Vec<ExtInfModel>, Error> {
         let res = query_as!(
             ExtInfModel,
-            "select id, name, url, track_id, prefix, extension, exclude as `exclude: bool`, m3u_id from extinf where m3u_id = ?",
+            "select id, name, duration, url, track_id, prefix, extension, exclude as `exclude: bool`, m3u_id from extinf where m3u_id = ?",
             m3u_id
         )
         .fetch_all(tx)
@@ -76,7 +79,7 @@ impl ExtInf {
     ) -> Result<Vec<ExtInfModel>, Error> {
         let res = query_as!(
             ExtInfModel,
-            r#"select id, name, url, track_id, prefix, extension, exclude as `exclude: bool`, m3u_id
+            r#"select id, name, duration, url, track_id, prefix, extension, exclude as `exclude: bool`, m3u_id
             from extinf 
             where exclude = 1 and prefix = ? and m3u_id = ?"#,
             prefix,
@@ -96,7 +99,7 @@ impl ExtInf {
     ) -> Result<ExtInfModel, Error> {
         let res = query_as!(
             ExtInfModel,
-            "select id, name, url, track_id, prefix, extension, exclude as `exclude: bool`, m3u_id from extinf where m3u_id = ? and track_id = ?",
+            "select id, name, duration, url, track_id, prefix, extension, exclude as `exclude: bool`, m3u_id from extinf where m3u_id = ? and track_id = ?",
             m3u_id, 
             track_id
         )
@@ -110,7 +113,7 @@ impl ExtInf {
 #[async_trait]
 impl CRUD<ExtInfModel, ExtInfRequest> for ExtInf {
     async fn get(&self, tx: &mut Connection, id: u64) -> Result<ExtInfModel, Error> {
-        let res = sqlx::query_as!(ExtInfModel, "select id, name, url, track_id, prefix, extension, exclude as `exclude: bool`, m3u_id from extinf where id = ?", id)
+        let res = sqlx::query_as!(ExtInfModel, "select id, name, duration, url, track_id, prefix, extension, exclude as `exclude: bool`, m3u_id from extinf where id = ?", id)
             .fetch_one(tx)
             .await;
 
@@ -120,8 +123,10 @@ impl CRUD<ExtInfModel, ExtInfRequest> for ExtInf {
     async fn insert(&self, tx: &mut Connection, extinf: ExtInfRequest) -> Result<u64, Error> {
         let res = sqlx::query_as!(
             ExtInfModel,
-            r#"insert into extinf (name, url, prefix, track_id, extension, exclude, m3u_id) values (?, ?, ?, ?, ?, ?, ?)"#,
+            r#"insert into extinf (name, normalized_name, duration, url, prefix, track_id, extension, exclude, m3u_id) values (?, ?, ?, ?, ?, ?, ?, ?, ?)"#,
             extinf.name,
+            extinf.normalized_name,
+            extinf.duration,
             extinf.url,
             extinf.prefix, 
             extinf.track_id, 
@@ -0,0 +1,103 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{Error, FromRow};
+
+use crate::{Connection, CRUD};
+
+#[derive(Debug, Clone)]
+pub struct HeaderAttributeRequest {
+    pub key: String,
+    pub value: String,
+    pub m3u_id: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct HeaderAttributeModel {
+    pub id: u64,
+    pub key: String,
+    pub value: String,
+
+    #[serde(skip)]
+    pub m3u_id: Option<u64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct HeaderAttribute {}
+
+impl HeaderAttribute {
+    pub async fn get_all_by_m3u_id(
+        &self,
+        tx: &mut Connection,
+        m3u_id: u64,
+    ) -> Result<Vec<HeaderAttributeModel>, Error> {
+        let res = sqlx::query_as!(
+            HeaderAttributeModel,
+            "select * from header_attribute where m3u_id = ? order by id",
+            m3u_id
+        )
+        .fetch_all(tx)
+        .await;
+
+        res
+    }
+
+    pub async fn delete_by_provider_id(
+        &self,
+        tx: &mut Connection,
+        provider_id: u64,
+    ) -> Result<u64, Error> {
+        let res = sqlx::query_as!(
+            u64,
+            "delete header_attribute from header_attribute
+            where m3u_id in (select id from `m3u` where provider_id = ?)",
+            provider_id
+        )
+        .execute(tx)
+        .await?
+        .rows_affected();
+
+        Ok(res)
+    }
+}
+
+#[async_trait::async_trait]
+impl CRUD<HeaderAttributeModel, HeaderAttributeRequest> for HeaderAttribute {
+    async fn get(&self, tx: &mut Connection, id: u64) -> Result<HeaderAttributeModel, Error> {
+        let res = sqlx::query_as!(
+            HeaderAttributeModel,
+            "select * from header_attribute where id = ?",
+            id
+        )
+        .fetch_one(tx)
+        .await;
+
+        res
+    }
+
+    async fn insert(
+        &self,
+        tx: &mut Connection,
+        attribute: HeaderAttributeRequest,
+    ) -> Result<u64, Error> {
+        let res = sqlx::query_as!(
+            HeaderAttributeModel,
+            r#"insert into header_attribute (`key`, `value`, m3u_id) values (?, ?, ?)"#,
+            attribute.key,
+            attribute.value,
+            attribute.m3u_id
+        )
+        .execute(tx)
+        .await?
+        .last_insert_id();
+
+        Ok(res)
+    }
+
+    async fn delete(&self, tx: &mut Connection, id: u64) -> Result<u64, Error> {
+        let res = sqlx::query_as!(u64, r#"delete from header_attribute where id = ?"#, id)
+            .execute(tx)
+            .await?
+            .rows_affected();
+
+        Ok(res)
+    }
+}
@@ -1,19 +1,35 @@
+mod api_key;
 mod attribute;
+mod channel_equivalent;
+mod channel_number;
+mod channel_override;
+mod directive;
 mod extinf;
 mod group;
-mod hls_url;
+mod header_attribute;
 mod m3u;
+mod parse_report;
 mod provider;
 mod xmltv_url;
 mod xtream_metadata;
 mod xtream_url;
+mod xtream_user;
+mod xtream_user_group;
 
+pub use self::api_key::*;
 pub use self::attribute::*;
+pub use self::channel_equivalent::*;
+pub use self::channel_number::*;
+pub use self::channel_override::*;
+pub use self::directive::*;
 pub use self::extinf::*;
 pub use self::group::*;
-pub use self::hls_url::*;
+pub use self::header_attribute::*;
 pub use self::m3u::*;
+pub use self::parse_report::*;
 pub use self::provider::*;
 pub use self::xmltv_url::*;
 pub use self::xtream_metadata::*;
 pub use self::xtream_url::*;
+pub use self::xtream_user::*;
+pub use self::xtream_user_group::*;
@@ -0,0 +1,119 @@
+use chrono::{NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{query_as, Error, FromRow};
+
+use crate::{Connection, CRUD};
+
+#[derive(Debug, Clone, Default)]
+pub struct ParseReportRequest {
+    pub total_lines: u32,
+    pub invalid_lines: u32,
+    pub invalid_extinf_entries: u32,
+    pub duplicate_channels: u32,
+    pub discovered_groups: u32,
+    pub excluded_channels: u32,
+    pub excluded_groups: u32,
+    pub details: String,
+    pub provider_id: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ParseReportModel {
+    pub id: u64,
+    pub total_lines: u32,
+    pub invalid_lines: u32,
+    pub invalid_extinf_entries: u32,
+    pub duplicate_channels: u32,
+    pub discovered_groups: u32,
+    pub excluded_channels: u32,
+    pub excluded_groups: u32,
+    pub details: String,
+    pub created_at: Option<NaiveDateTime>,
+
+    #[serde(skip)]
+    pub provider_id: Option<u64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ParseReport {}
+
+impl ParseReport {
+    pub async fn get_by_provider_id(
+        &self,
+        tx: &mut Connection,
+        provider_id: u64,
+    ) -> Result<ParseReportModel, Error> {
+        let res = query_as!(
+            ParseReportModel,
+            "select * from parse_report where provider_id = ? order by id desc limit 1",
+            provider_id
+        )
+        .fetch_one(tx)
+        .await;
+
+        res
+    }
+
+    pub async fn delete_by_provider_id(
+        &self,
+        tx: &mut Connection,
+        provider_id: u64,
+    ) -> Result<u64, Error> {
+        let res = query_as!(
+            u64,
+            "delete parse_report from parse_report where provider_id = ?",
+            provider_id
+        )
+        .execute(tx)
+        .await?
+        .rows_affected();
+
+        Ok(res)
+    }
+}
+
+#[async_trait::async_trait]
+impl CRUD<ParseReportModel, ParseReportRequest> for ParseReport {
+    async fn get(&self, tx: &mut Connection, id: u64) -> Result<ParseReportModel, Error> {
+        let res = query_as!(
+            ParseReportModel,
+            "select * from parse_report where id = ?",
+            id
+        )
+        .fetch_one(tx)
+        .await;
+
+        res
+    }
+
+    async fn insert(&self, tx: &mut Connection, report: ParseReportRequest) -> Result<u64, Error> {
+        let res = query_as!(
+            ParseReportModel,
+            r#"insert into parse_report (total_lines, invalid_lines, invalid_extinf_entries, duplicate_channels, discovered_groups, excluded_channels, excluded_groups, details, created_at, provider_id) values (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"#,
+            report.total_lines,
+            report.invalid_lines,
+            report.invalid_extinf_entries,
+            report.duplicate_channels,
+            report.discovered_groups,
+            report.excluded_channels,
+            report.excluded_groups,
+            report.details,
+            Utc::now(),
+            report.provider_id,
+        )
+        .execute(tx)
+        .await?
+        .last_insert_id();
+
+        Ok(res)
+    }
+
+    async fn delete(&self, tx: &mut Connection, id: u64) -> Result<u64, Error> {
+        let res = query_as!(u64, r#"delete from parse_report where id = ?"#, id)
+            .execute(tx)
+            .await?
+            .rows_affected();
+
+        Ok(res)
+    }
+}
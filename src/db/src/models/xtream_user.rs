@@ -0,0 +1,135 @@
+use chrono::{NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{query_as, Error, FromRow};
+
+use crate::{Connection, CRUD};
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct XtreamUserRequest {
+    pub username: String,
+    pub password: String,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    pub exp_date: Option<NaiveDateTime>,
+    pub max_connections: Option<u32>,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct XtreamUserModel {
+    pub id: u64,
+    pub username: String,
+    #[serde(skip_serializing)]
+    pub password: String,
+    pub enabled: bool,
+    pub exp_date: Option<NaiveDateTime>,
+    pub max_connections: Option<u32>,
+    pub created_at: Option<NaiveDateTime>,
+    modified_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Clone)]
+pub struct XtreamUser {}
+
+impl XtreamUser {
+    pub async fn get_all(&self, tx: &mut Connection) -> Result<Vec<XtreamUserModel>, Error> {
+        let res = query_as!(
+            XtreamUserModel,
+            "select id, username, password, enabled as `enabled: bool`, exp_date, max_connections, created_at, modified_at from xtream_user order by id"
+        )
+        .fetch_all(tx)
+        .await;
+
+        res
+    }
+
+    pub async fn get_by_username(
+        &self,
+        tx: &mut Connection,
+        username: &str,
+    ) -> Result<Option<XtreamUserModel>, Error> {
+        let res = query_as!(
+            XtreamUserModel,
+            "select id, username, password, enabled as `enabled: bool`, exp_date, max_connections, created_at, modified_at from xtream_user where username = ?",
+            username
+        )
+        .fetch_optional(tx)
+        .await;
+
+        res
+    }
+
+    pub async fn update(
+        &self,
+        tx: &mut Connection,
+        id: u64,
+        xtream_user: XtreamUserRequest,
+    ) -> Result<u64, Error> {
+        let res = query_as!(
+            XtreamUserModel,
+            r#"update xtream_user set username = ?, password = ?, enabled = ?, exp_date = ?, max_connections = ?, modified_at = ? where id = ?"#,
+            xtream_user.username,
+            xtream_user.password,
+            xtream_user.enabled,
+            xtream_user.exp_date,
+            xtream_user.max_connections,
+            Utc::now(),
+            id,
+        )
+        .execute(tx)
+        .await?
+        .rows_affected();
+
+        Ok(res)
+    }
+}
+
+#[async_trait::async_trait]
+impl CRUD<XtreamUserModel, XtreamUserRequest> for XtreamUser {
+    async fn get(&self, tx: &mut Connection, id: u64) -> Result<XtreamUserModel, Error> {
+        let res = query_as!(
+            XtreamUserModel,
+            "select id, username, password, enabled as `enabled: bool`, exp_date, max_connections, created_at, modified_at from xtream_user where id = ?",
+            id
+        )
+        .fetch_one(tx)
+        .await;
+
+        res
+    }
+
+    async fn insert(
+        &self,
+        tx: &mut Connection,
+        xtream_user: XtreamUserRequest,
+    ) -> Result<u64, Error> {
+        let res = query_as!(
+            XtreamUserModel,
+            r#"insert into xtream_user (username, password, enabled, exp_date, max_connections, created_at, modified_at) values (?, ?, ?, ?, ?, ?, ?)"#,
+            xtream_user.username,
+            xtream_user.password,
+            xtream_user.enabled,
+            xtream_user.exp_date,
+            xtream_user.max_connections,
+            Utc::now(),
+            Utc::now(),
+        )
+        .execute(tx)
+        .await?
+        .last_insert_id();
+
+        Ok(res)
+    }
+
+    async fn delete(&self, tx: &mut Connection, id: u64) -> Result<u64, Error> {
+        let res = query_as!(u64, r#"delete from xtream_user where id = ?"#, id)
+            .execute(tx)
+            .await?
+            .rows_affected();
+
+        Ok(res)
+    }
+}
@@ -0,0 +1,98 @@
+use chrono::{NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{query_as, Error, FromRow};
+
+use crate::{Connection, CRUD};
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct XtreamUserGroupRequest {
+    pub xtream_user_id: u64,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct XtreamUserGroupModel {
+    pub id: u64,
+    pub xtream_user_id: u64,
+    pub name: String,
+    created_at: Option<NaiveDateTime>,
+    modified_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Clone)]
+pub struct XtreamUserGroup {}
+
+impl XtreamUserGroup {
+    pub async fn get_all(
+        &self,
+        tx: &mut Connection,
+        xtream_user_id: u64,
+    ) -> Result<Vec<XtreamUserGroupModel>, Error> {
+        let res = query_as!(
+            XtreamUserGroupModel,
+            "select * from xtream_user_group where xtream_user_id = ? order by id",
+            xtream_user_id
+        )
+        .fetch_all(tx)
+        .await;
+
+        res
+    }
+
+    pub async fn delete_all(&self, tx: &mut Connection, xtream_user_id: u64) -> Result<u64, Error> {
+        let res = query_as!(
+            u64,
+            r#"delete from xtream_user_group where xtream_user_id = ?"#,
+            xtream_user_id
+        )
+        .execute(tx)
+        .await?
+        .rows_affected();
+
+        Ok(res)
+    }
+}
+
+#[async_trait::async_trait]
+impl CRUD<XtreamUserGroupModel, XtreamUserGroupRequest> for XtreamUserGroup {
+    async fn get(&self, tx: &mut Connection, id: u64) -> Result<XtreamUserGroupModel, Error> {
+        let res = query_as!(
+            XtreamUserGroupModel,
+            "select * from xtream_user_group where id = ?",
+            id
+        )
+        .fetch_one(tx)
+        .await;
+
+        res
+    }
+
+    async fn insert(
+        &self,
+        tx: &mut Connection,
+        xtream_user_group: XtreamUserGroupRequest,
+    ) -> Result<u64, Error> {
+        let res = query_as!(
+            XtreamUserGroupModel,
+            r#"insert into xtream_user_group (xtream_user_id, `name`, created_at, modified_at) values (?, ?, ?, ?)"#,
+            xtream_user_group.xtream_user_id,
+            xtream_user_group.name,
+            Utc::now(),
+            Utc::now(),
+        )
+        .execute(tx)
+        .await?
+        .last_insert_id();
+
+        Ok(res)
+    }
+
+    async fn delete(&self, tx: &mut Connection, id: u64) -> Result<u64, Error> {
+        let res = query_as!(u64, r#"delete from xtream_user_group where id = ?"#, id)
+            .execute(tx)
+            .await?
+            .rows_affected();
+
+        Ok(res)
+    }
+}
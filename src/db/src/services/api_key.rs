@@ -0,0 +1,79 @@
+use std::sync::Arc;
+
+use anyhow::{bail, ensure, Error};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    models::{ApiKeyModel, ApiKeyRequest},
+    CRUD, DB,
+};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ApiKeyDBService {
+    #[serde(skip)]
+    db: Option<Arc<DB>>,
+}
+
+impl ApiKeyDBService {
+    pub fn new() -> Self {
+        ApiKeyDBService { db: None }
+    }
+
+    pub fn initialize_db(&mut self, db: Arc<DB>) {
+        self.db = Some(db);
+    }
+
+    pub async fn get_keys(&self) -> Result<Vec<ApiKeyModel>, Error> {
+        if let Some(ref db) = self.db {
+            let mut tx = db.pool.begin().await?;
+
+            let keys = db.api_key.get_all(&mut tx).await?;
+
+            Ok(keys)
+        } else {
+            bail!("DB has not yet been initialized")
+        }
+    }
+
+    pub async fn get_by_hash(&self, key_hash: &str) -> Result<Option<ApiKeyModel>, Error> {
+        if let Some(ref db) = self.db {
+            let mut tx = db.pool.begin().await?;
+
+            let key = db.api_key.get_by_hash(&mut tx, key_hash).await?;
+
+            Ok(key)
+        } else {
+            bail!("DB has not yet been initialized")
+        }
+    }
+
+    pub async fn create_key(&self, req: ApiKeyRequest) -> Result<u64, Error> {
+        ensure!(!req.name.is_empty(), "Api key needs a name");
+
+        if let Some(ref db) = self.db {
+            let mut tx = db.pool.begin().await?;
+
+            let id = db.api_key.insert(&mut tx, req).await?;
+
+            tx.commit().await?;
+
+            Ok(id)
+        } else {
+            bail!("DB has not yet been initialized")
+        }
+    }
+
+    pub async fn delete_key(&self, id: u64) -> Result<u64, Error> {
+        if let Some(ref db) = self.db {
+            let mut tx = db.pool.begin().await?;
+
+            let aff_rows = db.api_key.delete(&mut tx, id).await?;
+
+            tx.commit().await?;
+
+            Ok(aff_rows)
+        } else {
+            bail!("DB has not yet been initialized")
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use std::sync::Arc;
+
+use anyhow::{bail, Error};
+use serde::{Deserialize, Serialize};
+
+use crate::{models::ChannelEquivalentModel, DB};
+
+const QUALITY_TAGS: [&str; 12] = [
+    "sd", "hd", "fhd", "uhd", "4k", "8k", "hevc", "h264", "h265", "720p", "1080p", "2160p",
+];
+
+/// Length of the indexed `extinf.normalized_name` column.
+const NORMALIZED_NAME_LENGTH: usize = 255;
+
+/// Country and language prefixes providers put in front of channel names, as in "UK: BBC One".
+/// Other prefixes are part of the name, as in "ESPN: Live".
+const NAME_PREFIXES: [&str; 48] = [
+    "uk", "us", "usa", "ca", "au", "nz", "ie", "in", "za", "de", "at", "ch", "fr", "be", "nl",
+    "lu", "it", "es", "pt", "br", "mx", "ar", "latam", "pl", "ro", "hu", "cz", "sk", "bg", "gr",
+    "tr", "se", "no", "dk", "fi", "ru", "ua", "hr", "rs", "ba", "al", "exyu", "en", "eng", "ger",
+    "fra", "ita", "spa",
+];
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChannelEquivalenceDBService {
+    #[serde(skip)]
+    db: Option<Arc<DB>>,
+}
+
+impl ChannelEquivalenceDBService {
+    pub fn new() -> Self {
+        ChannelEquivalenceDBService { db: None }
+    }
+
+    pub fn initialize_db(&mut self, db: Arc<DB>) {
+        self.db = Some(db);
+    }
+
+    pub async fn get_equivalents(
+        &self,
+        extinf_id: u64,
+    ) -> Result<Vec<ChannelEquivalentModel>, Error> {
+        if let Some(ref db) = self.db {
+            let mut tx = db.pool.begin().await?;
+
+            let equivalents = db
+                .channel_equivalent
+                .get_by_extinf_id(&mut tx, extinf_id)
+                .await?;
+
+            Ok(equivalents)
+        } else {
+            bail!("DB has not yet been initialized")
+        }
+    }
+}
+
+pub fn normalize_channel_name(name: &str) -> String {
+    let mut name = name.to_lowercase();
+
+    for (open, close) in [('(', ')'), ('[', ']'), ('{', '}')] {
+        while let (Some(start), Some(end)) = (name.find(open), name.find(close)) {
+            if end < start {
+                break;
+            }
+
+            name.replace_range(start..=end, " ");
+        }
+    }
+
+    if let Some(offset) = name.find([':', '|']) {
+        let prefix = name[..offset].replace(|c: char| !c.is_alphanumeric(), "");
+
+        if NAME_PREFIXES.contains(&prefix.as_str()) {
+            name.replace_range(..=offset, " ");
+        }
+    }
+
+    name.split(|c: char| !c.is_alphanumeric())
+        .filter(|token| !token.is_empty() && !QUALITY_TAGS.contains(token))
+        .collect::<Vec<&str>>()
+        .join(" ")
+        .chars()
+        .take(NORMALIZED_NAME_LENGTH)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_quality_tags_and_punctuation() {
+        assert_eq!(normalize_channel_name("BBC One HD"), "bbc one");
+        assert_eq!(normalize_channel_name("BBC.One.FHD"), "bbc one");
+        assert_eq!(
+            normalize_channel_name("Discovery-Channel 1080p HEVC"),
+            "discovery channel"
+        );
+    }
+
+    #[test]
+    fn strips_bracketed_suffixes() {
+        assert_eq!(normalize_channel_name("CNN (Backup)"), "cnn");
+        assert_eq!(normalize_channel_name("CNN [US] {720p}"), "cnn");
+        assert_eq!(normalize_channel_name("CNN (US) (Backup)"), "cnn");
+    }
+
+    #[test]
+    fn strips_country_prefixes() {
+        assert_eq!(normalize_channel_name("UK: BBC One"), "bbc one");
+        assert_eq!(normalize_channel_name("UK | BBC One HD"), "bbc one");
+        assert_eq!(normalize_channel_name("EX-YU: RTS 1"), "rts 1");
+        assert_eq!(
+            normalize_channel_name("Sky Sports: News"),
+            "sky sports news"
+        );
+    }
+
+    #[test]
+    fn keeps_prefixes_that_are_part_of_the_name() {
+        assert_eq!(normalize_channel_name("ESPN: Live"), "espn live");
+        assert_eq!(normalize_channel_name("BBC: News"), "bbc news");
+        assert_ne!(
+            normalize_channel_name("ESPN: Live"),
+            normalize_channel_name("FOX: Live")
+        );
+    }
+
+    #[test]
+    fn keeps_unbalanced_brackets() {
+        assert_eq!(normalize_channel_name("Rai 1 (Italia"), "rai 1 italia");
+        assert_eq!(normalize_channel_name("Rai 1 )Italia("), "rai 1 italia");
+    }
+
+    #[test]
+    fn matches_equivalent_names_across_providers() {
+        let names = [
+            "UK: BBC One HD",
+            "BBC ONE (FHD)",
+            "[UK] BBC One 4K",
+            "bbc-one",
+        ];
+
+        for name in names {
+            assert_eq!(normalize_channel_name(name), "bbc one", "{}", name);
+        }
+
+        assert_ne!(
+            normalize_channel_name("BBC One"),
+            normalize_channel_name("BBC Two")
+        );
+    }
+}
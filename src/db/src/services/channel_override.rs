@@ -0,0 +1,88 @@
+use std::sync::Arc;
+
+use anyhow::{bail, ensure, Error};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    models::{ChannelOverrideModel, ChannelOverrideRequest},
+    CRUD, DB,
+};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChannelOverrideDBService {
+    #[serde(skip)]
+    db: Option<Arc<DB>>,
+}
+
+impl ChannelOverrideDBService {
+    pub fn new() -> Self {
+        ChannelOverrideDBService { db: None }
+    }
+
+    pub fn initialize_db(&mut self, db: Arc<DB>) {
+        self.db = Some(db);
+    }
+
+    pub async fn get_overrides(&self) -> Result<Vec<ChannelOverrideModel>, Error> {
+        if let Some(ref db) = self.db {
+            let mut tx = db.pool.begin().await?;
+
+            let overrides = db.channel_override.get_all(&mut tx).await?;
+
+            Ok(overrides)
+        } else {
+            bail!("DB has not yet been initialized")
+        }
+    }
+
+    pub async fn create_override(&self, req: ChannelOverrideRequest) -> Result<u64, Error> {
+        ensure!(
+            req.tvg_id.is_some() || req.track_id.is_some(),
+            "Channel override needs a tvg_id or track_id"
+        );
+
+        if let Some(ref db) = self.db {
+            let mut tx = db.pool.begin().await?;
+
+            let id = db.channel_override.insert(&mut tx, req).await?;
+
+            tx.commit().await?;
+
+            Ok(id)
+        } else {
+            bail!("DB has not yet been initialized")
+        }
+    }
+
+    pub async fn delete_override(&self, id: u64) -> Result<u64, Error> {
+        if let Some(ref db) = self.db {
+            let mut tx = db.pool.begin().await?;
+
+            let aff_rows = db.channel_override.delete(&mut tx, id).await?;
+
+            tx.commit().await?;
+
+            Ok(aff_rows)
+        } else {
+            bail!("DB has not yet been initialized")
+        }
+    }
+}
+
+pub fn find_override<'a>(
+    overrides: &'a [ChannelOverrideModel],
+    tvg_id: Option<&str>,
+    track_id: Option<&str>,
+) -> Option<&'a ChannelOverrideModel> {
+    let tvg_id = tvg_id.filter(|tvg_id| !tvg_id.is_empty());
+    let track_id = track_id.filter(|track_id| !track_id.is_empty());
+
+    overrides
+        .iter()
+        .find(|channel_override| tvg_id.is_some() && channel_override.tvg_id.as_deref() == tvg_id)
+        .or_else(|| {
+            overrides.iter().find(|channel_override| {
+                track_id.is_some() && channel_override.track_id.as_deref() == track_id
+            })
+        })
+}
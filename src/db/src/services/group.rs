@@ -5,7 +5,7 @@ use serde::{Deserialize, Serialize};
 
 use crate::{models::GroupModel, DB};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct GroupDBService {
     #[serde(skip)]
     db: Option<Arc<DB>>,
@@ -1,2 +1,6 @@
+pub mod api_key;
+pub mod channel_equivalence;
+pub mod channel_override;
 pub mod group;
 pub mod provider;
+pub mod xtream_user;
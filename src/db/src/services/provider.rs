@@ -7,20 +7,27 @@ use url::Url;
 
 use crate::{
     models::{
-        AttributeModel, AttributeRequest, ExtInfModel, ExtInfRequest, GroupRequest, M3uModel,
-        M3uRequest, ProviderModel, ProviderRequest,
+        AttributeModel, AttributeRequest, ChannelNumberModel, ChannelNumberRequest,
+        ChannelOverrideModel, DirectiveModel, DirectiveRequest, ExtInfModel, ExtInfRequest,
+        GroupRequest, HeaderAttributeModel, HeaderAttributeRequest, M3uModel, M3uRequest,
+        ParseReportModel, ParseReportRequest, ProviderModel, ProviderRequest,
     },
     CRUD, DB,
 };
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+use super::channel_equivalence::normalize_channel_name;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ProviderDBService {
     #[serde(skip)]
     db: Option<Arc<DB>>,
 
     pub provider: Option<ProviderModel>,
     pub m3u: Option<M3uModel>,
+    pub header_attributes: Option<Vec<HeaderAttributeModel>>,
     pub extinfs: Option<Vec<ExtInfApiModel>>,
+    pub overrides: Option<Vec<ChannelOverrideModel>>,
+    pub channel_numbers: Option<Vec<ChannelNumberModel>>,
 }
 
 pub struct CreateProviderRequest {
@@ -28,19 +35,23 @@ pub struct CreateProviderRequest {
     pub m3u: M3U,
     pub channel_count: u32,
     pub groups: Vec<GroupRequest>,
+    pub report: ParseReportRequest,
 }
 
 #[derive(Debug)]
 pub struct M3U {
     pub domain: String,
     pub port: Option<u16>,
+    pub header_attributes: Vec<(String, String)>,
     pub extinfs: Vec<ExtInf>,
 }
 
 #[derive(Debug, Clone)]
 pub struct ExtInf {
     pub name: String,
+    pub duration: String,
     pub attributes: HashMap<String, String>,
+    pub directives: Vec<ExtInfDirective>,
     pub url: Url,
     pub track_id: Option<String>,
     pub prefix: Option<String>,
@@ -49,14 +60,22 @@ pub struct ExtInf {
     pub exclude: bool,
 }
 
+#[derive(Debug, Clone)]
+pub struct ExtInfDirective {
+    pub tag: String,
+    pub value: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExtInfApiModel {
     pub id: u64,
     pub name: String,
+    pub duration: Option<String>,
     pub url: String,
     pub exclude: bool,
     pub m3u_id: Option<u64>,
     pub attributes: Option<Vec<AttributeModel>>,
+    pub directives: Option<Vec<DirectiveModel>>,
     pub track_id: String,
     pub prefix: Option<String>,
     pub extension: Option<String>,
@@ -68,7 +87,10 @@ impl ProviderDBService {
             db: None,
             provider: None,
             m3u: None,
+            header_attributes: None,
             extinfs: None,
+            overrides: None,
+            channel_numbers: None,
         }
     }
 
@@ -92,6 +114,12 @@ impl ProviderDBService {
                 .await
                 .context("Could not get provider with id")?;
 
+            let header_attributes = db
+                .header_attribute
+                .get_all_by_m3u_id(&mut tx, id)
+                .await
+                .context("Could not get header attributes for provider")?;
+
             let extinfs = db
                 .extinf
                 .get_all_by_m3u(&mut tx, id)
@@ -102,25 +130,43 @@ impl ProviderDBService {
 
             for extinf in extinfs {
                 let attr = db.attribute.get_all_by_extinf_id(&mut tx, extinf.id).await;
+                let directives = db.directive.get_all_by_extinf_id(&mut tx, extinf.id).await;
 
                 extinf_models.push(ExtInfApiModel {
                     id: extinf.id,
                     name: extinf.name,
+                    duration: extinf.duration,
                     url: extinf.url,
                     exclude: extinf.exclude.unwrap_or_default(),
                     m3u_id: extinf.m3u_id,
                     attributes: Some(attr.unwrap()),
+                    directives: Some(directives.unwrap_or_default()),
                     track_id: extinf.track_id.unwrap_or_default(),
                     prefix: extinf.prefix,
                     extension: extinf.extension,
                 });
             }
 
+            let overrides = db
+                .channel_override
+                .get_all(&mut tx)
+                .await
+                .context("Could not get channel overrides")?;
+
+            let channel_numbers = db
+                .channel_number
+                .get_all(&mut tx)
+                .await
+                .context("Could not get channel numbers")?;
+
             tx.commit().await.context("Could not close transaction")?;
 
             self.provider = Some(provider);
             self.m3u = Some(m3u);
+            self.header_attributes = Some(header_attributes);
             self.extinfs = Some(extinf_models);
+            self.overrides = Some(overrides);
+            self.channel_numbers = Some(channel_numbers);
 
             Ok(self)
         } else {
@@ -134,13 +180,21 @@ impl ProviderDBService {
 
             let deleted_xtream_urls = db.xtream_url.delete_by_m3u_id(&mut tx, id).await;
             let deleted_xtream_metadata = db.xtream_metadata.delete_by_m3u_id(&mut tx, id).await;
+            let deleted_directives = db.directive.delete_by_provider_id(&mut tx, id).await;
             let deleted_attributes = db.attribute.delete_by_provider_id(&mut tx, id).await;
             let deleted_extinfs = db.extinf.delete_by_provider_id(&mut tx, id).await;
+            let deleted_header_attributes =
+                db.header_attribute.delete_by_provider_id(&mut tx, id).await;
             let deleted_m3us = db.m3u.delete_by_provider_id(&mut tx, id).await;
             let deleted_groups = db.group.delete_by_provider_id(&mut tx, id).await;
+            let deleted_reports = db.parse_report.delete_by_provider_id(&mut tx, id).await;
             let deleted_provider = db.provider.delete(&mut tx, id).await;
 
-            match deleted_attributes
+            match deleted_directives
+                .and_then(|aff_rows| {
+                    info!("Deleting {} directives", aff_rows);
+                    deleted_attributes
+                })
                 .and_then(|aff_rows| {
                     info!("Deleting {} xtream_url entries", aff_rows);
                     deleted_xtream_urls
@@ -155,6 +209,10 @@ impl ProviderDBService {
                 })
                 .and_then(|aff_rows| {
                     info!("Deleting {} extinf entries", aff_rows);
+                    deleted_header_attributes
+                })
+                .and_then(|aff_rows| {
+                    info!("Deleting {} header attributes", aff_rows);
                     deleted_m3us
                 })
                 .and_then(|aff_rows| {
@@ -163,6 +221,10 @@ impl ProviderDBService {
                 })
                 .and_then(|aff_rows| {
                     info!("Deleting {} m3u entries", aff_rows);
+                    deleted_reports
+                })
+                .and_then(|aff_rows| {
+                    info!("Deleting {} parse reports", aff_rows);
                     deleted_provider
                 }) {
                 Err(err) => {
@@ -197,13 +259,28 @@ impl ProviderDBService {
                 )
                 .await?;
 
+            for attr in req.m3u.header_attributes {
+                db.header_attribute
+                    .insert(
+                        &mut tx,
+                        HeaderAttributeRequest {
+                            key: attr.0,
+                            value: attr.1,
+                            m3u_id,
+                        },
+                    )
+                    .await?;
+            }
+
             for extinf in req.m3u.extinfs {
                 let extinf_id = db
                     .extinf
                     .insert(
                         &mut tx,
                         ExtInfRequest {
+                            normalized_name: Some(normalize_channel_name(&extinf.name)),
                             name: extinf.name,
+                            duration: Some(extinf.duration),
                             url: extinf.url.to_string(),
                             track_id: extinf.track_id,
                             prefix: extinf.prefix,
@@ -226,13 +303,30 @@ impl ProviderDBService {
                         )
                         .await?;
                 }
+
+                for directive in extinf.directives {
+                    db.directive
+                        .insert(
+                            &mut tx,
+                            DirectiveRequest {
+                                tag: directive.tag,
+                                value: directive.value,
+                                extinf_id,
+                            },
+                        )
+                        .await?;
+                }
             }
 
-            for mut group in req.groups.to_owned() {
+            for mut group in req.groups.iter().cloned() {
                 group.m3u_id = Some(m3u_id);
                 db.group.insert(&mut tx, group).await?;
             }
 
+            let mut report = req.report;
+            report.provider_id = Some(provider_id);
+            db.parse_report.insert(&mut tx, report).await?;
+
             tx.commit().await?;
 
             let groups = req.groups.into_iter();
@@ -250,6 +344,46 @@ impl ProviderDBService {
         }
     }
 
+    pub async fn save_channel_numbers(
+        &self,
+        channel_numbers: Vec<ChannelNumberRequest>,
+    ) -> Result<(), Error> {
+        if let Some(ref db) = self.db {
+            let mut tx = db.pool.begin().await?;
+
+            for channel_number in channel_numbers {
+                db.channel_number
+                    .upsert(&mut tx, channel_number)
+                    .await
+                    .context("Could not save channel number")?;
+            }
+
+            tx.commit().await?;
+
+            Ok(())
+        } else {
+            bail!("DB has not yet been initialized")
+        }
+    }
+
+    pub async fn get_report(&self, provider_id: u64) -> Result<ParseReportModel, Error> {
+        if let Some(ref db) = self.db {
+            let mut tx = db.pool.begin().await?;
+
+            let report = db
+                .parse_report
+                .get_by_provider_id(&mut tx, provider_id)
+                .await
+                .context("Could not get parse report for provider")?;
+
+            tx.commit().await?;
+
+            Ok(report)
+        } else {
+            bail!("DB has not yet been initialized")
+        }
+    }
+
     pub async fn get_provider_entries_by_url(
         &self,
         url: &str,
@@ -294,11 +428,23 @@ impl ProviderDBService {
     ) -> Result<Vec<String>, Error> {
         let mut tx = db.pool.begin().await?;
 
-        let excluded_extinfs_ids = db
+        let mut excluded_extinfs = db
             .extinf
             .get_exclude_eligible_by_m3u_id(&mut tx, m3u_id, prefix.to_string())
             .await
-            .context(format!("Unable to get ext entry with ID: {}", m3u_id))?
+            .context(format!("Unable to get ext entry with ID: {}", m3u_id))?;
+
+        if prefix == "live" {
+            let excluded_unprefixed_extinfs = db
+                .extinf
+                .get_exclude_eligible_by_m3u_id(&mut tx, m3u_id, String::new())
+                .await
+                .context(format!("Unable to get ext entry with ID: {}", m3u_id))?;
+
+            excluded_extinfs.extend(excluded_unprefixed_extinfs);
+        }
+
+        let excluded_extinfs_ids = excluded_extinfs
             .into_iter()
             .map(|extinf: ExtInfModel| extinf.track_id.unwrap_or_default())
             .collect::<Vec<String>>();
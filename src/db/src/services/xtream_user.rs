@@ -0,0 +1,218 @@
+use std::sync::Arc;
+
+use anyhow::{anyhow, bail, ensure, Error};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    is_duplicate_entry,
+    models::{XtreamUserGroupRequest, XtreamUserModel, XtreamUserRequest},
+    CRUD, DB,
+};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct XtreamUserDBService {
+    #[serde(skip)]
+    db: Option<Arc<DB>>,
+}
+
+impl XtreamUserDBService {
+    pub fn new() -> Self {
+        XtreamUserDBService { db: None }
+    }
+
+    pub fn initialize_db(&mut self, db: Arc<DB>) {
+        self.db = Some(db);
+    }
+
+    pub async fn get_users(&self) -> Result<Vec<XtreamUserModel>, Error> {
+        if let Some(ref db) = self.db {
+            let mut tx = db.pool.begin().await?;
+
+            let users = db.xtream_user.get_all(&mut tx).await?;
+
+            Ok(users)
+        } else {
+            bail!("DB has not yet been initialized")
+        }
+    }
+
+    /// Returns the user with the given credentials if it is enabled and has not expired.
+    pub async fn authenticate(
+        &self,
+        username: &str,
+        password: &str,
+    ) -> Result<Option<XtreamUserModel>, Error> {
+        if let Some(ref db) = self.db {
+            let mut tx = db.pool.begin().await?;
+
+            let user = db
+                .xtream_user
+                .get_by_username(&mut tx, username)
+                .await?
+                .filter(|user| user.password == password && is_active(user));
+
+            Ok(user)
+        } else {
+            bail!("DB has not yet been initialized")
+        }
+    }
+
+    /// Returns the user with the given id if it still exists, is enabled and has not expired.
+    pub async fn get_active_user(&self, id: u64) -> Result<Option<XtreamUserModel>, Error> {
+        if let Some(ref db) = self.db {
+            let mut tx = db.pool.begin().await?;
+
+            let user = match db.xtream_user.get(&mut tx, id).await {
+                Ok(user) => Some(user).filter(is_active),
+                Err(sqlx::Error::RowNotFound) => None,
+                Err(err) => return Err(err.into()),
+            };
+
+            Ok(user)
+        } else {
+            bail!("DB has not yet been initialized")
+        }
+    }
+
+    pub async fn create_user(&self, req: XtreamUserRequest) -> Result<u64, Error> {
+        ensure!(
+            !req.username.is_empty() && !req.password.is_empty(),
+            "Xtream user needs a username and password"
+        );
+
+        if let Some(ref db) = self.db {
+            let mut tx = db.pool.begin().await?;
+
+            ensure!(
+                db.xtream_user
+                    .get_by_username(&mut tx, &req.username)
+                    .await?
+                    .is_none(),
+                "Xtream user {} already exists",
+                req.username
+            );
+
+            let username = req.username.clone();
+            let id = db
+                .xtream_user
+                .insert(&mut tx, req)
+                .await
+                .map_err(|err| map_duplicate_username(err, &username))?;
+
+            tx.commit().await?;
+
+            Ok(id)
+        } else {
+            bail!("DB has not yet been initialized")
+        }
+    }
+
+    pub async fn update_user(&self, id: u64, req: XtreamUserRequest) -> Result<u64, Error> {
+        ensure!(
+            !req.username.is_empty() && !req.password.is_empty(),
+            "Xtream user needs a username and password"
+        );
+
+        if let Some(ref db) = self.db {
+            let mut tx = db.pool.begin().await?;
+
+            ensure!(
+                db.xtream_user
+                    .get_by_username(&mut tx, &req.username)
+                    .await?
+                    .is_none_or(|user| user.id == id),
+                "Xtream user {} already exists",
+                req.username
+            );
+
+            let username = req.username.clone();
+            let aff_rows = db
+                .xtream_user
+                .update(&mut tx, id, req)
+                .await
+                .map_err(|err| map_duplicate_username(err, &username))?;
+
+            tx.commit().await?;
+
+            Ok(aff_rows)
+        } else {
+            bail!("DB has not yet been initialized")
+        }
+    }
+
+    /// Returns the groups a user is entitled to. An empty list grants access to all groups.
+    pub async fn get_groups(&self, id: u64) -> Result<Vec<String>, Error> {
+        if let Some(ref db) = self.db {
+            let mut tx = db.pool.begin().await?;
+
+            let groups = db
+                .xtream_user_group
+                .get_all(&mut tx, id)
+                .await?
+                .into_iter()
+                .map(|group| group.name)
+                .collect();
+
+            Ok(groups)
+        } else {
+            bail!("DB has not yet been initialized")
+        }
+    }
+
+    pub async fn set_groups(&self, id: u64, groups: Vec<String>) -> Result<(), Error> {
+        if let Some(ref db) = self.db {
+            let mut tx = db.pool.begin().await?;
+
+            db.xtream_user.get(&mut tx, id).await?;
+            db.xtream_user_group.delete_all(&mut tx, id).await?;
+
+            for name in groups.into_iter().filter(|name| !name.is_empty()) {
+                db.xtream_user_group
+                    .insert(
+                        &mut tx,
+                        XtreamUserGroupRequest {
+                            xtream_user_id: id,
+                            name,
+                        },
+                    )
+                    .await?;
+            }
+
+            tx.commit().await?;
+
+            Ok(())
+        } else {
+            bail!("DB has not yet been initialized")
+        }
+    }
+
+    pub async fn delete_user(&self, id: u64) -> Result<u64, Error> {
+        if let Some(ref db) = self.db {
+            let mut tx = db.pool.begin().await?;
+
+            let aff_rows = db.xtream_user.delete(&mut tx, id).await?;
+
+            tx.commit().await?;
+
+            Ok(aff_rows)
+        } else {
+            bail!("DB has not yet been initialized")
+        }
+    }
+}
+
+fn is_active(user: &XtreamUserModel) -> bool {
+    user.enabled
+        && user
+            .exp_date
+            .is_none_or(|exp_date| exp_date > Utc::now().naive_utc())
+}
+
+/// Reports a username taken by a concurrent create or update like the check before the query.
+fn map_duplicate_username(err: sqlx::Error, username: &str) -> Error {
+    match is_duplicate_entry(&err) {
+        true => anyhow!("Xtream user {} already exists", username),
+        false => err.into(),
+    }
+}
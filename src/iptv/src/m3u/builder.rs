@@ -1,7 +1,9 @@
 use anyhow::{bail, Context, Error};
 use chrono::Utc;
+use db::models::HeaderAttributeModel;
 use db::services::provider::{ExtInfApiModel, ProviderDBService};
 use log::{error, info, trace};
+use std::cmp::Reverse;
 use std::fmt::Write;
 use tokio::fs::File;
 use tokio::io::{AsyncWriteExt, BufWriter};
@@ -9,15 +11,20 @@ use tokio::task::JoinHandle;
 use tokio::{spawn, try_join};
 use url::Url;
 
-use crate::models::{IptvConfiguration, M3uType};
+use crate::models::{IptvConfiguration, M3uType, ProviderConfig};
+
+use super::{
+    ordering::{number_channels, sort_channels, Channel},
+    tools::namespace_stream_id,
+};
 
 pub async fn create_m3u_file(
-    service: ProviderDBService,
+    providers: Vec<(ProviderConfig, ProviderDBService)>,
     iptv_config: IptvConfiguration,
 ) -> Result<(), Error> {
-    let custom_handle = compose_custom_m3u(service.clone(), iptv_config.clone());
-    let ts_handle = compose_ts_m3u(service.clone(), iptv_config.clone());
-    let m3u8_handle = compose_m3u8_m3u(service.clone(), iptv_config.clone());
+    let custom_handle = compose_custom_m3u(providers.clone(), iptv_config.clone());
+    let ts_handle = compose_ts_m3u(providers.clone(), iptv_config.clone());
+    let m3u8_handle = compose_m3u8_m3u(providers.clone(), iptv_config.clone());
 
     match try_join!(custom_handle, ts_handle, m3u8_handle) {
         Ok(_) => (),
@@ -30,20 +37,14 @@ pub async fn create_m3u_file(
 }
 
 async fn compose_custom_m3u(
-    provider_db_service: ProviderDBService,
+    providers: Vec<(ProviderConfig, ProviderDBService)>,
     iptv_config: IptvConfiguration,
 ) -> Result<JoinHandle<()>, Error> {
     let custom_path = build_file_path(M3uType::Custom);
 
     let custom_handle = spawn(async move {
-        if let Err(err) = compose_m3u(
-            provider_db_service,
-            &custom_path,
-            iptv_config,
-            M3uType::Custom,
-            false,
-        )
-        .await
+        if let Err(err) =
+            compose_m3u(providers, &custom_path, iptv_config, M3uType::Custom, false).await
         {
             error!("{}", err)
         }
@@ -53,20 +54,14 @@ async fn compose_custom_m3u(
 }
 
 async fn compose_ts_m3u(
-    provider_db_service: ProviderDBService,
+    providers: Vec<(ProviderConfig, ProviderDBService)>,
     iptv_config: IptvConfiguration,
 ) -> Result<JoinHandle<()>, Error> {
     let custom_path = build_file_path(M3uType::Ts);
 
     let custom_handle = spawn(async move {
-        if let Err(err) = compose_m3u(
-            provider_db_service,
-            &custom_path,
-            iptv_config,
-            M3uType::Ts,
-            false,
-        )
-        .await
+        if let Err(err) =
+            compose_m3u(providers, &custom_path, iptv_config, M3uType::Ts, false).await
         {
             error!("{}", err)
         }
@@ -76,20 +71,14 @@ async fn compose_ts_m3u(
 }
 
 async fn compose_m3u8_m3u(
-    provider_db_service: ProviderDBService,
+    providers: Vec<(ProviderConfig, ProviderDBService)>,
     iptv_config: IptvConfiguration,
 ) -> Result<JoinHandle<()>, Error> {
     let custom_path = build_file_path(M3uType::M3u8);
 
     let custom_handle = spawn(async move {
-        if let Err(err) = compose_m3u(
-            provider_db_service,
-            &custom_path,
-            iptv_config,
-            M3uType::M3u8,
-            true,
-        )
-        .await
+        if let Err(err) =
+            compose_m3u(providers, &custom_path, iptv_config, M3uType::M3u8, true).await
         {
             error!("{}", err)
         }
@@ -99,7 +88,7 @@ async fn compose_m3u8_m3u(
 }
 
 async fn compose_m3u(
-    provider_service: ProviderDBService,
+    mut providers: Vec<(ProviderConfig, ProviderDBService)>,
     path: &String,
     iptv_config: IptvConfiguration,
     m3u_type: M3uType,
@@ -108,14 +97,41 @@ async fn compose_m3u(
     let file = create_file(path).await?;
     let mut writer = BufWriter::new(file);
 
+    providers.sort_by_key(|(provider, _)| Reverse(provider.priority));
+
+    let mut header_attributes: Vec<HeaderAttributeModel> = vec![];
+
+    for (_, service) in providers.iter() {
+        for attr in service.header_attributes.clone().unwrap_or_default() {
+            if !header_attributes
+                .iter()
+                .any(|header_attribute| header_attribute.key == attr.key)
+            {
+                header_attributes.push(attr);
+            }
+        }
+    }
+
+    let header_line = compose_header_line(header_attributes, iptv_config.clone())?;
+
     writer
-        .write("#EXTM3U\n".as_bytes())
+        .write(header_line.as_bytes())
         .await
         .context("writing #EXTM3U line to file")?;
 
-    if let Some(extinfs) = provider_service.extinfs {
-        let total_extinf_entries_length = extinfs.len();
-        let mut extinf_excludes = 0;
+    let extinfs: Vec<Vec<ExtInfApiModel>> = providers
+        .iter_mut()
+        .map(|(_, service)| service.extinfs.take().unwrap_or_default())
+        .collect();
+
+    let mut total_extinf_entries_length = 0;
+    let mut extinf_excludes = 0;
+    let mut channels = vec![];
+
+    for ((provider, service), extinfs) in providers.iter().zip(extinfs) {
+        let overrides = service.overrides.as_deref().unwrap_or_default();
+
+        total_extinf_entries_length += extinfs.len();
 
         for extinf in extinfs {
             if extinf.exclude {
@@ -126,28 +142,55 @@ async fn compose_m3u(
                 continue;
             }
 
-            if let Ok(line) = compose_extinf_lines(extinf, iptv_config.clone(), m3u_type) {
-                writer
-                    .write(line.as_bytes())
-                    .await
-                    .context("writing extinf line to file")?;
-            }
+            channels.push(Channel::new(extinf, overrides, provider));
         }
+    }
 
-        writer.flush().await.context("Flushing output stream")?;
+    sort_channels(&mut channels, &iptv_config);
 
-        let valid_extinf_entries = total_extinf_entries_length - extinf_excludes;
+    let stored_numbers = providers
+        .first()
+        .and_then(|(_, service)| service.channel_numbers.as_deref())
+        .unwrap_or_default();
 
-        if log {
-            info!("Excluded {} channels based on group", extinf_excludes);
-            info!("Total extinf entries is {}", total_extinf_entries_length);
-            info!(
-                "Wrote {} extinf entries to multiple .m3u files (ts, m3u8, custom)",
-                valid_extinf_entries
-            )
+    let (chnos, new_numbers) = number_channels(&channels, stored_numbers, &iptv_config);
+
+    // The playlists are written concurrently and numbered alike, only the logged one stores
+    // the new numbers
+    if log && !new_numbers.is_empty() {
+        if let Some((_, service)) = providers.first() {
+            if let Err(err) = service.save_channel_numbers(new_numbers).await {
+                error!("Could not store channel numbers: {:#}", err);
+            }
+        }
+    }
+
+    for (channel, chno) in channels.into_iter().zip(chnos) {
+        if let Ok(line) = compose_extinf_lines(channel, chno, iptv_config.clone(), m3u_type) {
+            writer
+                .write(line.as_bytes())
+                .await
+                .context("writing extinf line to file")?;
         }
     }
 
+    writer.flush().await.context("Flushing output stream")?;
+
+    let valid_extinf_entries = total_extinf_entries_length - extinf_excludes;
+
+    if log {
+        info!("Excluded {} channels based on group", extinf_excludes);
+        info!(
+            "Total extinf entries of {} providers is {}",
+            providers.len(),
+            total_extinf_entries_length
+        );
+        info!(
+            "Wrote {} extinf entries to multiple .m3u files (ts, m3u8, custom)",
+            valid_extinf_entries
+        )
+    }
+
     Ok(())
 }
 
@@ -170,21 +213,79 @@ async fn create_file(path: &String) -> Result<File, anyhow::Error> {
     Ok(file)
 }
 
+fn compose_header_line(
+    header_attributes: Vec<HeaderAttributeModel>,
+    iptv_config: IptvConfiguration,
+) -> Result<String, Error> {
+    let mut line = String::new();
+
+    write!(line, "#EXTM3U").context("writing #EXTM3U line")?;
+
+    for attr in header_attributes {
+        let attr_value = match attr.key.as_str() {
+            "url-tvg" | "x-tvg-url" => format!("http://{}/epg", iptv_config.proxy_domain),
+            _ => attr.value,
+        };
+
+        write!(line, " {}=\"{}\"", attr.key, attr_value).context(format!(
+            "writing header attribute with key {} and value {}",
+            attr.key, attr_value
+        ))?;
+    }
+
+    writeln!(line).context("writing #EXTM3U line")?;
+
+    Ok(line)
+}
+
 fn compose_extinf_lines(
-    extinf: ExtInfApiModel,
+    channel: Channel,
+    chno: Option<String>,
     iptv_config: IptvConfiguration,
     m3u_type: M3uType,
 ) -> Result<String, Error> {
     let mut line = String::new();
 
-    write!(line, "#EXTINF:-1").context("writing #EXTINF:-1 line")?;
+    let name = channel.name().to_string();
+    let group_title = Some(channel.group_title()).filter(|group_title| !group_title.is_empty());
+    let Channel {
+        extinf,
+        channel_override,
+        provider,
+    } = channel;
+
+    let duration = extinf
+        .duration
+        .clone()
+        .filter(|duration| !duration.is_empty())
+        .unwrap_or(String::from("-1"));
 
-    if let None = extinf.attributes {
+    write!(line, "#EXTINF:{}", duration).context("writing #EXTINF line")?;
+
+    if extinf.attributes.is_none() {
         bail!("No attributes..")
     }
 
+    let mut override_attributes: Vec<(&str, String)> = vec![
+        ("group-title", group_title),
+        ("tvg-chno", chno),
+        (
+            "tvg-logo",
+            channel_override.and_then(|channel_override| channel_override.logo.clone()),
+        ),
+    ]
+    .into_iter()
+    .filter_map(|(key, value)| value.map(|value| (key, value)))
+    .collect();
+
     for attr in extinf.attributes.unwrap_or_default() {
-        let attr_value = try_parse_url_from_attr(attr.value, attr.id, iptv_config.clone());
+        let attr_value = match override_attributes
+            .iter()
+            .position(|(key, _)| *key == attr.key)
+        {
+            Some(index) => override_attributes.remove(index).1,
+            None => try_parse_url_from_attr(attr.value, attr.id, iptv_config.clone()),
+        };
 
         write!(line, " {}=\"{}\"", attr.key, attr_value).context(format!(
             "writing attribute with key {} and value {} for extinf channel {}",
@@ -192,6 +293,28 @@ fn compose_extinf_lines(
         ))?;
     }
 
+    for (key, value) in override_attributes {
+        write!(line, " {}=\"{}\"", key, value).context(format!(
+            "writing override attribute with key {} and value {} for extinf channel {}",
+            key, value, extinf.name
+        ))?;
+    }
+
+    writeln!(line, ",{}", name)
+        .context(format!("writing extinf line for channel {}", extinf.name))?;
+
+    for directive in extinf.directives.unwrap_or_default() {
+        if directive.value.is_empty() {
+            writeln!(line, "#{}", directive.tag)
+        } else {
+            writeln!(line, "#{}:{}", directive.tag, directive.value)
+        }
+        .context(format!(
+            "writing directive {} for extinf channel {}",
+            directive.tag, extinf.name
+        ))?;
+    }
+
     let proxified_url = match m3u_type {
         M3uType::Custom => proxify_url(
             extinf.id.to_string(),
@@ -202,7 +325,10 @@ fn compose_extinf_lines(
             Some(m3u_type),
         ),
         _ => proxify_url(
-            extinf.track_id,
+            namespace_stream_id(provider.slot, &extinf.track_id).map_err(|err| {
+                error!("Skipped channel {}: {}", name, err);
+                err
+            })?,
             extinf.prefix,
             extinf.extension,
             iptv_config,
@@ -211,8 +337,8 @@ fn compose_extinf_lines(
         ),
     }?;
 
-    write!(line, ",{}{}{}{}", extinf.name, "\n", proxified_url, "\n")
-        .context(format!("writing extinf lines for channel {}", extinf.name))?;
+    writeln!(line, "{}", proxified_url)
+        .context(format!("writing extinf url for channel {}", extinf.name))?;
 
     Ok(line)
 }
@@ -233,7 +359,7 @@ fn proxify_url(
         UrlType::Stream => {
             match m3u_type.unwrap_or_default() {
                 M3uType::Custom => {
-                    write!(url, "/{}/{}", "stream", id)?;
+                    write!(url, "/stream/{}", id)?;
                 }
                 M3uType::Ts => {
                     if let Some(prefix) = prefix {
@@ -257,7 +383,7 @@ fn proxify_url(
                 M3uType::M3u8 => {
                     if let Some(prefix) = prefix {
                         if prefix.is_empty() {
-                            write!(url, "/{}", "live")?;
+                            write!(url, "/live")?;
                         } else {
                             write!(url, "/{}", prefix)?;
                         }
@@ -272,13 +398,13 @@ fn proxify_url(
                     if let Some(extension) = extension {
                         write!(url, ".{}", extension)?;
                     } else {
-                        write!(url, ".{}", "m3u8")?;
+                        write!(url, ".m3u8")?;
                     }
                 }
             };
         }
         UrlType::Attribute => {
-            write!(url, "/{}/{}", "attr", id)?;
+            write!(url, "/attr/{}", id)?;
         }
     };
 
@@ -305,13 +431,11 @@ fn try_parse_url_from_attr(val: String, id: u64, iptv_config: IptvConfiguration)
     }
     .unwrap_or_default();
 
-    let attr_value = if url_parsed_attr.is_empty() {
+    if url_parsed_attr.is_empty() {
         val
     } else {
         url_parsed_attr
-    };
-
-    attr_value
+    }
 }
 
 #[derive(PartialEq)]
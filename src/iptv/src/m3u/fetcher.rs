@@ -1,8 +1,110 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::{anyhow, Context, Error};
+use futures_util::TryStreamExt;
 use rest_client::RestClient;
+use tokio::{
+    fs::File,
+    io::{AsyncBufRead, BufReader},
+};
+use tokio_util::io::StreamReader;
 use url::Url;
 
-pub async fn get_m3u(url: &Url) -> Result<String, anyhow::Error> {
-    let client = RestClient::new();
-    let res = client.get_string(url).await;
-    Ok(res)
+use crate::models::{ProviderConfig, ProviderConfigs};
+
+pub async fn get_m3u(
+    url: &Url,
+    client: Arc<RestClient>,
+) -> Result<Box<dyn AsyncBufRead + Unpin + Send>, Error> {
+    let reader: Box<dyn AsyncBufRead + Unpin + Send> = match url.scheme() {
+        "file" => Box::new(get_m3u_file(url).await?),
+        _ => Box::new(get_m3u_stream(url, client).await?),
+    };
+
+    Ok(reader)
+}
+
+async fn get_m3u_stream(
+    url: &Url,
+    client: Arc<RestClient>,
+) -> Result<impl AsyncBufRead + Unpin + Send, Error> {
+    let stream = client
+        .get_bytes_stream(url)
+        .await
+        .context("requesting M3U stream")?
+        .map_err(io::Error::other);
+
+    let reader = BufReader::new(StreamReader::new(Box::pin(stream)));
+
+    Ok(reader)
+}
+
+async fn get_m3u_file(url: &Url) -> Result<impl AsyncBufRead + Unpin + Send, Error> {
+    let path = url
+        .to_file_path()
+        .map_err(|_| anyhow!("{} is not a valid file path", url))?;
+
+    let file = File::open(&path)
+        .await
+        .context(format!("opening M3U file {}", path.display()))?;
+
+    Ok(BufReader::new(file))
+}
+
+pub fn parse_source(source: &str) -> Result<Url, Error> {
+    if let Ok(url) = Url::parse(source) {
+        return Ok(url);
+    }
+
+    let path = Path::new(source);
+
+    let path: PathBuf = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir()
+            .context("getting current directory")?
+            .join(path)
+    };
+
+    let url = Url::from_file_path(&path)
+        .map_err(|_| anyhow!("{} is neither a valid URL nor a file path", source))?;
+
+    Ok(url)
+}
+
+pub fn compose_source(url: &Url) -> String {
+    if url.scheme() != "file" {
+        return url.to_string();
+    }
+
+    match url.to_file_path() {
+        Ok(path) => path.to_string_lossy().to_string(),
+        Err(_) => url.to_string(),
+    }
+}
+
+pub fn load_provider_configs(path: &str) -> Result<Vec<ProviderConfig>, Error> {
+    let content =
+        std::fs::read_to_string(path).context(format!("reading providers file {}", path))?;
+
+    let provider_configs = serde_yaml::from_str::<ProviderConfigs>(&content)
+        .context(format!("deserializing providers file {}", path))?;
+
+    provider_configs
+        .providers
+        .into_iter()
+        .map(|mut provider| {
+            let source = parse_source(&provider.source)
+                .context(format!("parsing source of provider {}", provider.name))?;
+
+            provider.source = compose_source(&source);
+            provider.apply_deprecated_http_keys();
+
+            Ok(provider)
+        })
+        .collect()
 }
@@ -0,0 +1,281 @@
+use std::collections::HashMap;
+
+use anyhow::{Context, Error};
+use db::services::provider::ExtInf;
+use regex::Regex;
+
+use crate::models::{FilterAction, FilterCondition, FilterField, FilterMatcher, FilterRules};
+
+pub fn load_filter_rules(path: &str) -> Result<FilterRules, Error> {
+    let content =
+        std::fs::read_to_string(path).context(format!("reading filter rules file {}", path))?;
+
+    let rules = serde_yaml::from_str::<FilterRules>(&content)
+        .context(format!("deserializing filter rules file {}", path))?;
+
+    ChannelFilter::new(&rules).context("validating filter rules")?;
+
+    Ok(rules)
+}
+
+#[derive(Debug, Clone)]
+pub struct ChannelFilter {
+    rules: FilterRules,
+    regexes: HashMap<String, Regex>,
+}
+
+impl ChannelFilter {
+    pub fn new(rules: &FilterRules) -> Result<Self, Error> {
+        let mut regexes = HashMap::new();
+
+        for rule in &rules.rules {
+            compile_regexes(&rule.condition, &mut regexes)?;
+        }
+
+        Ok(ChannelFilter {
+            rules: rules.clone(),
+            regexes,
+        })
+    }
+
+    pub fn is_excluded(&self, extinf: &ExtInf) -> bool {
+        let mut has_include_rules = false;
+        let mut included = false;
+
+        for rule in &self.rules.rules {
+            match rule.action {
+                FilterAction::Exclude => {
+                    if self.matches(&rule.condition, extinf) {
+                        return true;
+                    }
+                }
+                FilterAction::Include => {
+                    has_include_rules = true;
+                    included = included || self.matches(&rule.condition, extinf);
+                }
+            }
+        }
+
+        has_include_rules && !included
+    }
+
+    fn matches(&self, condition: &FilterCondition, extinf: &ExtInf) -> bool {
+        match condition {
+            FilterCondition::All { all } => all.iter().all(|cond| self.matches(cond, extinf)),
+            FilterCondition::Any { any } => any.iter().any(|cond| self.matches(cond, extinf)),
+            FilterCondition::Not { not } => !self.matches(not, extinf),
+            FilterCondition::Field { field, matcher } => {
+                let value = get_field_value(*field, extinf);
+
+                match matcher {
+                    FilterMatcher::Equals(expected) => value.eq_ignore_ascii_case(expected),
+                    FilterMatcher::Contains(expected) => {
+                        value.to_lowercase().contains(&expected.to_lowercase())
+                    }
+                    FilterMatcher::StartsWith(expected) => {
+                        value.to_lowercase().starts_with(&expected.to_lowercase())
+                    }
+                    FilterMatcher::Regex(pattern) => self
+                        .regexes
+                        .get(pattern)
+                        .map(|regex| regex.is_match(&value))
+                        .unwrap_or_default(),
+                }
+            }
+        }
+    }
+}
+
+fn compile_regexes(
+    condition: &FilterCondition,
+    regexes: &mut HashMap<String, Regex>,
+) -> Result<(), Error> {
+    match condition {
+        FilterCondition::All { all: conditions } | FilterCondition::Any { any: conditions } => {
+            for condition in conditions {
+                compile_regexes(condition, regexes)?;
+            }
+        }
+        FilterCondition::Not { not } => compile_regexes(not, regexes)?,
+        FilterCondition::Field {
+            matcher: FilterMatcher::Regex(pattern),
+            ..
+        } => {
+            if !regexes.contains_key(pattern) {
+                let regex =
+                    Regex::new(pattern).context(format!("compiling filter regex {}", pattern))?;
+
+                regexes.insert(pattern.clone(), regex);
+            }
+        }
+        FilterCondition::Field { .. } => (),
+    }
+
+    Ok(())
+}
+
+fn get_field_value(field: FilterField, extinf: &ExtInf) -> String {
+    match field {
+        FilterField::Name => extinf.name.clone(),
+        FilterField::Group => extinf.group_title.clone(),
+        FilterField::TvgId => extinf.attributes.get("tvg-id").cloned().unwrap_or_default(),
+        FilterField::TvgCountry => extinf
+            .attributes
+            .get("tvg-country")
+            .cloned()
+            .unwrap_or_default(),
+        FilterField::UrlPrefix => match extinf.prefix.as_deref() {
+            Some("") | None => String::from("live"),
+            Some(prefix) => prefix.to_string(),
+        },
+        FilterField::Url => extinf.url.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use url::Url;
+
+    use super::*;
+
+    fn extinf(name: &str, group_title: &str, attributes: &[(&str, &str)], url: &str) -> ExtInf {
+        let url = Url::parse(url).unwrap();
+        let prefix = url
+            .path_segments()
+            .and_then(|mut segments| segments.next())
+            .filter(|segment| ["movie", "series"].contains(segment))
+            .map(String::from);
+
+        ExtInf {
+            name: name.to_string(),
+            duration: String::from("-1"),
+            attributes: attributes
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+            directives: vec![],
+            url,
+            track_id: None,
+            prefix,
+            extension: None,
+            group_title: group_title.to_string(),
+            exclude: false,
+        }
+    }
+
+    fn channel_filter(rules: &str) -> ChannelFilter {
+        ChannelFilter::new(&serde_yaml::from_str(rules).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn keeps_everything_without_rules() {
+        let filter = channel_filter("rules: []");
+
+        assert!(!filter.is_excluded(&extinf("One", "News", &[], "http://up/1")));
+    }
+
+    #[test]
+    fn excludes_channels_matching_any_exclude_rule() {
+        let filter = channel_filter(
+            r#"
+rules:
+  - action: exclude
+    match:
+      any:
+        - field: group
+          contains: ADULT
+        - field: name
+          regex: "(?i)\\b(xxx|test)\\b"
+"#,
+        );
+
+        assert!(filter.is_excluded(&extinf("One", "Adult Movies", &[], "http://up/1")));
+        assert!(filter.is_excluded(&extinf("Test Channel", "News", &[], "http://up/2")));
+        assert!(!filter.is_excluded(&extinf("Testing", "News", &[], "http://up/3")));
+    }
+
+    #[test]
+    fn requires_a_match_of_some_include_rule() {
+        let filter = channel_filter(
+            r#"
+rules:
+  - action: include
+    match:
+      all:
+        - field: tvg_country
+          equals: se
+        - not:
+            field: url_prefix
+            equals: series
+  - action: include
+    match:
+      field: tvg_id
+      starts_with: BBC
+"#,
+        );
+
+        let swedish = [("tvg-country", "SE")];
+
+        assert!(!filter.is_excluded(&extinf("SVT1", "SE", &swedish, "http://up/live/1")));
+        assert!(filter.is_excluded(&extinf("Show", "SE", &swedish, "http://up/series/2")));
+        assert!(!filter.is_excluded(&extinf(
+            "BBC One",
+            "UK",
+            &[("tvg-id", "bbc1.uk")],
+            "http://up/3"
+        )));
+        assert!(filter.is_excluded(&extinf("ITV", "UK", &[("tvg-id", "itv.uk")], "http://up/4")));
+    }
+
+    #[test]
+    fn exclude_rules_win_over_include_rules() {
+        let filter = channel_filter(
+            r#"
+rules:
+  - action: include
+    match:
+      field: group
+      equals: news
+  - action: exclude
+    match:
+      field: url
+      contains: backup
+"#,
+        );
+
+        assert!(!filter.is_excluded(&extinf("One", "News", &[], "http://up/1")));
+        assert!(filter.is_excluded(&extinf("One", "News", &[], "http://backup/1")));
+    }
+
+    #[test]
+    fn treats_missing_url_prefix_as_live() {
+        let filter = channel_filter(
+            r#"
+rules:
+  - action: include
+    match:
+      field: url_prefix
+      equals: live
+"#,
+        );
+
+        assert!(!filter.is_excluded(&extinf("One", "News", &[], "http://up/1")));
+        assert!(filter.is_excluded(&extinf("Film", "Movies", &[], "http://up/movie/1")));
+    }
+
+    #[test]
+    fn rejects_invalid_regex() {
+        let rules = serde_yaml::from_str::<FilterRules>(
+            r#"
+rules:
+  - action: exclude
+    match:
+      field: name
+      regex: "(unclosed"
+"#,
+        )
+        .unwrap();
+
+        assert!(ChannelFilter::new(&rules).is_err());
+    }
+}
@@ -1,4 +1,6 @@
 pub mod builder;
 pub mod fetcher;
+pub mod filter;
+pub mod ordering;
 pub mod parser;
 pub mod tools;
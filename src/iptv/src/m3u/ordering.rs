@@ -0,0 +1,501 @@
+use std::collections::{HashMap, HashSet};
+
+use db::{
+    models::{ChannelNumberModel, ChannelNumberRequest, ChannelOverrideModel},
+    services::{channel_override::find_override, provider::ExtInfApiModel},
+};
+
+use crate::models::{IptvConfiguration, M3uSort, ProviderConfig};
+
+pub struct Channel<'a> {
+    pub extinf: ExtInfApiModel,
+    pub channel_override: Option<&'a ChannelOverrideModel>,
+    pub provider: &'a ProviderConfig,
+}
+
+impl<'a> Channel<'a> {
+    pub fn new(
+        extinf: ExtInfApiModel,
+        overrides: &'a [ChannelOverrideModel],
+        provider: &'a ProviderConfig,
+    ) -> Self {
+        let tvg_id = get_attribute(&extinf, "tvg-id");
+        let channel_override = find_override(overrides, tvg_id, Some(&extinf.track_id));
+
+        Channel {
+            extinf,
+            channel_override,
+            provider,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        self.channel_override
+            .and_then(|channel_override| channel_override.name.as_deref())
+            .unwrap_or(&self.extinf.name)
+    }
+
+    pub fn group_title(&self) -> String {
+        if let Some(group_title) = self
+            .channel_override
+            .and_then(|channel_override| channel_override.group_title.as_deref())
+        {
+            return group_title.to_string();
+        }
+
+        let group_title = get_attribute(&self.extinf, "group-title").unwrap_or_default();
+
+        match &self.provider.group_prefix {
+            Some(group_prefix) if !group_title.is_empty() => {
+                format!("{}{}", group_prefix, group_title)
+            }
+            _ => group_title.to_string(),
+        }
+    }
+
+    /// Identifies the channel in stored channel numbers.
+    fn number_key(&self) -> (&str, &str) {
+        (
+            get_attribute(&self.extinf, "tvg-id").unwrap_or_default(),
+            &self.extinf.track_id,
+        )
+    }
+
+    fn position(&self) -> Option<u32> {
+        self.channel_override
+            .and_then(|channel_override| channel_override.position)
+    }
+}
+
+pub fn sort_channels(channels: &mut Vec<Channel>, iptv_config: &IptvConfiguration) {
+    match iptv_config.sort {
+        M3uSort::Provider => (),
+        M3uSort::Name => {
+            channels.sort_by_cached_key(|channel| channel.name().to_lowercase());
+        }
+        M3uSort::Position => {
+            channels.sort_by_key(|channel| (channel.position().is_none(), channel.position()));
+        }
+        M3uSort::Group => {
+            let mut provider_group_order: HashMap<String, usize> = HashMap::new();
+
+            for channel in channels.iter() {
+                let next_index = provider_group_order.len();
+
+                provider_group_order
+                    .entry(channel.group_title().to_lowercase())
+                    .or_insert(next_index);
+            }
+
+            channels.sort_by_cached_key(|channel| {
+                let group_title = channel.group_title().to_lowercase();
+
+                let configured_index = iptv_config
+                    .group_order
+                    .iter()
+                    .position(|group| group.to_lowercase() == group_title)
+                    .unwrap_or(usize::MAX);
+
+                (configured_index, provider_group_order[&group_title])
+            });
+        }
+    }
+}
+
+/// Assigns a `tvg-chno` to each channel. Override numbers are kept as they are. With automatic
+/// numbering, channels keep the number stored for them by earlier playlists, so adding, removing
+/// or moving channels doesn't renumber the others. Channels without a stored number are numbered
+/// in playlist order, from the configured start of their group or from `chno_start`, skipping
+/// numbers that are taken. Returns the numbers along with the new ones that have to be stored.
+pub fn number_channels(
+    channels: &[Channel],
+    stored_numbers: &[ChannelNumberModel],
+    iptv_config: &IptvConfiguration,
+) -> (Vec<Option<String>>, Vec<ChannelNumberRequest>) {
+    let mut chnos: Vec<Option<String>> = channels
+        .iter()
+        .map(|channel| {
+            channel
+                .channel_override
+                .and_then(|channel_override| channel_override.chno.clone())
+        })
+        .collect();
+
+    if !iptv_config.chno_auto {
+        return (chnos, vec![]);
+    }
+
+    let mut used_numbers: HashSet<u32> = chnos
+        .iter()
+        .flatten()
+        .filter_map(|chno| chno.trim().parse().ok())
+        .collect();
+
+    let stored_numbers: HashMap<(&str, &str), u32> = stored_numbers
+        .iter()
+        .map(|number| {
+            (
+                (number.tvg_id.as_str(), number.track_id.as_str()),
+                number.chno,
+            )
+        })
+        .collect();
+
+    for (channel, chno) in channels.iter().zip(chnos.iter_mut()) {
+        if chno.is_some() {
+            continue;
+        }
+
+        if let Some(number) = stored_numbers.get(&channel.number_key()) {
+            if used_numbers.insert(*number) {
+                *chno = Some(number.to_string());
+            }
+        }
+    }
+
+    // Numbers of channels missing from this playlist stay reserved for when they come back
+    used_numbers.extend(stored_numbers.values());
+
+    let mut new_numbers: Vec<ChannelNumberRequest> = vec![];
+
+    let group_starts: HashMap<String, u32> = iptv_config
+        .chno_group_starts
+        .iter()
+        .map(|(group, start)| (group.to_lowercase(), *start))
+        .collect();
+    let mut group_numbers: HashMap<String, u32> = HashMap::new();
+
+    for (channel, chno) in channels.iter().zip(chnos.iter_mut()) {
+        if chno.is_some() {
+            continue;
+        }
+
+        let group_title = channel.group_title().to_lowercase();
+
+        if let Some(start) = group_starts.get(&group_title) {
+            let next_number = group_numbers.entry(group_title).or_insert(*start);
+            let number = take_free_number(next_number, &mut used_numbers);
+
+            *chno = Some(number.to_string());
+            new_numbers.push(compose_number_request(channel, number));
+        }
+    }
+
+    let mut next_number = iptv_config.chno_start.max(1);
+
+    for (channel, chno) in channels.iter().zip(chnos.iter_mut()) {
+        if chno.is_some() {
+            continue;
+        }
+
+        let number = take_free_number(&mut next_number, &mut used_numbers);
+
+        *chno = Some(number.to_string());
+        new_numbers.push(compose_number_request(channel, number));
+    }
+
+    (chnos, new_numbers)
+}
+
+fn compose_number_request(channel: &Channel, chno: u32) -> ChannelNumberRequest {
+    let (tvg_id, track_id) = channel.number_key();
+
+    ChannelNumberRequest {
+        tvg_id: tvg_id.to_string(),
+        track_id: track_id.to_string(),
+        chno,
+    }
+}
+
+fn take_free_number(next_number: &mut u32, used_numbers: &mut HashSet<u32>) -> u32 {
+    while !used_numbers.insert(*next_number) {
+        *next_number += 1;
+    }
+
+    *next_number += 1;
+
+    *next_number - 1
+}
+
+pub fn get_attribute<'a>(extinf: &'a ExtInfApiModel, key: &str) -> Option<&'a str> {
+    extinf
+        .attributes
+        .as_ref()?
+        .iter()
+        .find(|attr| attr.key == key)
+        .map(|attr| attr.value.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use db::models::AttributeModel;
+    use serde_json::json;
+
+    use super::*;
+
+    fn iptv_config() -> IptvConfiguration {
+        IptvConfiguration {
+            proxy_domain: String::from("proxy.local"),
+            xtream_username: String::from("user"),
+            xtream_password: String::from("pass"),
+            sort: M3uSort::Provider,
+            group_order: vec![],
+            chno_auto: true,
+            chno_start: 1,
+            chno_group_starts: HashMap::new(),
+        }
+    }
+
+    fn extinf(id: u64, name: &str, group_title: &str) -> ExtInfApiModel {
+        ExtInfApiModel {
+            id,
+            name: name.to_string(),
+            duration: None,
+            url: format!("http://host/live/u/p/{}.ts", id),
+            exclude: false,
+            m3u_id: None,
+            attributes: Some(vec![
+                AttributeModel {
+                    id,
+                    key: String::from("tvg-id"),
+                    value: format!("tvg{}", id),
+                    extinf_id: Some(id),
+                },
+                AttributeModel {
+                    id,
+                    key: String::from("group-title"),
+                    value: group_title.to_string(),
+                    extinf_id: Some(id),
+                },
+            ]),
+            directives: None,
+            track_id: id.to_string(),
+            prefix: Some(String::from("live")),
+            extension: Some(String::from("ts")),
+        }
+    }
+
+    fn channel_override(
+        tvg_id: &str,
+        chno: Option<&str>,
+        position: Option<u32>,
+    ) -> ChannelOverrideModel {
+        serde_json::from_value(json!({
+            "id": 1,
+            "tvg_id": tvg_id,
+            "track_id": null,
+            "name": null,
+            "group_title": null,
+            "chno": chno,
+            "logo": null,
+            "position": position,
+            "created_at": null,
+            "modified_at": null,
+        }))
+        .unwrap()
+    }
+
+    fn names(channels: &[Channel]) -> Vec<String> {
+        channels
+            .iter()
+            .map(|channel| channel.name().to_string())
+            .collect()
+    }
+
+    fn chnos(chnos: &[Option<&str>]) -> Vec<Option<String>> {
+        chnos.iter().map(|chno| chno.map(String::from)).collect()
+    }
+
+    fn stored_numbers(new_numbers: Vec<ChannelNumberRequest>) -> Vec<ChannelNumberModel> {
+        new_numbers
+            .into_iter()
+            .enumerate()
+            .map(|(id, number)| {
+                serde_json::from_value(json!({
+                    "id": id,
+                    "tvg_id": number.tvg_id,
+                    "track_id": number.track_id,
+                    "chno": number.chno,
+                    "created_at": null,
+                    "modified_at": null,
+                }))
+                .unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn sorts_by_name() {
+        let provider = ProviderConfig::default();
+        let mut channels = vec![
+            Channel::new(extinf(1, "zdf", "DE"), &[], &provider),
+            Channel::new(extinf(2, "ARD", "DE"), &[], &provider),
+            Channel::new(extinf(3, "Bbc", "UK"), &[], &provider),
+        ];
+
+        let mut config = iptv_config();
+        config.sort = M3uSort::Name;
+        sort_channels(&mut channels, &config);
+
+        assert_eq!(names(&channels), vec!["ARD", "Bbc", "zdf"]);
+    }
+
+    #[test]
+    fn sorts_by_configured_group_order_then_provider_order() {
+        let provider = ProviderConfig::default();
+        let mut channels = vec![
+            Channel::new(extinf(1, "One", "News"), &[], &provider),
+            Channel::new(extinf(2, "Two", "Sports"), &[], &provider),
+            Channel::new(extinf(3, "Three", "Kids"), &[], &provider),
+            Channel::new(extinf(4, "Four", "News"), &[], &provider),
+        ];
+
+        let mut config = iptv_config();
+        config.sort = M3uSort::Group;
+        config.group_order = vec![String::from("kids")];
+        sort_channels(&mut channels, &config);
+
+        assert_eq!(names(&channels), vec!["Three", "One", "Four", "Two"]);
+    }
+
+    #[test]
+    fn sorts_by_position_with_unpositioned_channels_last() {
+        let provider = ProviderConfig::default();
+        let overrides = vec![
+            channel_override("tvg2", None, Some(1)),
+            channel_override("tvg3", None, Some(0)),
+        ];
+        let mut channels = vec![
+            Channel::new(extinf(1, "One", ""), &overrides, &provider),
+            Channel::new(extinf(2, "Two", ""), &overrides, &provider),
+            Channel::new(extinf(3, "Three", ""), &overrides, &provider),
+        ];
+
+        let mut config = iptv_config();
+        config.sort = M3uSort::Position;
+        sort_channels(&mut channels, &config);
+
+        assert_eq!(names(&channels), vec!["Three", "Two", "One"]);
+    }
+
+    #[test]
+    fn keeps_only_override_numbers_without_auto_numbering() {
+        let provider = ProviderConfig::default();
+        let overrides = vec![channel_override("tvg2", Some("7"), None)];
+        let channels = vec![
+            Channel::new(extinf(1, "One", ""), &overrides, &provider),
+            Channel::new(extinf(2, "Two", ""), &overrides, &provider),
+        ];
+
+        let mut config = iptv_config();
+        config.chno_auto = false;
+
+        assert_eq!(
+            number_channels(&channels, &[], &config).0,
+            chnos(&[None, Some("7")])
+        );
+    }
+
+    #[test]
+    fn skips_numbers_taken_by_overrides() {
+        let provider = ProviderConfig::default();
+        let overrides = vec![channel_override("tvg3", Some("2"), None)];
+        let channels = vec![
+            Channel::new(extinf(1, "One", ""), &overrides, &provider),
+            Channel::new(extinf(2, "Two", ""), &overrides, &provider),
+            Channel::new(extinf(3, "Three", ""), &overrides, &provider),
+        ];
+
+        assert_eq!(
+            number_channels(&channels, &[], &iptv_config()).0,
+            chnos(&[Some("1"), Some("3"), Some("2")])
+        );
+    }
+
+    #[test]
+    fn skips_numbers_taken_by_group_starts() {
+        let provider = ProviderConfig::default();
+        let overrides = vec![channel_override("tvg4", Some("3"), None)];
+        let channels = vec![
+            Channel::new(extinf(1, "One", "News"), &overrides, &provider),
+            Channel::new(extinf(2, "Two", "News"), &overrides, &provider),
+            Channel::new(extinf(3, "Three", "Sports"), &overrides, &provider),
+            Channel::new(extinf(4, "Four", "Sports"), &overrides, &provider),
+            Channel::new(extinf(5, "Five", "Sports"), &overrides, &provider),
+            Channel::new(extinf(6, "Six", "News"), &overrides, &provider),
+        ];
+
+        let mut config = iptv_config();
+        config.chno_group_starts = HashMap::from([(String::from("SPORTS"), 2)]);
+
+        assert_eq!(
+            number_channels(&channels, &[], &config).0,
+            chnos(&[
+                Some("1"),
+                Some("5"),
+                Some("2"),
+                Some("3"),
+                Some("4"),
+                Some("6"),
+            ])
+        );
+    }
+
+    #[test]
+    fn keeps_numbers_of_other_channels_when_a_channel_is_removed() {
+        let provider = ProviderConfig::default();
+        let channels = vec![
+            Channel::new(extinf(1, "One", ""), &[], &provider),
+            Channel::new(extinf(2, "Two", ""), &[], &provider),
+            Channel::new(extinf(3, "Three", ""), &[], &provider),
+        ];
+
+        let (numbers, new_numbers) = number_channels(&channels, &[], &iptv_config());
+
+        assert_eq!(numbers, chnos(&[Some("1"), Some("2"), Some("3")]));
+        assert_eq!(new_numbers.len(), 3);
+
+        let stored = stored_numbers(new_numbers);
+        let channels = vec![
+            Channel::new(extinf(1, "One", ""), &[], &provider),
+            Channel::new(extinf(3, "Three", ""), &[], &provider),
+            Channel::new(extinf(4, "Four", ""), &[], &provider),
+        ];
+
+        let (numbers, new_numbers) = number_channels(&channels, &stored, &iptv_config());
+
+        assert_eq!(numbers, chnos(&[Some("1"), Some("3"), Some("4")]));
+        assert_eq!(
+            new_numbers,
+            vec![ChannelNumberRequest {
+                tvg_id: String::from("tvg4"),
+                track_id: String::from("4"),
+                chno: 4,
+            }]
+        );
+    }
+
+    #[test]
+    fn renumbers_stored_channel_whose_number_is_overridden() {
+        let provider = ProviderConfig::default();
+        let channels = vec![
+            Channel::new(extinf(1, "One", ""), &[], &provider),
+            Channel::new(extinf(2, "Two", ""), &[], &provider),
+        ];
+
+        let (_, new_numbers) = number_channels(&channels, &[], &iptv_config());
+        let stored = stored_numbers(new_numbers);
+
+        let overrides = vec![channel_override("tvg2", Some("1"), None)];
+        let channels = vec![
+            Channel::new(extinf(1, "One", ""), &overrides, &provider),
+            Channel::new(extinf(2, "Two", ""), &overrides, &provider),
+        ];
+
+        let (numbers, new_numbers) = number_channels(&channels, &stored, &iptv_config());
+
+        assert_eq!(numbers, chnos(&[Some("3"), Some("1")]));
+        assert_eq!(new_numbers.len(), 1);
+        assert_eq!(new_numbers[0].chno, 3);
+    }
+}
@@ -1,30 +1,51 @@
-use std::{collections::HashMap, str::Split, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    str::Split,
+    sync::Arc,
+};
 
 use anyhow::{Context, Error};
-use db::{models::GroupRequest, services::provider::ExtInf};
+use db::{
+    models::GroupRequest,
+    services::provider::{ExtInf, ExtInfDirective},
+};
 use log::{debug, error, info, trace};
-use regex::Regex;
 use rest_client::RestClient;
 use tokio::{
-    io::{AsyncBufReadExt, BufReader, Lines},
+    io::{self, AsyncBufRead, AsyncBufReadExt},
     try_join,
 };
 use url::Url;
 
-use crate::models::{ParsedM3u, XtreamCategory, XtreamConfig};
+use crate::models::{FilterRules, ParseReport, ParsedM3u, XtreamCategory, XtreamConfig};
 
-use super::fetcher::get_m3u;
+use super::{fetcher::get_m3u, filter::ChannelFilter};
 
 pub async fn parse_m3u_url(
     url: &Url,
-    group_excludes: &Vec<String>,
+    group_excludes: &[String],
+    group_includes: &[String],
+    filter_rules: &FilterRules,
     xtream_config: XtreamConfig,
     client: Arc<RestClient>,
 ) -> Result<ParsedM3u, Error> {
-    let m3u = get_m3u(&url).await.context("Could not get M3U content")?;
-    let m3u_reader = BufReader::new(m3u.as_bytes()).lines();
+    let channel_filter = ChannelFilter::new(filter_rules).context("Could not load filter rules")?;
 
-    let m3u = match process_lines(m3u_reader, group_excludes, xtream_config, client).await {
+    let m3u_reader = get_m3u(url, client.clone())
+        .await
+        .context("Could not get M3U content")?
+        .split(b'\n');
+
+    let m3u = match process_lines(
+        m3u_reader,
+        group_excludes,
+        group_includes,
+        &channel_filter,
+        xtream_config,
+        client,
+    )
+    .await
+    {
         Ok(extinfs) => extinfs,
         Err(err) => {
             error!("{}", err);
@@ -35,69 +56,175 @@ pub async fn parse_m3u_url(
     Ok(m3u)
 }
 
-async fn process_lines(
-    mut lines: Lines<BufReader<&[u8]>>,
-    group_excludes: &Vec<String>,
+async fn process_lines<R>(
+    mut lines: io::Split<R>,
+    group_excludes: &[String],
+    group_includes: &[String],
+    channel_filter: &ChannelFilter,
     xtream_config: XtreamConfig,
     client: Arc<RestClient>,
-) -> Result<ParsedM3u, Error> {
-    let mut total_line_count = 0;
-    let mut invalid_line_count = 0;
-    let mut invalid_extinf_entry_count = 0;
-
-    let valid_extinf_line = Regex::new(r#"^(#\S+(?:\s+[^\s="]+=".*")+),(.*)\s*(.*)"#).unwrap();
-
+) -> Result<ParsedM3u, Error>
+where
+    R: AsyncBufRead + Unpin,
+{
+    let mut report = ParseReport::default();
+    let mut seen_urls: HashSet<String> = HashSet::new();
+
+    let mut header_attributes: Vec<(String, String)> = vec![];
     let mut parsed_extinf_entries: Vec<ExtInf> = vec![];
     let mut groups: Vec<GroupRequest> = vec![];
 
-    while let Some(line) = lines.next_line().await? {
-        total_line_count += 1;
+    let mut extinf_line: Option<(String, ExtInfTokens)> = None;
+    // Directives since the last url, with their line, which belong to the next extinf entry
+    let mut directives: Vec<(u32, String, ExtInfDirective)> = vec![];
+
+    while let Some(line) = next_line(&mut lines).await? {
+        report.total_lines += 1;
+        let line_number = report.total_lines;
+
+        if line.starts_with("#EXTINF") {
+            let tokens = match tokenize_extinf_line(&line) {
+                Ok(tokens) => tokens,
+                Err(err) => {
+                    debug!("\nInvalid line {} ignored: {}\n{}", line_number, err, line);
+                    report.add_invalid_line(line_number, err, &line);
+
+                    // The url and directives of the invalid entry must not end up on another one
+                    if let Some((previous_line, _)) = extinf_line.take() {
+                        report.invalid_extinf_entries += 1;
+                        debug!("\nSkipped extinf entry without url\n{}", previous_line);
+                    }
+
+                    directives.clear();
+                    continue;
+                }
+            };
+
+            for diagnostic in &tokens.diagnostics {
+                debug!("\nLine {}: {}\n{}", line_number, diagnostic, line);
+                report.add_diagnostic(line_number, diagnostic.clone(), &line);
+            }
+
+            if let Some((previous_line, _)) = extinf_line.replace((line, tokens)) {
+                report.invalid_extinf_entries += 1;
+                directives.clear();
+                debug!("\nSkipped extinf entry without url\n{}", previous_line);
+            }
+
+            continue;
+        }
+
+        if let Some(header) = line.strip_prefix("#EXTM3U") {
+            let mut tokenizer = Tokenizer::new(header);
+
+            for (key, value) in tokenizer.attributes() {
+                match header_attributes
+                    .iter_mut()
+                    .find(|(existing, _)| *existing == key)
+                {
+                    Some(attribute) => attribute.1 = value,
+                    None => header_attributes.push((key, value)),
+                }
+            }
+
+            for diagnostic in tokenizer.diagnostics {
+                debug!("\nLine {}: {}\n{}", line_number, diagnostic, line);
+                report.add_diagnostic(line_number, diagnostic, &line);
+            }
+
+            continue;
+        }
+
+        if let Some(directive) = parse_directive(&line) {
+            directives.push((line_number, line, directive));
+            continue;
+        }
 
         if !is_valid_line(&line) {
-            invalid_line_count += 1;
             debug!("\nInvalid line ignored\n{}", line);
+            report.add_invalid_line(line_number, String::from("unsupported line"), &line);
             continue;
         }
 
-        if valid_extinf_line.is_match(&line) {
-            let attributes = parse_attributes(&line);
-            let group_title = get_group_title(&attributes);
-
-            let exclude = should_be_excluded(&group_title, &group_excludes);
-
-            if let Some(url) = lines.next_line().await.context("Could not read line")? {
-                if let Ok(url) = Url::parse(&url) {
-                    let path_segments = get_path_segments(&url);
-                    let last_segment = get_last_path_segment(&path_segments);
-
-                    parsed_extinf_entries.push(ExtInf {
-                        name: parse_name(&line),
-                        attributes,
-                        url: url.clone(),
-                        track_id: parse_track_id(&last_segment),
-                        prefix: parse_prefix(&path_segments),
-                        extension: parse_extension(last_segment),
-                        group_title: group_title.clone(),
-                        exclude,
-                    });
-
-                    if !is_duplicate_group(&groups, group_title.clone()) {
-                        groups.push(GroupRequest {
-                            name: group_title,
-                            exclude,
-                            xtream_cat_id: None,
-                            m3u_id: None,
-                        });
-                    }
+        let url = line;
+        let (line, tokens) = match extinf_line.take() {
+            Some(extinf_line) => extinf_line,
+            None => {
+                debug!("\nUrl without extinf entry ignored\n{}", url);
+                report.add_invalid_line(
+                    line_number,
+                    String::from("url without extinf entry"),
+                    &url,
+                );
+                directives.clear();
+                continue;
+            }
+        };
 
-                    trace!("\r\nSuccessfully parsed extinf\r\n{}\r\n{}", line, url);
-                } else {
-                    invalid_extinf_entry_count += 1;
-                    invalid_line_count += 1;
-                    debug!("\nSkipped invalid extinf entry\n{}\n{}", line.as_str(), url);
-                    continue;
-                };
+        let attributes = tokens.attributes;
+        let directives = directives
+            .drain(..)
+            .map(|(_, _, directive)| directive)
+            .collect::<Vec<ExtInfDirective>>();
+        let group_title = get_group_title(&attributes, &directives);
+
+        let exclude = should_be_excluded(&group_title, group_excludes, group_includes);
+
+        if let Ok(url) = Url::parse(&url) {
+            if !seen_urls.insert(url.to_string()) {
+                report.duplicate_channels.push(tokens.name.clone());
+            }
+
+            let path_segments = get_path_segments(&url);
+            let last_segment = get_last_path_segment(&path_segments);
+
+            let mut extinf = ExtInf {
+                name: tokens.name,
+                duration: tokens.duration,
+                attributes,
+                directives,
+                url: url.clone(),
+                track_id: parse_track_id(&last_segment),
+                prefix: parse_prefix(&path_segments),
+                extension: parse_extension(last_segment),
+                group_title: group_title.clone(),
+                exclude,
+            };
+
+            extinf.exclude = exclude || channel_filter.is_excluded(&extinf);
+
+            if extinf.exclude {
+                report.excluded_channels += 1;
             }
+
+            match groups.iter_mut().find(|group| group.name == group_title) {
+                Some(group) => group.exclude = group.exclude && extinf.exclude,
+                None => groups.push(GroupRequest {
+                    name: group_title,
+                    exclude: extinf.exclude,
+                    xtream_cat_id: None,
+                    m3u_id: None,
+                }),
+            }
+
+            parsed_extinf_entries.push(extinf);
+
+            trace!("\r\nSuccessfully parsed extinf\r\n{}\r\n{}", line, url);
+        } else {
+            report.invalid_extinf_entries += 1;
+            debug!("\nSkipped invalid extinf entry\n{}\n{}", line.as_str(), url);
+            report.add_invalid_line(line_number, String::from("invalid url"), &url);
+        };
+    }
+
+    if extinf_line.is_none() {
+        for (line_number, line, _) in directives {
+            debug!("\nDirective without extinf entry ignored\n{}", line);
+            report.add_invalid_line(
+                line_number,
+                String::from("directive without extinf entry"),
+                &line,
+            );
         }
     }
 
@@ -105,20 +232,38 @@ async fn process_lines(
         groups = try_set_category_ids(groups, xtream_config, client).await?;
     }
 
-    log_lines_info(
-        invalid_extinf_entry_count,
-        invalid_line_count,
-        total_line_count,
-    );
+    report.groups = groups.iter().map(|group| group.name.clone()).collect();
+    report.excluded_groups = groups.iter().filter(|group| group.exclude).count() as u32;
+
+    log_lines_info(&report);
 
     let res = ParsedM3u {
+        header_attributes,
         extinfs: parsed_extinf_entries,
         groups,
+        report,
     };
 
     Ok(res)
 }
 
+async fn next_line<R>(lines: &mut io::Split<R>) -> Result<Option<String>, Error>
+where
+    R: AsyncBufRead + Unpin,
+{
+    let line = lines
+        .next_segment()
+        .await
+        .context("reading line from M3U stream")?
+        .map(|segment| {
+            String::from_utf8_lossy(&segment)
+                .trim_end_matches('\r')
+                .to_string()
+        });
+
+    Ok(line)
+}
+
 async fn try_set_category_ids(
     mut groups: Vec<GroupRequest>,
     xtream_config: XtreamConfig,
@@ -140,26 +285,14 @@ async fn try_set_category_ids(
     Ok(groups)
 }
 
-fn is_duplicate_group(groups: &Vec<GroupRequest>, group_title: String) -> bool {
-    if groups
-        .clone()
-        .into_iter()
-        .any(|group| group.name == group_title)
-    {
-        true
-    } else {
-        false
-    }
-}
-
-fn get_path_segments(url: &Url) -> Split<char> {
+fn get_path_segments(url: &Url) -> Split<'_, char> {
     url.path_segments().ok_or("no segments").unwrap()
 }
 
 fn get_last_path_segment(segments: &Split<char>) -> String {
     segments
         .clone()
-        .last()
+        .next_back()
         .ok_or("no items")
         .map(String::from)
         .unwrap_or_default()
@@ -168,7 +301,7 @@ fn get_last_path_segment(segments: &Split<char>) -> String {
 fn parse_prefix(segments: &Split<char>) -> Option<String> {
     let first_segment = segments.clone().next().unwrap_or_default();
 
-    let valid_prefix = vec!["live", "movie", "series"];
+    let valid_prefix = ["live", "movie", "series"];
 
     let is_valid_prefix = valid_prefix
         .iter()
@@ -181,16 +314,16 @@ fn parse_prefix(segments: &Split<char>) -> Option<String> {
     Some(String::from(first_segment))
 }
 
-pub fn parse_track_id(last_segment: &String) -> Option<String> {
+pub fn parse_track_id(last_segment: &str) -> Option<String> {
     if last_segment.is_empty() {
         return None;
     }
 
     if last_segment.contains(".") {
-        return Some(last_segment.split('.').nth(0).unwrap().to_string());
+        return Some(last_segment.split('.').next().unwrap().to_string());
     }
 
-    Some(last_segment.clone())
+    Some(last_segment.to_string())
 }
 
 pub fn parse_extension(last_segment: String) -> Option<String> {
@@ -205,52 +338,243 @@ pub fn parse_extension(last_segment: String) -> Option<String> {
     Some(last_segment.split('.').nth(1).unwrap().to_string())
 }
 
-fn get_group_title(attributes: &HashMap<String, String>) -> String {
+fn get_group_title(attributes: &HashMap<String, String>, directives: &[ExtInfDirective]) -> String {
     attributes
         .get_key_value("group-title")
         .map(|value| value.1.clone())
+        .filter(|group_title| !group_title.is_empty())
+        .or_else(|| {
+            directives
+                .iter()
+                .find(|directive| directive.tag == "EXTGRP")
+                .map(|directive| directive.value.clone())
+        })
         .unwrap_or_default()
 }
 
-fn is_valid_line(line: &String) -> bool {
-    let valid_line_starters = vec!["#EXTINF", "#EXTM3U"];
+fn is_valid_line(line: &str) -> bool {
+    !line.trim().is_empty() && !line.starts_with('#')
+}
 
-    valid_line_starters
-        .iter()
-        .any(|valid_start| line.starts_with(valid_start))
+fn parse_directive(line: &str) -> Option<ExtInfDirective> {
+    let line = line.strip_prefix('#')?;
+    let (tag, value) = line.split_once(':').unwrap_or((line, ""));
+
+    let is_valid_tag = !tag.is_empty()
+        && tag
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '-' || c == '_');
+
+    if !is_valid_tag {
+        return None;
+    }
+
+    Some(ExtInfDirective {
+        tag: tag.to_string(),
+        value: value.trim().to_string(),
+    })
 }
 
-fn parse_name(extinf_line: &str) -> String {
-    extinf_line
-        .split("\",")
-        .last()
-        .unwrap_or_default()
-        .to_string()
+#[derive(Debug, Clone, Default, PartialEq)]
+struct ExtInfTokens {
+    duration: String,
+    attributes: HashMap<String, String>,
+    name: String,
+    diagnostics: Vec<String>,
 }
 
-fn parse_attributes(extinf_line: &str) -> HashMap<String, String> {
-    let key_value_pairs = Regex::new(r#"[^\s"]+(?:"[^"]*")"#).unwrap();
+fn tokenize_extinf_line(line: &str) -> Result<ExtInfTokens, String> {
+    let rest = line
+        .strip_prefix("#EXTINF:")
+        .ok_or_else(|| String::from("missing #EXTINF: prefix"))?;
 
-    let attributes: HashMap<_, _> = key_value_pairs
-        .captures_iter(extinf_line)
-        .map(|cap| {
-            let key = cap[0].split("=").next().unwrap().to_owned();
-            let value = cap[0].split("\"").nth(1).unwrap().to_owned();
+    let mut tokenizer = Tokenizer::new(rest);
 
-            (key, value)
-        })
-        .collect();
+    tokenizer.skip_whitespace();
 
-    attributes
+    let duration = match tokenizer
+        .rest()
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .next()
+    {
+        Some(duration) if !duration.contains('=') => {
+            tokenizer.take_while(|c| !c.is_whitespace() && c != ',')
+        }
+        _ => "",
+    };
+
+    let duration = if duration.is_empty() {
+        tokenizer
+            .diagnostics
+            .push(String::from("missing duration, using -1"));
+        String::from("-1")
+    } else {
+        duration.to_string()
+    };
+
+    let attributes = tokenizer.attributes().into_iter().collect();
+
+    if tokenizer.peek() != Some(',') {
+        return Err(String::from("missing ',' before channel name"));
+    }
+
+    tokenizer.next();
+    let name = tokenizer.rest().trim().to_string();
+
+    if name.is_empty() {
+        tokenizer
+            .diagnostics
+            .push(String::from("empty channel name"));
+    }
+
+    Ok(ExtInfTokens {
+        duration,
+        attributes,
+        name,
+        diagnostics: tokenizer.diagnostics,
+    })
+}
+
+struct Tokenizer<'a> {
+    input: &'a str,
+    pos: usize,
+    diagnostics: Vec<String>,
+}
+
+impl<'a> Tokenizer<'a> {
+    fn new(input: &'a str) -> Self {
+        Tokenizer {
+            input,
+            pos: 0,
+            diagnostics: vec![],
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+
+        Some(c)
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    fn take_while<P>(&mut self, predicate: P) -> &'a str
+    where
+        P: Fn(char) -> bool,
+    {
+        let start = self.pos;
+
+        while let Some(c) = self.peek() {
+            if !predicate(c) {
+                break;
+            }
+
+            self.pos += c.len_utf8();
+        }
+
+        &self.input[start..self.pos]
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.take_while(char::is_whitespace);
+    }
+
+    /// Attributes in the order they appear in the input.
+    fn attributes(&mut self) -> Vec<(String, String)> {
+        let mut attributes: Vec<(String, String)> = vec![];
+
+        loop {
+            self.skip_whitespace();
+
+            match self.peek() {
+                None | Some(',') => break,
+                _ => (),
+            }
+
+            let key = self.take_while(|c| !c.is_whitespace() && c != '=' && c != ',');
+
+            if self.peek() != Some('=') {
+                self.diagnostics
+                    .push(format!("attribute {} has no value, ignored", key));
+                continue;
+            }
+
+            self.next();
+
+            let value = match self.peek() {
+                Some(quote) if quote == '"' || quote == '\'' => {
+                    self.next();
+
+                    match self.rest().find(quote) {
+                        Some(end) => {
+                            let value = &self.rest()[..end];
+                            self.pos += end + quote.len_utf8();
+
+                            value
+                        }
+                        None => {
+                            self.diagnostics.push(format!(
+                                "unterminated quote in attribute {}, read as unquoted",
+                                key
+                            ));
+
+                            self.take_while(|c| !c.is_whitespace() && c != ',')
+                        }
+                    }
+                }
+                _ => self.take_while(|c| !c.is_whitespace() && c != ','),
+            };
+
+            if key.is_empty() {
+                self.diagnostics
+                    .push(format!("value {} without attribute key, ignored", value));
+                continue;
+            }
+
+            match attributes.iter_mut().find(|(existing, _)| existing == key) {
+                Some(attribute) => {
+                    attribute.1 = value.to_string();
+                    self.diagnostics
+                        .push(format!("duplicate attribute {}, last value kept", key));
+                }
+                None => attributes.push((key.to_string(), value.to_string())),
+            }
+        }
+
+        attributes
+    }
 }
 
-fn should_be_excluded(group_title: &String, group_excludes: &Vec<String>) -> bool {
+fn should_be_excluded(
+    group_title: &str,
+    group_excludes: &[String],
+    group_includes: &[String],
+) -> bool {
+    if !group_includes.is_empty() {
+        let include = group_includes.iter().any(|include| {
+            group_title
+                .to_ascii_lowercase()
+                .contains(&include.to_lowercase())
+        });
+
+        if !include {
+            return true;
+        }
+    }
+
     if group_title.is_empty() {
         return false;
     }
 
     let exclude = group_excludes.iter().any(|exclude| {
-        (*group_title)
+        group_title
             .to_ascii_lowercase()
             .contains(&exclude.to_lowercase())
     });
@@ -258,15 +582,25 @@ fn should_be_excluded(group_title: &String, group_excludes: &Vec<String>) -> boo
     exclude
 }
 
-fn log_lines_info(invalid_extinf_entry_count: i32, invalid_line_count: i32, total_line_count: i32) {
+fn log_lines_info(report: &ParseReport) {
     info!(
         "Ignored {} invalid extinf entries",
-        invalid_extinf_entry_count
+        report.invalid_extinf_entries
     );
 
     info!(
         "Ignored {} invalid lines out of a total of {} lines",
-        invalid_line_count, total_line_count
+        report.invalid_lines, report.total_lines
+    );
+
+    info!(
+        "Found {} duplicate channels",
+        report.duplicate_channels.len()
+    );
+
+    info!(
+        "Found {} recoverable problems in parsed lines",
+        report.total_diagnostics
     );
 }
 
@@ -274,15 +608,15 @@ async fn try_get_xtream_categories(
     xtream_config: &XtreamConfig,
     client: &Arc<RestClient>,
 ) -> Result<Vec<XtreamCategory>, Error> {
-    let live_categories = get_xtream_category("get_live_categories", &xtream_config, &client);
-    let vod_categories = get_xtream_category("get_vod_categories", &xtream_config, &client);
-    let series_categories = get_xtream_category("get_series_categories", &xtream_config, &client);
+    let live_categories = get_xtream_category("get_live_categories", xtream_config, client);
+    let vod_categories = get_xtream_category("get_vod_categories", xtream_config, client);
+    let series_categories = get_xtream_category("get_series_categories", xtream_config, client);
 
     let (live, vod, series) = try_join!(live_categories, vod_categories, series_categories)?;
 
     let categories = live
         .into_iter()
-        .chain(vod.into_iter().chain(series.into_iter()))
+        .chain(vod.into_iter().chain(series))
         .collect::<Vec<XtreamCategory>>();
 
     Ok(categories)
@@ -309,3 +643,328 @@ async fn get_xtream_category(
 
     Ok(xtream_categories)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn parse(content: &'static str) -> ParsedM3u {
+        let lines = io::BufReader::new(content.as_bytes()).split(b'\n');
+        let channel_filter = ChannelFilter::new(&FilterRules::default()).unwrap();
+
+        process_lines(
+            lines,
+            &[],
+            &[],
+            &channel_filter,
+            XtreamConfig::default(),
+            Arc::new(RestClient::new()),
+        )
+        .await
+        .unwrap()
+    }
+
+    fn directive_tags(extinf: &ExtInf) -> Vec<&str> {
+        extinf
+            .directives
+            .iter()
+            .map(|directive| directive.tag.as_str())
+            .collect()
+    }
+
+    fn attribute<'a>(tokens: &'a ExtInfTokens, key: &str) -> Option<&'a str> {
+        tokens.attributes.get(key).map(String::as_str)
+    }
+
+    #[test]
+    fn tokenizes_quoted_attributes() {
+        let tokens = tokenize_extinf_line(
+            r#"#EXTINF:-1 tvg-id="bbc1.uk" tvg-name="BBC One HD" tvg-logo="http://logo.example/bbc1.png" group-title="UK | Entertainment",BBC One HD"#,
+        )
+        .unwrap();
+
+        assert_eq!(tokens.duration, "-1");
+        assert_eq!(attribute(&tokens, "tvg-id"), Some("bbc1.uk"));
+        assert_eq!(attribute(&tokens, "tvg-name"), Some("BBC One HD"));
+        assert_eq!(
+            attribute(&tokens, "tvg-logo"),
+            Some("http://logo.example/bbc1.png")
+        );
+        assert_eq!(
+            attribute(&tokens, "group-title"),
+            Some("UK | Entertainment")
+        );
+        assert_eq!(tokens.name, "BBC One HD");
+        assert!(tokens.diagnostics.is_empty());
+    }
+
+    #[test]
+    fn tokenizes_unquoted_values() {
+        let tokens =
+            tokenize_extinf_line("#EXTINF:0 tvg-id=cnn.us tvg-chno=202 catchup-days=7,CNN")
+                .unwrap();
+
+        assert_eq!(tokens.duration, "0");
+        assert_eq!(attribute(&tokens, "tvg-id"), Some("cnn.us"));
+        assert_eq!(attribute(&tokens, "tvg-chno"), Some("202"));
+        assert_eq!(attribute(&tokens, "catchup-days"), Some("7"));
+        assert_eq!(tokens.name, "CNN");
+        assert!(tokens.diagnostics.is_empty());
+    }
+
+    #[test]
+    fn tokenizes_single_quoted_values() {
+        let tokens = tokenize_extinf_line(
+            r#"#EXTINF:-1 tvg-name='Canal "Plus"' group-title='FR: Cinéma',Canal+"#,
+        )
+        .unwrap();
+
+        assert_eq!(attribute(&tokens, "tvg-name"), Some(r#"Canal "Plus""#));
+        assert_eq!(attribute(&tokens, "group-title"), Some("FR: Cinéma"));
+        assert_eq!(tokens.name, "Canal+");
+    }
+
+    #[test]
+    fn keeps_commas_inside_quoted_attributes() {
+        let tokens = tokenize_extinf_line(
+            r#"#EXTINF:-1 tvg-name="News, Weather & Sport" group-title="News, Docs",News, Weather & Sport"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            attribute(&tokens, "tvg-name"),
+            Some("News, Weather & Sport")
+        );
+        assert_eq!(attribute(&tokens, "group-title"), Some("News, Docs"));
+        assert_eq!(tokens.name, "News, Weather & Sport");
+    }
+
+    #[test]
+    fn keeps_quote_comma_inside_title() {
+        let tokens =
+            tokenize_extinf_line(r#"#EXTINF:-1 group-title="Movies",The "Best", Movie (2020)"#)
+                .unwrap();
+
+        assert_eq!(attribute(&tokens, "group-title"), Some("Movies"));
+        assert_eq!(tokens.name, r#"The "Best", Movie (2020)"#);
+    }
+
+    #[test]
+    fn keeps_duration_of_vod_entries() {
+        let tokens =
+            tokenize_extinf_line(r#"#EXTINF:5400.5 tvg-id="" group-title="VOD",Movie"#).unwrap();
+
+        assert_eq!(tokens.duration, "5400.5");
+        assert_eq!(attribute(&tokens, "tvg-id"), Some(""));
+    }
+
+    #[test]
+    fn reports_missing_duration() {
+        let tokens =
+            tokenize_extinf_line(r#"#EXTINF:tvg-id="abc" group-title="News",ABC"#).unwrap();
+
+        assert_eq!(tokens.duration, "-1");
+        assert_eq!(attribute(&tokens, "tvg-id"), Some("abc"));
+        assert_eq!(tokens.name, "ABC");
+        assert_eq!(tokens.diagnostics, vec!["missing duration, using -1"]);
+
+        let tokens = tokenize_extinf_line("#EXTINF:,Untitled").unwrap();
+
+        assert_eq!(tokens.duration, "-1");
+        assert_eq!(tokens.name, "Untitled");
+        assert_eq!(tokens.diagnostics, vec!["missing duration, using -1"]);
+    }
+
+    #[test]
+    fn reports_unterminated_quotes() {
+        let tokens =
+            tokenize_extinf_line(r#"#EXTINF:-1 tvg-id="abc group-title=News,ABC News"#).unwrap();
+
+        assert_eq!(attribute(&tokens, "tvg-id"), Some("abc"));
+        assert_eq!(attribute(&tokens, "group-title"), Some("News"));
+        assert_eq!(tokens.name, "ABC News");
+        assert_eq!(
+            tokens.diagnostics,
+            vec!["unterminated quote in attribute tvg-id, read as unquoted"]
+        );
+    }
+
+    #[test]
+    fn reports_attribute_problems() {
+        let tokens =
+            tokenize_extinf_line(r#"#EXTINF:-1 tvg-id="a" radio tvg-id="b" ="orphan",Name"#)
+                .unwrap();
+
+        assert_eq!(attribute(&tokens, "tvg-id"), Some("b"));
+        assert_eq!(
+            tokens.diagnostics,
+            vec![
+                "attribute radio has no value, ignored",
+                "duplicate attribute tvg-id, last value kept",
+                "value orphan without attribute key, ignored",
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_empty_channel_name() {
+        let tokens = tokenize_extinf_line(r#"#EXTINF:-1 tvg-id="abc","#).unwrap();
+
+        assert_eq!(tokens.name, "");
+        assert_eq!(tokens.diagnostics, vec!["empty channel name"]);
+    }
+
+    #[test]
+    fn rejects_line_without_name_separator() {
+        assert_eq!(
+            tokenize_extinf_line(r#"#EXTINF:-1 tvg-id="abc""#),
+            Err(String::from("missing ',' before channel name"))
+        );
+    }
+
+    #[tokio::test]
+    async fn records_diagnostics_in_report() {
+        let m3u = parse(
+            "#EXTM3U\n\
+             #EXTINF:tvg-id=\"abc\",ABC\n\
+             http://host/live/u/p/1.ts\n",
+        )
+        .await;
+
+        assert_eq!(m3u.extinfs.len(), 1);
+        assert_eq!(m3u.report.total_diagnostics, 1);
+        assert_eq!(m3u.report.diagnostics[0].line_number, 2);
+        assert_eq!(
+            m3u.report.diagnostics[0].message,
+            "missing duration, using -1"
+        );
+    }
+
+    #[tokio::test]
+    async fn header_attributes_keep_source_order() {
+        let m3u = parse(
+            "#EXTM3U x-tvg-url=\"http://epg/a.xml\" tvg-shift=\"2\" url-tvg=\"http://epg/b.xml\"\n\
+             #EXTM3U tvg-shift=\"1\" catchup=\"default\"\n",
+        )
+        .await;
+
+        assert_eq!(
+            m3u.header_attributes,
+            vec![
+                (String::from("x-tvg-url"), String::from("http://epg/a.xml")),
+                (String::from("tvg-shift"), String::from("1")),
+                (String::from("url-tvg"), String::from("http://epg/b.xml")),
+                (String::from("catchup"), String::from("default")),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn directives_are_attached_to_their_extinf() {
+        let m3u = parse(
+            "#EXTM3U\n\
+             #EXTINF:-1,One\n\
+             #EXTGRP:News\n\
+             #EXTVLCOPT:http-user-agent=Player/1.0\n\
+             http://host/live/u/p/1.ts\n\
+             #EXTINF:-1,Two\n\
+             http://host/live/u/p/2.ts\n",
+        )
+        .await;
+
+        assert_eq!(m3u.extinfs.len(), 2);
+        assert_eq!(directive_tags(&m3u.extinfs[0]), vec!["EXTGRP", "EXTVLCOPT"]);
+        assert_eq!(
+            m3u.extinfs[0].directives[1].value,
+            "http-user-agent=Player/1.0"
+        );
+        assert_eq!(m3u.extinfs[0].group_title, "News");
+        assert!(m3u.extinfs[1].directives.is_empty());
+    }
+
+    #[tokio::test]
+    async fn directives_of_extinf_without_url_are_dropped() {
+        let m3u = parse(
+            "#EXTINF:-1,Without url\n\
+             #KODIPROP:inputstream.adaptive.license_type=widevine\n\
+             #EXTINF:-1,With url\n\
+             http://host/live/u/p/1.ts\n",
+        )
+        .await;
+
+        assert_eq!(m3u.extinfs.len(), 1);
+        assert_eq!(m3u.extinfs[0].name, "With url");
+        assert!(m3u.extinfs[0].directives.is_empty());
+        assert_eq!(m3u.report.invalid_extinf_entries, 1);
+    }
+
+    #[tokio::test]
+    async fn invalid_extinf_does_not_take_over_previous_entry() {
+        let m3u = parse(
+            "#EXTINF:-1,Without url\n\
+             #EXTVLCOPT:http-user-agent=Player/1.0\n\
+             #EXTINF:-1 tvg-id=\"broken\"\n\
+             http://host/live/u/p/1.ts\n\
+             #EXTINF:-1,Two\n\
+             http://host/live/u/p/2.ts\n",
+        )
+        .await;
+
+        assert_eq!(m3u.extinfs.len(), 1);
+        assert_eq!(m3u.extinfs[0].name, "Two");
+        assert!(m3u.extinfs[0].directives.is_empty());
+        assert_eq!(m3u.report.invalid_extinf_entries, 1);
+        assert_eq!(
+            m3u.report
+                .invalid_line_details
+                .iter()
+                .map(|line| line.reason.as_str())
+                .collect::<Vec<&str>>(),
+            vec![
+                "missing ',' before channel name",
+                "url without extinf entry"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn directives_before_extinf_are_attached_to_it() {
+        let m3u = parse(
+            "#EXTM3U\n\
+             #KODIPROP:inputstream.adaptive.license_type=widevine\n\
+             #EXTINF:-1,One\n\
+             http://host/live/u/p/1.ts\n\
+             #EXTGRP:Sports\n\
+             #EXTVLCOPT:http-referrer=http://host/\n\
+             #EXTINF:-1,Two\n\
+             http://host/live/u/p/2.ts\n",
+        )
+        .await;
+
+        assert_eq!(m3u.extinfs.len(), 2);
+        assert_eq!(directive_tags(&m3u.extinfs[0]), vec!["KODIPROP"]);
+        assert_eq!(directive_tags(&m3u.extinfs[1]), vec!["EXTGRP", "EXTVLCOPT"]);
+        assert_eq!(m3u.extinfs[1].group_title, "Sports");
+        assert_eq!(m3u.report.invalid_lines, 0);
+    }
+
+    #[tokio::test]
+    async fn directives_without_extinf_are_reported() {
+        let m3u = parse(
+            "#EXTM3U\n\
+             #EXTINF:-1,One\n\
+             http://host/live/u/p/1.ts\n\
+             #EXTGRP:Orphan\n",
+        )
+        .await;
+
+        assert_eq!(m3u.extinfs.len(), 1);
+        assert!(m3u.extinfs[0].directives.is_empty());
+        assert_eq!(m3u.report.invalid_lines, 1);
+        assert_eq!(m3u.report.invalid_line_details[0].line_number, 4);
+        assert_eq!(
+            m3u.report.invalid_line_details[0].reason,
+            "directive without extinf entry"
+        );
+    }
+}
@@ -1,8 +1,11 @@
+use anyhow::{bail, Error};
 use itertools::Itertools;
 use std::convert::TryInto;
 
 use crate::models::ParsedM3u;
 
+pub const STREAM_ID_SLOT_SIZE: u64 = 1_000_000_000;
+
 pub fn count_groups(m3u: &ParsedM3u) -> u32 {
     m3u.extinfs
         .iter()
@@ -14,5 +17,78 @@ pub fn count_groups(m3u: &ParsedM3u) -> u32 {
 }
 
 pub fn count_channels(m3u: &ParsedM3u) -> u32 {
-    m3u.extinfs.iter().count().try_into().unwrap_or_default()
+    m3u.extinfs.len().try_into().unwrap_or_default()
+}
+
+pub fn get_stream_host(m3u: &ParsedM3u) -> (String, Option<u16>) {
+    m3u.extinfs
+        .iter()
+        .find_map(|extinf| {
+            extinf
+                .url
+                .host_str()
+                .map(|host| (host.to_string(), extinf.url.port()))
+        })
+        .unwrap_or_default()
+}
+
+/// Moves an upstream id into the id range of a provider slot. Ids of the primary slot keep
+/// their value. Ids that don't fit into a slot are rejected, since they would collide with the
+/// ids of the next slot, the primary slot included.
+pub fn namespace_id(slot: u64, id: u64) -> Result<u64, Error> {
+    if id >= STREAM_ID_SLOT_SIZE {
+        bail!(
+            "id {} of provider slot {} exceeds the slot size of {}",
+            id,
+            slot,
+            STREAM_ID_SLOT_SIZE
+        );
+    }
+
+    Ok(slot * STREAM_ID_SLOT_SIZE + id)
+}
+
+pub fn namespace_stream_id(slot: u64, id: &str) -> Result<String, Error> {
+    match id.parse::<u64>() {
+        Ok(id) => Ok(namespace_id(slot, id)?.to_string()),
+        Err(_) => Ok(id.to_string()),
+    }
+}
+
+pub fn split_stream_id(id: u64) -> (u64, u64) {
+    (id / STREAM_ID_SLOT_SIZE, id % STREAM_ID_SLOT_SIZE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_ids_of_primary_slot() {
+        assert_eq!(namespace_stream_id(0, "1234").unwrap(), "1234");
+        assert_eq!(
+            namespace_id(0, STREAM_ID_SLOT_SIZE - 1).unwrap(),
+            STREAM_ID_SLOT_SIZE - 1
+        );
+        assert!(namespace_id(0, 5_000_000_000).is_err());
+    }
+
+    #[test]
+    fn moves_ids_into_their_slot() {
+        assert_eq!(namespace_stream_id(2, "1234").unwrap(), "2000001234");
+        assert_eq!(split_stream_id(2_000_001_234), (2, 1234));
+        assert_eq!(split_stream_id(1234), (0, 1234));
+    }
+
+    #[test]
+    fn keeps_non_numeric_ids() {
+        assert_eq!(namespace_stream_id(2, "abc").unwrap(), "abc");
+    }
+
+    #[test]
+    fn rejects_ids_that_overflow_their_slot() {
+        assert!(namespace_id(1, STREAM_ID_SLOT_SIZE - 1).is_ok());
+        assert!(namespace_id(1, STREAM_ID_SLOT_SIZE).is_err());
+        assert!(namespace_stream_id(1, "1000000001").is_err());
+    }
 }
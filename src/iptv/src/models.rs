@@ -1,10 +1,76 @@
+use std::collections::HashMap;
+
 use db::{models::GroupRequest, services::provider::ExtInf};
+use log::warn;
+use rest_client::HttpProfile;
 use serde::{Deserialize, Serialize};
+use url::Url;
 
 #[derive(Debug, Clone, Default)]
 pub struct ParsedM3u {
+    pub header_attributes: Vec<(String, String)>,
     pub extinfs: Vec<ExtInf>,
     pub groups: Vec<GroupRequest>,
+    pub report: ParseReport,
+}
+
+const MAX_REPORTED_LINES: usize = 1000;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ParseReport {
+    pub total_lines: u32,
+    pub invalid_lines: u32,
+    pub invalid_extinf_entries: u32,
+    pub excluded_channels: u32,
+    pub excluded_groups: u32,
+    pub groups: Vec<String>,
+    pub duplicate_channels: Vec<String>,
+    pub invalid_line_details: Vec<InvalidLine>,
+    #[serde(default)]
+    pub total_diagnostics: u32,
+    #[serde(default)]
+    pub diagnostics: Vec<LineDiagnostic>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct InvalidLine {
+    pub line_number: u32,
+    pub reason: String,
+    pub content: String,
+}
+
+/// A recoverable problem in a line that was parsed anyway, e.g. an unterminated quote.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LineDiagnostic {
+    pub line_number: u32,
+    pub message: String,
+    pub content: String,
+}
+
+impl ParseReport {
+    pub fn add_invalid_line(&mut self, line_number: u32, reason: String, content: &str) {
+        self.invalid_lines += 1;
+
+        if self.invalid_line_details.len() < MAX_REPORTED_LINES {
+            self.invalid_line_details.push(InvalidLine {
+                line_number,
+                reason,
+                content: content.to_string(),
+            });
+        }
+    }
+
+    pub fn add_diagnostic(&mut self, line_number: u32, message: String, content: &str) {
+        self.total_diagnostics += 1;
+
+        if self.diagnostics.len() < MAX_REPORTED_LINES {
+            self.diagnostics.push(LineDiagnostic {
+                line_number,
+                message,
+                content: content.to_string(),
+            });
+        }
+    }
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -27,22 +93,179 @@ pub struct XtreamConfig {
     pub password: String,
 }
 
-#[derive(PartialEq, Clone, Copy)]
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct ProviderConfigs {
+    #[serde(default)]
+    pub providers: Vec<ProviderConfig>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct ProviderConfig {
+    pub name: String,
+    pub source: String,
+    #[serde(default)]
+    pub group_prefix: Option<String>,
+    #[serde(default)]
+    pub priority: i32,
+    #[serde(default)]
+    pub xtream: Option<XtreamConfig>,
+    #[serde(default)]
+    pub max_connections: Option<u32>,
+    #[serde(default)]
+    pub http: HttpProfile,
+    /// Deprecated, replaced by `http.user_agent`.
+    #[serde(default)]
+    pub user_agent: Option<String>,
+    /// Deprecated, replaced by a `Referer` entry in `http.headers`.
+    #[serde(default)]
+    pub referer: Option<String>,
+
+    #[serde(skip)]
+    pub slot: u64,
+}
+
+impl ProviderConfig {
+    /// Moves the deprecated `user_agent` and `referer` keys into the HTTP profile, unless the
+    /// profile sets them itself.
+    pub fn apply_deprecated_http_keys(&mut self) {
+        if let Some(user_agent) = self.user_agent.take() {
+            warn!(
+                "user_agent of provider {} is deprecated, use http.user_agent",
+                self.name
+            );
+
+            self.http.user_agent.get_or_insert(user_agent);
+        }
+
+        if let Some(referer) = self.referer.take() {
+            warn!(
+                "referer of provider {} is deprecated, use a Referer entry in http.headers",
+                self.name
+            );
+
+            if !self
+                .http
+                .headers
+                .keys()
+                .any(|name| name.eq_ignore_ascii_case("referer"))
+            {
+                self.http.headers.insert(String::from("Referer"), referer);
+            }
+        }
+    }
+
+    /// Hosts whose requests use the provider's HTTP profile: the source, the Xtream domain and
+    /// any hosts listed in the profile.
+    pub fn http_hosts(&self) -> Vec<String> {
+        let source = Url::parse(&self.source).ok();
+        let xtream = self
+            .xtream
+            .as_ref()
+            .and_then(|xtream| Url::parse(&format!("http://{}", xtream.base_domain)).ok());
+
+        source
+            .iter()
+            .chain(xtream.iter())
+            .filter_map(|url| url.host_str().map(str::to_string))
+            .chain(self.http.hosts.iter().cloned())
+            .collect()
+    }
+}
+
+#[derive(PartialEq, Clone, Copy, Default)]
 pub enum M3uType {
     Ts,
     M3u8,
+    #[default]
     Custom,
 }
 
-impl Default for M3uType {
-    fn default() -> Self {
-        M3uType::Custom
-    }
-}
-
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct IptvConfiguration {
     pub proxy_domain: String,
     pub xtream_username: String,
     pub xtream_password: String,
+
+    #[serde(default)]
+    pub sort: M3uSort,
+    #[serde(default)]
+    pub group_order: Vec<String>,
+
+    #[serde(default)]
+    pub chno_auto: bool,
+    #[serde(default)]
+    pub chno_start: u32,
+    #[serde(default)]
+    pub chno_group_starts: HashMap<String, u32>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum M3uSort {
+    #[default]
+    Provider,
+    Group,
+    Name,
+    Position,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct FilterRules {
+    #[serde(default)]
+    pub rules: Vec<FilterRule>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct FilterRule {
+    pub action: FilterAction,
+
+    #[serde(rename = "match")]
+    pub condition: FilterCondition,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FilterAction {
+    Include,
+    Exclude,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum FilterCondition {
+    All {
+        all: Vec<FilterCondition>,
+    },
+    Any {
+        any: Vec<FilterCondition>,
+    },
+    Not {
+        not: Box<FilterCondition>,
+    },
+    Field {
+        field: FilterField,
+
+        #[serde(flatten)]
+        matcher: FilterMatcher,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterField {
+    Name,
+    Group,
+    TvgId,
+    TvgCountry,
+    UrlPrefix,
+    Url,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterMatcher {
+    Equals(String),
+    Contains(String),
+    StartsWith(String),
+    Regex(String),
 }
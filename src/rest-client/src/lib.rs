@@ -1,11 +1,100 @@
+use std::{collections::HashMap, time::Duration};
+
+use anyhow::Context;
 use futures_util::Stream;
-use log::error;
-use reqwest::{header::HeaderMap, Client, ClientBuilder, Error, Method, Response, Url};
+use log::{error, warn};
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue, COOKIE, USER_AGENT},
+    Client, ClientBuilder, Error, Method, Proxy, RequestBuilder, Response, Url,
+};
+use serde::Deserialize;
 use warp::hyper::body::Bytes;
 
+/// HTTP settings a provider requires for its upstream requests.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct HttpProfile {
+    #[serde(default)]
+    pub user_agent: Option<String>,
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    #[serde(default)]
+    pub cookies: HashMap<String, String>,
+    #[serde(default)]
+    pub proxy: Option<String>,
+    #[serde(default)]
+    pub connect_timeout: Option<u64>,
+    #[serde(default)]
+    pub timeout: Option<u64>,
+    #[serde(default)]
+    pub hosts: Vec<String>,
+}
+
+impl HttpProfile {
+    pub fn is_empty(&self) -> bool {
+        *self == HttpProfile::default()
+    }
+
+    pub fn compose_headers(&self) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+
+        for (name, value) in self.headers.iter() {
+            match (
+                HeaderName::from_bytes(name.as_bytes()),
+                HeaderValue::from_str(value),
+            ) {
+                (Ok(name), Ok(value)) => {
+                    headers.insert(name, value);
+                }
+                _ => error!("Ignoring invalid header {}: {}", name, value),
+            }
+        }
+
+        if let Some(user_agent) = &self.user_agent {
+            match HeaderValue::from_str(user_agent) {
+                Ok(user_agent) => {
+                    headers.insert(USER_AGENT, user_agent);
+                }
+                Err(_) => error!("Ignoring invalid user agent {}", user_agent),
+            }
+        }
+
+        if !self.cookies.is_empty() {
+            let cookies = self
+                .cookies
+                .iter()
+                .map(|(name, value)| format!("{}={}", name, value))
+                .collect::<Vec<String>>()
+                .join("; ");
+
+            match HeaderValue::from_str(&cookies) {
+                Ok(cookies) => {
+                    headers.insert(COOKIE, cookies);
+                }
+                Err(_) => error!("Ignoring invalid cookies {}", cookies),
+            }
+        }
+
+        headers
+    }
+}
+
 #[derive(Clone)]
 pub struct RestClient {
     client: Client,
+    profiles: Vec<ProfileClient>,
+}
+
+#[derive(Clone)]
+struct ProfileClient {
+    hosts: Vec<String>,
+    client: Client,
+    timeout: Option<Duration>,
+}
+
+impl Default for RestClient {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl RestClient {
@@ -15,7 +104,88 @@ impl RestClient {
             .build()
             .expect("REST client created");
 
-        Self { client }
+        Self {
+            client,
+            profiles: vec![],
+        }
+    }
+
+    /// Creates a client that sends requests to the given hosts with the settings of their
+    /// profile. Requests to any other host use the default settings.
+    ///
+    /// Profiles are configured per provider and looked up by host here, since requests such as
+    /// HLS segments and followed redirects only know their URL, not the provider they belong to.
+    pub fn with_profiles(profiles: Vec<(Vec<String>, HttpProfile)>) -> Result<Self, anyhow::Error> {
+        let mut client = Self::new();
+        let mut host_profiles: HashMap<String, HttpProfile> = HashMap::new();
+
+        for (hosts, profile) in profiles {
+            let hosts = hosts
+                .iter()
+                .map(|host| host.to_lowercase())
+                .collect::<Vec<String>>();
+
+            for host in &hosts {
+                match host_profiles.get(host) {
+                    Some(existing) if *existing != profile => warn!(
+                        "Host {} is in several differing http profiles, its requests use the first one",
+                        host
+                    ),
+                    Some(_) => (),
+                    None => {
+                        host_profiles.insert(host.clone(), profile.clone());
+                    }
+                }
+            }
+
+            let mut builder = ClientBuilder::new()
+                .http1_only()
+                .default_headers(profile.compose_headers());
+
+            if let Some(proxy) = &profile.proxy {
+                builder = builder.proxy(
+                    Proxy::all(proxy).context(format!("parsing http profile proxy {}", proxy))?,
+                );
+            }
+
+            if let Some(connect_timeout) = profile.connect_timeout {
+                builder = builder.connect_timeout(Duration::from_secs(connect_timeout));
+            }
+
+            client.profiles.push(ProfileClient {
+                hosts,
+                client: builder.build().context("building http profile client")?,
+                timeout: profile.timeout.map(Duration::from_secs),
+            });
+        }
+
+        Ok(client)
+    }
+
+    fn get_profile(&self, url: &Url) -> Option<&ProfileClient> {
+        let host = url.host_str()?.to_lowercase();
+
+        self.profiles
+            .iter()
+            .find(|profile| profile.hosts.contains(&host))
+    }
+
+    fn compose_request(&self, method: Method, url: &Url) -> RequestBuilder {
+        match self.get_profile(url) {
+            Some(profile) => profile.client.request(method, url.to_string()),
+            None => self.client.request(method, url.to_string()),
+        }
+    }
+
+    /// Like `compose_request`, bound by the profile's total timeout. Not used for proxied
+    /// streams or streamed downloads, which are only bound by the connect timeout.
+    fn compose_timed_request(&self, url: &Url) -> RequestBuilder {
+        let request = self.compose_request(Method::GET, url);
+
+        match self.get_profile(url).and_then(|profile| profile.timeout) {
+            Some(timeout) => request.timeout(timeout),
+            None => request,
+        }
     }
 
     pub async fn request(
@@ -25,8 +195,7 @@ impl RestClient {
         headers: HeaderMap,
     ) -> Result<Response, Error> {
         let res = self
-            .client
-            .request(method, url)
+            .compose_request(method, &url)
             .headers(headers)
             .send()
             .await?;
@@ -34,15 +203,14 @@ impl RestClient {
         Ok(res)
     }
     pub async fn get(&self, url: &Url) -> Result<Response, Error> {
-        let resp = self.client.get(url.to_string()).send().await;
+        let resp = self.compose_timed_request(url).send().await;
 
         resp
     }
 
     pub async fn get_bytes(&self, url: &Url) -> Result<Bytes, Error> {
         let resp = self
-            .client
-            .get(url.to_string())
+            .compose_timed_request(url)
             .send()
             .await?
             .bytes()
@@ -56,8 +224,7 @@ impl RestClient {
         url: &Url,
     ) -> Result<impl Stream<Item = Result<Bytes, Error>>, anyhow::Error> {
         let resp = self
-            .client
-            .get(url.to_string())
+            .compose_request(Method::GET, url)
             .send()
             .await?
             .bytes_stream();
@@ -66,7 +233,7 @@ impl RestClient {
     }
 
     pub async fn get_string(&self, url: &Url) -> String {
-        let res = match self.client.get(url.to_string()).send().await {
+        let res = match self.compose_timed_request(url).send().await {
             Ok(res) => res.text().await.unwrap_or_default(),
             Err(err) => {
                 error!("Error getting string from request: {}", err.to_string());
@@ -1,6 +1,6 @@
 use std::sync::Arc;
 
-use anyhow::bail;
+use anyhow::{anyhow, bail};
 use api::{
     handlers::{
         m3u::m3u_file_exist,
@@ -11,10 +11,12 @@ use api::{
 use chrono::{Duration, NaiveDateTime, Utc};
 use db::DB;
 use db::{models::ProviderModel, services::provider::ProviderDBService};
-use iptv::{m3u::builder::create_m3u_file, models::IptvConfiguration};
+use iptv::{
+    m3u::builder::create_m3u_file,
+    models::{IptvConfiguration, ProviderConfig},
+};
 use log::{debug, error, info};
 use rest_client::RestClient;
-use url::Url;
 use warp::hyper::StatusCode;
 
 use crate::{
@@ -29,14 +31,7 @@ pub async fn init_app(
     db: Arc<DB>,
     client: Arc<RestClient>,
 ) {
-    if is_existing_provider(&config.m3u, db.clone(), client.clone()).await {
-        try_provider_update(config, api_config, iptv_config, db.clone(), client.clone()).await;
-    } else {
-        info!("Creating new provider..");
-        let provider_id = create_new_provider(&config.m3u, api_config, db.clone(), client).await;
-
-        create_m3u(provider_id, iptv_config, db.clone()).await;
-    }
+    try_provider_update(config, api_config, iptv_config, db.clone(), client.clone()).await;
 }
 
 pub async fn try_provider_update(
@@ -46,22 +41,56 @@ pub async fn try_provider_update(
     db: Arc<DB>,
     client: Arc<RestClient>,
 ) {
-    let provider = get_provider(&config.m3u, db.clone(), client.clone())
-        .await
-        .unwrap_or_default();
+    let mut refreshed = false;
+
+    for provider_config in api_config.providers.iter() {
+        if !is_existing_provider(&provider_config.source, db.clone(), client.clone()).await {
+            info!("Creating new provider {}..", provider_config.name);
+            create_new_provider(
+                &provider_config.source,
+                api_config.clone(),
+                db.clone(),
+                client.clone(),
+            )
+            .await;
+
+            refreshed = true;
+            continue;
+        }
 
-    let created_date = get_created_date(provider.created_at);
+        let provider = get_provider(&provider_config.source, db.clone(), client.clone())
+            .await
+            .unwrap_or_default();
 
-    if should_update_provider(created_date, config.hourly_update_frequency)
-        || config.env == Environment::Development
-    {
-        info!("Provider is out of date, refreshing..");
-        let provider_id = create_new_provider(&config.m3u, api_config, db.clone(), client).await;
+        let created_date = get_created_date(provider.created_at);
 
-        create_m3u(provider_id, iptv_config, db.clone()).await;
-    } else {
-        info!("Provider is up to date. Skipping update...");
+        if should_update_provider(created_date, config.hourly_update_frequency)
+            || config.env == Environment::Development
+        {
+            info!(
+                "Provider {} is out of date, refreshing..",
+                provider_config.name
+            );
+            create_new_provider(
+                &provider_config.source,
+                api_config.clone(),
+                db.clone(),
+                client.clone(),
+            )
+            .await;
+
+            refreshed = true;
+        } else {
+            info!(
+                "Provider {} is up to date. Skipping update...",
+                provider_config.name
+            );
+        }
+    }
 
+    if refreshed {
+        create_m3u(api_config.providers, iptv_config, db.clone()).await;
+    } else {
         match m3u_file_exist(Output::Custom)
             .await
             .unwrap_or_default()
@@ -73,35 +102,65 @@ pub async fn try_provider_update(
                 if config.env == Environment::Development {
                     debug!("Creating file anyways since developing..");
 
-                    create_m3u(provider.id, iptv_config.clone(), db.clone()).await;
+                    create_m3u(api_config.providers, iptv_config.clone(), db.clone()).await;
                 }
             }
             _ => {
                 info!("Creating new m3u file..");
-                create_m3u(provider.id, iptv_config.clone(), db.clone()).await;
+                create_m3u(api_config.providers, iptv_config.clone(), db.clone()).await;
             }
         };
     }
 }
 
-async fn create_m3u(provider_id: u64, iptv_config: IptvConfiguration, db: Arc<DB>) {
-    if provider_id > 0 {
-        let mut provider = ProviderDBService::new();
+async fn create_m3u(
+    provider_configs: Vec<ProviderConfig>,
+    iptv_config: IptvConfiguration,
+    db: Arc<DB>,
+) {
+    let mut provider_db_service = ProviderDBService::new();
+
+    provider_db_service.initialize_db(db.clone());
 
-        provider.initialize_db(db);
+    let mut providers = vec![];
 
-        if let Ok(provider) = provider.get_provider(provider_id).await {
-            if let Err(err) = create_m3u_file(provider, iptv_config).await {
-                error!(".m3u file created failed with {}", err)
+    for provider_config in provider_configs {
+        let latest_provider_entry = provider_db_service
+            .get_latest_provider_entry(&provider_config.source)
+            .await;
+
+        let provider = match latest_provider_entry {
+            Some(latest_provider_entry) => {
+                provider_db_service
+                    .clone()
+                    .get_provider(latest_provider_entry.id)
+                    .await
             }
+            None => Err(anyhow!("No provider entry exists")),
+        };
+
+        match provider {
+            Ok(provider) => providers.push((provider_config, provider)),
+            Err(err) => error!(
+                "Could not load provider {} at this time: {}",
+                provider_config.name, err
+            ),
         }
-    } else {
-        error!("Could not create provider at this time")
+    }
+
+    if providers.is_empty() {
+        error!("Could not create provider at this time");
+
+        return;
+    }
+
+    if let Err(err) = create_m3u_file(providers, iptv_config).await {
+        error!(".m3u file created failed with {}", err)
     }
 }
 
 async fn create_new_provider(
-    m3u: &Url,
+    source: &str,
     config: ApiConfiguration,
     db: Arc<DB>,
     client: Arc<RestClient>,
@@ -109,7 +168,7 @@ async fn create_new_provider(
     let response = create_provider(
         CreateProviderRequestApiModel {
             name: None::<String>,
-            source: m3u.to_string(),
+            source: source.to_string(),
         },
         config,
         db.clone(),
@@ -123,8 +182,8 @@ async fn create_new_provider(
     id
 }
 
-async fn is_existing_provider(m3u: &Url, db: Arc<DB>, client: Arc<RestClient>) -> bool {
-    let response = provider_exists(m3u.as_str(), db.clone(), client)
+async fn is_existing_provider(source: &str, db: Arc<DB>, client: Arc<RestClient>) -> bool {
+    let response = provider_exists(source, db.clone(), client)
         .await
         .expect("is exisiting provider successful response");
 
@@ -135,11 +194,11 @@ async fn is_existing_provider(m3u: &Url, db: Arc<DB>, client: Arc<RestClient>) -
 }
 
 async fn get_provider(
-    m3u: &Url,
+    source: &str,
     db: Arc<DB>,
     client: Arc<RestClient>,
 ) -> Result<ProviderModel, anyhow::Error> {
-    let response = get_provider_entries_by_url(m3u.as_str(), db.clone(), client)
+    let response = get_provider_entries_by_url(source, db.clone(), client)
         .await
         .expect("Could not get provider created date");
 
@@ -148,7 +207,7 @@ async fn get_provider(
         .unwrap_or_default();
 
     if let Some(provider) = provider.first() {
-        return Ok(provider.to_owned());
+        Ok(provider.to_owned())
     } else {
         bail!("No provider entry exists")
     }
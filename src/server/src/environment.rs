@@ -1,7 +1,17 @@
 use api::models::{xtream::XtreamConfig, ApiConfiguration};
 use envy::from_env;
-use iptv::models::IptvConfiguration;
-use serde::Deserialize;
+use iptv::{
+    m3u::{
+        fetcher::{compose_source, load_provider_configs, parse_source},
+        filter::load_filter_rules,
+    },
+    models::{FilterRules, IptvConfiguration, M3uSort, ProviderConfig},
+};
+use log::warn;
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use rest_client::HttpProfile;
+use serde::{de::Error, Deserialize, Deserializer};
+use std::collections::HashMap;
 use url::Url;
 
 pub fn init_env() -> Configuration {
@@ -12,18 +22,67 @@ pub fn init_env() -> Configuration {
 }
 
 pub fn map_api_configuration(config: Configuration) -> ApiConfiguration {
+    let xtream = XtreamConfig {
+        xtream_enabled: config.xtream_enabled,
+        xtream_base_domain: config.xtream_base_domain,
+        xtream_username: config.xtream_username,
+        xtream_password: config.xtream_password,
+        xtream_proxied_domain: config.xtream_proxied_domain,
+        xtream_proxied_username: config.xtream_proxied_username,
+        xtream_proxied_password: config.xtream_proxied_password,
+    };
+
+    let default_provider = ProviderConfig {
+        name: String::from("default"),
+        source: compose_source(&config.m3u),
+        group_prefix: None,
+        priority: 0,
+        xtream: Some(xtream.clone().into()),
+        max_connections: config.max_connections,
+        http: HttpProfile {
+            user_agent: config.user_agent,
+            headers: config
+                .referer
+                .map(|referer| HashMap::from([(String::from("Referer"), referer)]))
+                .unwrap_or_default(),
+            ..HttpProfile::default()
+        },
+        user_agent: None,
+        referer: None,
+        slot: 0,
+    };
+
+    let providers = std::iter::once(default_provider)
+        .chain(config.providers)
+        .enumerate()
+        .map(|(slot, provider)| ProviderConfig {
+            slot: slot as u64,
+            ..provider
+        })
+        .collect();
+
     ApiConfiguration {
-        m3u_url: config.m3u,
+        providers,
         group_excludes: config.group_excludes,
-        xtream: XtreamConfig {
-            xtream_enabled: config.xtream_enabled,
-            xtream_base_domain: config.xtream_base_domain,
-            xtream_username: config.xtream_username,
-            xtream_password: config.xtream_password,
-            xtream_proxied_domain: config.xtream_proxied_domain,
-            xtream_proxied_username: config.xtream_proxied_username,
-            xtream_proxied_password: config.xtream_proxied_password,
-        },
+        group_includes: config.group_includes,
+        filter_rules: config.filter_rules,
+        connection_queue_timeout: config.connection_queue_timeout,
+        stream_token_secret: config.stream_token_secret.unwrap_or_else(|| {
+            warn!("STREAM_TOKEN_SECRET is not set, stream urls will stop working after a restart");
+
+            thread_rng()
+                .sample_iter(&Alphanumeric)
+                .take(64)
+                .map(char::from)
+                .collect()
+        }),
+        stream_token_ttl: config.stream_token_ttl,
+        admin_api_key: config.admin_api_key.filter(|key| !key.is_empty()).or_else(|| {
+            warn!("ADMIN_API_KEY is not set, the admin api only accepts api keys stored in the database");
+
+            None
+        }),
+        xtream,
     }
 }
 
@@ -32,6 +91,11 @@ pub fn map_iptv_configuration(config: Configuration) -> IptvConfiguration {
         proxy_domain: config.xtream_proxied_domain.unwrap_or_default(),
         xtream_username: config.xtream_proxied_username,
         xtream_password: config.xtream_proxied_password,
+        sort: config.m3u_sort,
+        group_order: config.group_order,
+        chno_auto: config.chno_auto,
+        chno_start: config.chno_start,
+        chno_group_starts: config.chno_group_starts,
     }
 }
 
@@ -40,9 +104,13 @@ pub struct Configuration {
     #[serde(default = "default_port")]
     pub port: u16,
 
+    #[serde(deserialize_with = "deserialize_source")]
     pub m3u: Url,
     pub database_url: String,
 
+    #[serde(default, deserialize_with = "deserialize_providers")]
+    pub providers: Vec<ProviderConfig>,
+
     #[serde(default = "default_init_app")]
     pub init_app: bool,
 
@@ -55,8 +123,50 @@ pub struct Configuration {
     #[serde(default = "group_excludes")]
     pub group_excludes: Vec<String>,
 
+    #[serde(default = "group_includes")]
+    pub group_includes: Vec<String>,
+
+    #[serde(default, deserialize_with = "deserialize_filter_rules")]
+    pub filter_rules: FilterRules,
+
     pub proxy_domain: String,
 
+    #[serde(default)]
+    pub max_connections: Option<u32>,
+
+    #[serde(default = "connection_queue_timeout")]
+    pub connection_queue_timeout: u64,
+
+    #[serde(default)]
+    pub stream_token_secret: Option<String>,
+
+    #[serde(default = "stream_token_ttl")]
+    pub stream_token_ttl: u64,
+
+    #[serde(default)]
+    pub admin_api_key: Option<String>,
+
+    #[serde(default)]
+    pub user_agent: Option<String>,
+
+    #[serde(default)]
+    pub referer: Option<String>,
+
+    #[serde(default)]
+    pub m3u_sort: M3uSort,
+
+    #[serde(default = "group_order")]
+    pub group_order: Vec<String>,
+
+    #[serde(default = "chno_auto")]
+    pub chno_auto: bool,
+
+    #[serde(default = "chno_start")]
+    pub chno_start: u32,
+
+    #[serde(default, deserialize_with = "deserialize_chno_group_starts")]
+    pub chno_group_starts: HashMap<String, u32>,
+
     #[serde(default = "xtream_enabled")]
     pub xtream_enabled: bool,
 
@@ -79,6 +189,53 @@ pub struct Configuration {
     xtream_proxied_password: String,
 }
 
+fn deserialize_source<'de, D>(deserializer: D) -> Result<Url, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let source = String::deserialize(deserializer)?;
+
+    parse_source(&source).map_err(D::Error::custom)
+}
+
+fn deserialize_providers<'de, D>(deserializer: D) -> Result<Vec<ProviderConfig>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let path = String::deserialize(deserializer)?;
+
+    load_provider_configs(&path).map_err(|err| D::Error::custom(format!("{:#}", err)))
+}
+
+fn deserialize_filter_rules<'de, D>(deserializer: D) -> Result<FilterRules, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let path = String::deserialize(deserializer)?;
+
+    load_filter_rules(&path).map_err(|err| D::Error::custom(format!("{:#}", err)))
+}
+
+fn deserialize_chno_group_starts<'de, D>(deserializer: D) -> Result<HashMap<String, u32>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let group_starts = Vec::<String>::deserialize(deserializer)?;
+
+    group_starts
+        .iter()
+        .map(|group_start| {
+            let (group, start) = group_start.rsplit_once('=').ok_or_else(|| {
+                D::Error::custom(format!("expected GROUP=NUMBER, got {}", group_start))
+            })?;
+
+            let start = start.trim().parse::<u32>().map_err(D::Error::custom)?;
+
+            Ok((group.trim().to_string(), start))
+        })
+        .collect()
+}
+
 fn default_port() -> u16 {
     3001
 }
@@ -99,10 +256,34 @@ fn hourly_update_frequency() -> u16 {
     12
 }
 
+fn connection_queue_timeout() -> u64 {
+    5
+}
+
+fn stream_token_ttl() -> u64 {
+    7 * 24 * 60 * 60
+}
+
 fn group_excludes() -> Vec<String> {
     vec![]
 }
 
+fn group_includes() -> Vec<String> {
+    vec![]
+}
+
+fn group_order() -> Vec<String> {
+    vec![]
+}
+
+fn chno_auto() -> bool {
+    false
+}
+
+fn chno_start() -> u32 {
+    1
+}
+
 fn xtream_base_domain() -> String {
     String::from("")
 }
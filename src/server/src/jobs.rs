@@ -25,14 +25,14 @@ pub(crate) fn init_jobs(
 
     let update_provider_job = create_update_provider_job(
         config.clone(),
-        api_config,
+        api_config.clone(),
         iptv_config,
         db.clone(),
         client.clone(),
     );
     let remove_obsolete_m3u_files = create_remove_obsolete_m3u_files_job();
     let purge_obsolete_provider_entries =
-        create_purge_obsolete_provider_entries(config, db, client);
+        create_purge_obsolete_provider_entries(api_config, db, client);
 
     schedule
         .add(update_provider_job)
@@ -147,7 +147,7 @@ fn create_remove_obsolete_m3u_files_job() -> Job {
 }
 
 fn create_purge_obsolete_provider_entries(
-    config: Configuration,
+    api_config: ApiConfiguration,
     db: Arc<DB>,
     client: Arc<RestClient>,
 ) -> Job {
@@ -156,27 +156,36 @@ fn create_purge_obsolete_provider_entries(
             let db = db.clone();
             let client = client.clone();
 
-            let config = config.clone();
+            let api_config = api_config.clone();
 
             Box::pin(async move {
                 debug!("Running purge obsolete provider entries");
 
-                let response = get_provider_entries_by_url(config.m3u.as_str(), db.clone(), client)
+                for provider_config in api_config.providers {
+                    let response = get_provider_entries_by_url(
+                        &provider_config.source,
+                        db.clone(),
+                        client.clone(),
+                    )
                     .await
                     .expect("Could not get provider created date");
 
-                let mut provider = deserialize_body::<Vec<ProviderModel>>(response)
-                    .await
-                    .unwrap_or_default();
+                    let mut provider = deserialize_body::<Vec<ProviderModel>>(response)
+                        .await
+                        .unwrap_or_default();
 
-                provider.sort_by_key(|x| x.created_at);
+                    provider.sort_by_key(|x| x.created_at);
 
-                let obsolete_provider_entries = provider.split_last().unwrap().1;
+                    let obsolete_provider_entries = match provider.split_last() {
+                        Some((_, obsolete_provider_entries)) => obsolete_provider_entries,
+                        None => continue,
+                    };
 
-                for provider in obsolete_provider_entries {
-                    let _res = delete_provider(provider.id, db.clone())
-                        .await
-                        .unwrap_or_default();
+                    for provider in obsolete_provider_entries {
+                        let _res = delete_provider(provider.id, db.clone())
+                            .await
+                            .unwrap_or_default();
+                    }
                 }
             })
         })
@@ -29,7 +29,16 @@ async fn main() {
     let db = init_db(pool).await;
     let db = Arc::new(db);
 
-    let client = Arc::new(RestClient::new());
+    let http_profiles = api_config
+        .providers
+        .iter()
+        .filter(|provider| !provider.http.is_empty())
+        .map(|provider| (provider.http_hosts(), provider.http.clone()))
+        .collect();
+
+    let client = Arc::new(
+        RestClient::with_profiles(http_profiles).expect("Valid provider http profiles provided"),
+    );
 
     let api = init_api(api_config.clone(), db.clone(), client.clone());
 